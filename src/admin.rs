@@ -0,0 +1,234 @@
+use crate::{
+    keys::{AdminError, ApiKeyInfo, KeyStore, OrgRow},
+    models::ApiError,
+    security::Scope,
+};
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, State},
+    http::{Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tracing::info;
+
+pub const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// `/admin` routes for provisioning orgs and API keys, guarded by
+/// `ADMIN_API_KEY` (sent as `X-Admin-Key`). Without that variable every
+/// admin route answers `403`.
+pub fn router<S>(keys: KeyStore) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let admin_key = std::env::var("ADMIN_API_KEY")
+        .ok()
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+        .map(Arc::<str>::from);
+    Router::new()
+        .route("/orgs", get(list_orgs).post(create_org))
+        .route("/orgs/{org_id}/keys", get(list_keys).post(create_key))
+        .route("/orgs/{org_id}/keys/{key_id}", delete(revoke_key))
+        .route(
+            "/orgs/{org_id}/keys/{key_id}/signing_secret",
+            get(get_signing_secret),
+        )
+        .route_layer(middleware::from_fn_with_state(admin_key, require_admin))
+        .with_state(keys)
+}
+
+async fn require_admin(
+    State(admin_key): State<Option<Arc<str>>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(expected) = admin_key else {
+        return admin_error(StatusCode::FORBIDDEN, "admin_disabled");
+    };
+    let presented = request
+        .headers()
+        .get(ADMIN_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if !bool::from(presented.as_bytes().ct_eq(expected.as_bytes())) {
+        return admin_error(StatusCode::UNAUTHORIZED, "invalid_admin_key");
+    }
+    next.run(request).await
+}
+
+fn admin_error(status: StatusCode, code: &str) -> Response {
+    let payload = ApiError {
+        error: "admin".to_string(),
+        detail: Some(code.to_string()),
+    };
+    (status, Json(payload)).into_response()
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            AdminError::InvalidOrgId => StatusCode::BAD_REQUEST,
+            AdminError::OrgNotFound | AdminError::KeyNotFound => StatusCode::NOT_FOUND,
+            AdminError::OrgExists | AdminError::ReadOnly | AdminError::SigningDisabled => {
+                StatusCode::CONFLICT
+            }
+            AdminError::Store(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        admin_error(status, &self.to_string())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateOrgRequest {
+    org_id: String,
+    #[serde(default)]
+    name: Option<String>,
+    /// A `RATE_LIMIT_PLANS` entry; defaults to the default plan.
+    #[serde(default)]
+    rate_plan: Option<String>,
+}
+
+/// Create an org.
+///
+/// - Method: `POST`
+/// - Path: `/admin/orgs`
+/// - Body: `{ org_id, name?, rate_plan? }`
+/// - Response: `201` `OrgRow`; `409` when the org exists
+async fn create_org(
+    State(keys): State<KeyStore>,
+    Json(payload): Json<CreateOrgRequest>,
+) -> Result<(StatusCode, Json<OrgRow>), AdminError> {
+    crate::metrics::inc_requests("/admin/orgs");
+    let org = keys
+        .create_org(payload.org_id.trim(), payload.name, payload.rate_plan)
+        .await?;
+    info!(target = "hermes.api", org_id = %org.org_id, "admin_org_created");
+    Ok((StatusCode::CREATED, Json(org)))
+}
+
+/// List orgs.
+///
+/// - Method: `GET`
+/// - Path: `/admin/orgs`
+/// - Response: `OrgRow[]`, including orgs that only appear on keys
+async fn list_orgs(State(keys): State<KeyStore>) -> Json<Vec<OrgRow>> {
+    crate::metrics::inc_requests("/admin/orgs");
+    Json(keys.orgs())
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateKeyRequest {
+    /// Defaults to every scope except `accounts:admin`.
+    #[serde(default)]
+    scopes: Option<Vec<String>>,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// A freshly minted key; `key` is never shown again.
+#[derive(Debug, Serialize)]
+struct CreatedKey {
+    key: String,
+    /// Hex HMAC key for signed requests, so the key itself need not be sent.
+    /// Absent when the server has no `API_KEY_PEPPER`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signing_secret: Option<String>,
+    #[serde(flatten)]
+    info: ApiKeyInfo,
+}
+
+/// Mint an API key for an org.
+///
+/// - Method: `POST`
+/// - Path: `/admin/orgs/{org_id}/keys`
+/// - Body: `{ scopes?, expires_at? }`
+/// - Response: `201` `{ key, signing_secret?, key_id, org_id, scopes, disabled, created_at, expires_at? }`
+async fn create_key(
+    State(keys): State<KeyStore>,
+    Path(org_id): Path<String>,
+    Json(payload): Json<CreateKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedKey>), Response> {
+    crate::metrics::inc_requests("/admin/orgs/{org_id}/keys");
+    let scopes = match payload.scopes {
+        None => Scope::DEFAULT.to_vec(),
+        Some(names) => names
+            .iter()
+            .map(|name| Scope::parse(name))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| admin_error(StatusCode::BAD_REQUEST, "unknown_scope"))?,
+    };
+    let (info, key) = keys
+        .mint_key(&org_id, scopes, payload.expires_at)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    info!(target = "hermes.api", org_id = %org_id, key_id = %info.key_id, "admin_key_created");
+    let signing_secret = keys.signing_secret(&info.key_id);
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedKey {
+            key,
+            signing_secret,
+            info,
+        }),
+    ))
+}
+
+/// List an org's keys, without secrets.
+///
+/// - Method: `GET`
+/// - Path: `/admin/orgs/{org_id}/keys`
+/// - Response: `ApiKeyInfo[]` with `last_used_at`
+async fn list_keys(
+    State(keys): State<KeyStore>,
+    Path(org_id): Path<String>,
+) -> Result<Json<Vec<ApiKeyInfo>>, AdminError> {
+    crate::metrics::inc_requests("/admin/orgs/{org_id}/keys");
+    Ok(Json(keys.keys_for(&org_id)?))
+}
+
+/// Revoke a key; it stops authenticating immediately.
+///
+/// - Method: `DELETE`
+/// - Path: `/admin/orgs/{org_id}/keys/{key_id}`
+/// - Response: `ApiKeyInfo` with `disabled: true`
+async fn revoke_key(
+    State(keys): State<KeyStore>,
+    Path((org_id, key_id)): Path<(String, String)>,
+) -> Result<Json<ApiKeyInfo>, AdminError> {
+    crate::metrics::inc_requests("/admin/orgs/{org_id}/keys/{key_id}");
+    let info = keys.revoke(&org_id, &key_id).await?;
+    info!(target = "hermes.api", org_id = %org_id, key_id = %key_id, "admin_key_revoked");
+    Ok(Json(info))
+}
+
+#[derive(Debug, Serialize)]
+struct SigningSecret {
+    key_id: String,
+    signing_secret: String,
+}
+
+/// The signing secret of any key, including ones from `DEMO_API_KEYS` or a
+/// key file that were never minted here.
+///
+/// - Method: `GET`
+/// - Path: `/admin/orgs/{org_id}/keys/{key_id}/signing_secret`
+/// - Response: `{ key_id, signing_secret }`
+async fn get_signing_secret(
+    State(keys): State<KeyStore>,
+    Path((org_id, key_id)): Path<(String, String)>,
+) -> Result<Json<SigningSecret>, AdminError> {
+    crate::metrics::inc_requests("/admin/orgs/{org_id}/keys/{key_id}/signing_secret");
+    let signing_secret = keys.signing_secret_for(&org_id, &key_id)?;
+    info!(target = "hermes.api", org_id = %org_id, key_id = %key_id, "admin_signing_secret_read");
+    Ok(Json(SigningSecret {
+        key_id,
+        signing_secret,
+    }))
+}
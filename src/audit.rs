@@ -0,0 +1,482 @@
+use crate::{
+    models::{ApiError, ListingRequest, ListingResponse},
+    pipeline::PipelineError,
+    security::AuthContext,
+    supabase::{SupabaseClient, SupabaseError},
+};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use uuid::Uuid;
+
+/// The in-memory sink keeps only this many recent events.
+const MEMORY_CAPACITY: usize = 10_000;
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 1000;
+
+/// An externally visible action taken on behalf of an org.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditAction {
+    #[serde(rename = "listing.create")]
+    ListingCreate,
+    #[serde(rename = "listing.continue")]
+    ListingContinue,
+    #[serde(rename = "job.enqueue")]
+    JobEnqueue,
+    /// A live `createOffer` + `publishOffer` against eBay.
+    #[serde(rename = "ebay.publish_offer")]
+    PublishOffer,
+    /// A live update and republish of an offer that already existed for the SKU.
+    #[serde(rename = "ebay.reconcile_offer")]
+    ReconcileOffer,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 5] = [
+        AuditAction::ListingCreate,
+        AuditAction::ListingContinue,
+        AuditAction::JobEnqueue,
+        AuditAction::PublishOffer,
+        AuditAction::ReconcileOffer,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::ListingCreate => "listing.create",
+            AuditAction::ListingContinue => "listing.continue",
+            AuditAction::JobEnqueue => "job.enqueue",
+            AuditAction::PublishOffer => "ebay.publish_offer",
+            AuditAction::ReconcileOffer => "ebay.reconcile_offer",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == raw.trim())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Succeeded,
+    Failed,
+    /// Queued as a job; the job's own events follow once it publishes.
+    Accepted,
+}
+
+/// One entry of the audit trail. Events are only ever appended.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: String,
+    pub at: DateTime<Utc>,
+    pub org_id: String,
+    pub api_key_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub action: AuditAction,
+    /// `METHOD /path` for request events; the pipeline stage for eBay calls.
+    pub endpoint: String,
+    pub sku: String,
+    pub marketplace: String,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offer_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listing_id: Option<String>,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEvent {
+    /// An event for `request`, made by `context`, still to be given an
+    /// outcome with [`AuditEvent::finish`] or [`AuditEvent::accepted`].
+    pub fn new(
+        context: &AuthContext,
+        action: AuditAction,
+        endpoint: &str,
+        request: &ListingRequest,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            at: Utc::now(),
+            org_id: context.org_id.clone(),
+            api_key_id: context.api_key_id.clone(),
+            request_id: context.request_id.clone(),
+            action,
+            endpoint: endpoint.to_string(),
+            sku: request.sku.clone(),
+            marketplace: request.marketplace.ebay_code().to_string(),
+            dry_run: request.dry_run,
+            job_id: None,
+            offer_id: None,
+            listing_id: None,
+            outcome: AuditOutcome::Failed,
+            error: None,
+        }
+    }
+
+    /// Record how a pipeline run ended, with the offer and listing ids it
+    /// produced. Dry runs have no real listing id.
+    pub fn finish(self, result: &Result<ListingResponse, PipelineError>) -> Self {
+        match result {
+            Ok(response) => Self {
+                offer_id: response
+                    .stages
+                    .iter()
+                    .find(|stage| stage.name == "publish_offer")
+                    .and_then(|stage| stage.output.get("offer_id"))
+                    .and_then(|id| id.as_str())
+                    .map(str::to_string),
+                listing_id: (!self.dry_run).then(|| response.listing_id.clone()),
+                outcome: AuditOutcome::Succeeded,
+                ..self
+            },
+            Err(err) => self.failed(err),
+        }
+    }
+
+    pub fn failed(self, err: impl std::fmt::Display) -> Self {
+        Self {
+            outcome: AuditOutcome::Failed,
+            error: Some(err.to_string()),
+            ..self
+        }
+    }
+
+    pub fn accepted(self, job_id: impl ToString) -> Self {
+        Self {
+            outcome: AuditOutcome::Accepted,
+            job_id: Some(job_id.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_ids(self, offer_id: Option<String>, listing_id: Option<String>) -> Self {
+        Self {
+            offer_id,
+            listing_id,
+            outcome: AuditOutcome::Succeeded,
+            ..self
+        }
+    }
+
+    fn matches(&self, org_id: &str, query: &AuditQuery) -> bool {
+        self.org_id == org_id
+            && query.since.is_none_or(|since| self.at >= since)
+            && query.until.is_none_or(|until| self.at < until)
+            && query.action.is_none_or(|action| self.action == action)
+            && query.sku.as_deref().is_none_or(|sku| self.sku == sku)
+    }
+}
+
+/// Filters for [`AuditLog::query`]; events come back newest first.
+#[derive(Debug, Default, Clone)]
+pub struct AuditQuery {
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive; pass the `at` of the last event seen to page back.
+    pub until: Option<DateTime<Utc>>,
+    pub action: Option<AuditAction>,
+    pub sku: Option<String>,
+    pub limit: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("audit file {path}: {message}")]
+    File { path: String, message: String },
+    #[error("audit supabase: {0}")]
+    Supabase(#[from] SupabaseError),
+}
+
+impl IntoResponse for AuditError {
+    fn into_response(self) -> Response {
+        let payload = ApiError {
+            error: "audit_unavailable".to_string(),
+            detail: None,
+        };
+        (StatusCode::SERVICE_UNAVAILABLE, Json(payload)).into_response()
+    }
+}
+
+/// Where events go: kept in process, appended to a JSONL file, or inserted
+/// into the Supabase `audit_log` table.
+enum AuditSink {
+    Memory(Mutex<VecDeque<AuditEvent>>),
+    File {
+        path: PathBuf,
+        /// Serializes appends so lines from concurrent writers never interleave.
+        writing: tokio::sync::Mutex<()>,
+    },
+    Supabase(SupabaseClient),
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    sink: Arc<AuditSink>,
+}
+
+impl AuditLog {
+    /// Pick the sink from `AUDIT_SINK`: `memory` (default), `file` (JSONL at
+    /// `AUDIT_LOG_FILE`, default `audit.jsonl`) or `supabase`. A Supabase
+    /// sink without `SUPABASE_URL` falls back to memory.
+    pub fn from_env() -> Self {
+        let sink = match std::env::var("AUDIT_SINK")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "file" => AuditSink::File {
+                path: std::env::var("AUDIT_LOG_FILE")
+                    .ok()
+                    .filter(|path| !path.trim().is_empty())
+                    .unwrap_or_else(|| "audit.jsonl".to_string())
+                    .into(),
+                writing: tokio::sync::Mutex::new(()),
+            },
+            "supabase" => match SupabaseClient::from_env() {
+                Some(client) => AuditSink::Supabase(client),
+                None => {
+                    warn!(
+                        target = "hermes.api",
+                        "AUDIT_SINK=supabase without SUPABASE_URL; keeping audit events in memory"
+                    );
+                    AuditSink::Memory(Mutex::default())
+                }
+            },
+            _ => AuditSink::Memory(Mutex::default()),
+        };
+        Self {
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// Append `event`. A sink failure is logged rather than failing the
+    /// action being audited, which has already happened.
+    pub async fn record(&self, event: AuditEvent) {
+        if let Err(err) = self.append(&event).await {
+            warn!(
+                target = "hermes.api",
+                org_id = %event.org_id,
+                action = event.action.as_str(),
+                error = %err,
+                "audit_write_failed"
+            );
+        }
+    }
+
+    async fn append(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        match &*self.sink {
+            AuditSink::Memory(events) => {
+                let mut events = events.lock().expect("audit lock");
+                if events.len() >= MEMORY_CAPACITY {
+                    events.pop_front();
+                }
+                events.push_back(event.clone());
+                Ok(())
+            }
+            AuditSink::File { path, writing } => {
+                let io_err = |err: std::io::Error| AuditError::File {
+                    path: path.display().to_string(),
+                    message: err.to_string(),
+                };
+                let mut line = serde_json::to_string(event).expect("audit events serialize");
+                line.push('\n');
+                let _writing = writing.lock().await;
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(io_err)?;
+                file.write_all(line.as_bytes()).await.map_err(io_err)?;
+                file.flush().await.map_err(io_err)
+            }
+            AuditSink::Supabase(client) => Ok(client.insert_row("audit_log", event).await?),
+        }
+    }
+
+    /// An org's events matching `query`, newest first.
+    pub async fn query(
+        &self,
+        org_id: &str,
+        query: &AuditQuery,
+    ) -> Result<Vec<AuditEvent>, AuditError> {
+        let limit = query.limit.clamp(1, MAX_LIMIT);
+        let mut events: Vec<AuditEvent> = match &*self.sink {
+            AuditSink::Memory(events) => events
+                .lock()
+                .expect("audit lock")
+                .iter()
+                .filter(|event| event.matches(org_id, query))
+                .cloned()
+                .collect(),
+            AuditSink::File { path, .. } => {
+                let raw = match tokio::fs::read_to_string(path).await {
+                    Ok(raw) => raw,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+                    Err(err) => {
+                        return Err(AuditError::File {
+                            path: path.display().to_string(),
+                            message: err.to_string(),
+                        });
+                    }
+                };
+                raw.lines()
+                    .filter_map(|line| serde_json::from_str::<AuditEvent>(line).ok())
+                    .filter(|event| event.matches(org_id, query))
+                    .collect()
+            }
+            AuditSink::Supabase(client) => {
+                let mut params = vec![
+                    ("org_id", format!("eq.{org_id}")),
+                    ("order", "at.desc".to_string()),
+                    ("limit", limit.to_string()),
+                ];
+                if let Some(since) = query.since {
+                    params.push(("at", format!("gte.{}", since.to_rfc3339())));
+                }
+                if let Some(until) = query.until {
+                    params.push(("at", format!("lt.{}", until.to_rfc3339())));
+                }
+                if let Some(action) = query.action {
+                    params.push(("action", format!("eq.{}", action.as_str())));
+                }
+                if let Some(sku) = &query.sku {
+                    params.push(("sku", format!("eq.{sku}")));
+                }
+                client.select_rows("audit_log", &params).await?
+            }
+        };
+        events.sort_by_key(|event| std::cmp::Reverse(event.at));
+        events.truncate(limit);
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StageReport;
+    use serde_json::json;
+
+    fn context(org_id: &str) -> AuthContext {
+        AuthContext {
+            org_id: org_id.into(),
+            api_key_id: "k1".into(),
+            scopes: Vec::new(),
+            plan: None,
+            request_id: Some("req-1".into()),
+        }
+    }
+
+    fn request(sku: &str) -> ListingRequest {
+        serde_json::from_value(json!({
+            "images_source": "https://example.com/a.jpg",
+            "sku": sku,
+            "merchant_location_key": "loc",
+            "fulfillment_policy_id": "f",
+            "payment_policy_id": "p",
+            "return_policy_id": "r",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn finished_events_carry_the_ebay_ids() {
+        let event = AuditEvent::new(
+            &context("acme"),
+            AuditAction::ListingCreate,
+            "POST /listings",
+            &request("SKU-1"),
+        );
+        let response = ListingResponse {
+            listing_id: "1100".into(),
+            stages: vec![StageReport::new(
+                "publish_offer",
+                3,
+                json!({ "offer_id": "OFF-9" }),
+            )],
+        };
+        let event = event.finish(&Ok(response));
+        assert_eq!(event.outcome, AuditOutcome::Succeeded);
+        assert_eq!(event.offer_id.as_deref(), Some("OFF-9"));
+        assert_eq!(event.listing_id.as_deref(), Some("1100"));
+        assert_eq!(event.request_id.as_deref(), Some("req-1"));
+        assert_eq!(event.marketplace, "EBAY_US");
+
+        let failed = AuditEvent::new(
+            &context("acme"),
+            AuditAction::ListingCreate,
+            "POST /listings",
+            &request("SKU-1"),
+        )
+        .finish(&Err(PipelineError::internal("publish_offer", "HTTP 500")));
+        assert_eq!(failed.outcome, AuditOutcome::Failed);
+        assert!(failed.error.unwrap().contains("HTTP 500"));
+    }
+
+    #[tokio::test]
+    async fn file_sink_appends_and_filters_per_org() {
+        let path = std::env::temp_dir().join(format!("hermes-audit-{}.jsonl", Uuid::new_v4()));
+        let log = AuditLog {
+            sink: Arc::new(AuditSink::File {
+                path: path.clone(),
+                writing: tokio::sync::Mutex::new(()),
+            }),
+        };
+        for (org_id, sku) in [("acme", "A"), ("acme", "B"), ("venture", "A")] {
+            let event = AuditEvent::new(
+                &context(org_id),
+                AuditAction::JobEnqueue,
+                "POST /jobs/listings",
+                &request(sku),
+            );
+            log.record(event.accepted(Uuid::new_v4())).await;
+        }
+
+        let all = AuditQuery {
+            limit: DEFAULT_LIMIT,
+            ..AuditQuery::default()
+        };
+        let events = log.query("acme", &all).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].at >= events[1].at, "newest first");
+        assert!(events.iter().all(|event| event.org_id == "acme"));
+
+        let only_b = AuditQuery {
+            sku: Some("B".into()),
+            ..all.clone()
+        };
+        let events = log.query("acme", &only_b).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].outcome, AuditOutcome::Accepted);
+        assert!(events[0].job_id.is_some());
+
+        let publishes = AuditQuery {
+            action: Some(AuditAction::PublishOffer),
+            ..all
+        };
+        assert!(log.query("acme", &publishes).await.unwrap().is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
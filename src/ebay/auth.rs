@@ -1,23 +1,58 @@
 #![allow(dead_code)]
 
-use crate::ebay::config::{APP_ID, APP_SECRET, OAUTH_TOKEN_URL};
+use crate::ebay::config::{APP_ID, APP_SECRET, AUTHORIZE_URL, OAUTH_TOKEN_URL, RU_NAME};
+use crate::ebay::error::EbayApiError;
+use crate::ebay::executor::{Replay, execute};
 use crate::http::build_client;
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use reqwest::Client;
 use serde::Deserialize;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum EbayAuthError {
     #[error("missing ebay app credentials in env")]
     MissingCredentials,
-    #[error("oauth request failed: {0}")]
-    Request(String),
+    #[error("EBAY_RU_NAME is not set")]
+    MissingRuName,
+    #[error(transparent)]
+    Api(#[from] EbayApiError),
 }
 
 #[derive(Deserialize)]
 struct TokenResponse {
     access_token: String,
+    /// Seconds until the token expires; eBay user tokens last two hours.
+    #[serde(default = "TokenResponse::default_expires_in")]
+    expires_in: u64,
+    /// Only in authorization-code grants.
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    refresh_token_expires_in: Option<u64>,
+}
+
+impl TokenResponse {
+    fn default_expires_in() -> u64 {
+        7200
+    }
+}
+
+/// An access token and how long eBay said it stays valid.
+#[derive(Debug, Clone)]
+pub struct AccessToken {
+    pub access_token: String,
+    pub expires_in: Duration,
+}
+
+/// What a seller's consent yields: a first access token and the long-lived
+/// refresh token used to mint the next ones.
+#[derive(Debug, Clone)]
+pub struct UserGrant {
+    pub access: AccessToken,
+    pub refresh_token: String,
+    pub refresh_token_expires_in: Option<Duration>,
 }
 
 fn basic_auth_header() -> Result<String, EbayAuthError> {
@@ -34,13 +69,23 @@ pub async fn get_app_access_token(scopes: &[&str]) -> Result<String, EbayAuthErr
         ("grant_type", "client_credentials"),
         ("scope", &scopes.join(" ")),
     ];
-    request_token(&body).await
+    Ok(request_token(&body).await?.access_token)
 }
 
 pub async fn get_user_access_token_from_refresh(
     refresh_token: &str,
     scopes: &[&str],
 ) -> Result<String, EbayAuthError> {
+    Ok(refresh_user_access_token(refresh_token, scopes)
+        .await?
+        .access_token)
+}
+
+/// Exchange a refresh token for a user access token, keeping its lifetime.
+pub async fn refresh_user_access_token(
+    refresh_token: &str,
+    scopes: &[&str],
+) -> Result<AccessToken, EbayAuthError> {
     basic_auth_header()?;
     let body = [
         ("grant_type", "refresh_token"),
@@ -50,26 +95,80 @@ pub async fn get_user_access_token_from_refresh(
     request_token(&body).await
 }
 
-async fn request_token(params: &[(&str, &str)]) -> Result<String, EbayAuthError> {
-    let client = build_client();
-    let response = client
-        .post(OAUTH_TOKEN_URL.as_str())
-        .basic_auth(APP_ID.as_str(), Some(APP_SECRET.as_str()))
-        .form(&params)
-        .send()
-        .await
-        .map_err(|err| EbayAuthError::Request(err.to_string()))?;
+/// The eBay consent page a seller is sent to; eBay redirects back to the
+/// app's RuName URL with `code` and the same `state`.
+pub fn consent_url(state: &str, scopes: &[&str]) -> Result<String, EbayAuthError> {
+    basic_auth_header()?;
+    if RU_NAME.is_empty() {
+        return Err(EbayAuthError::MissingRuName);
+    }
+    let query = [
+        ("client_id", APP_ID.as_str()),
+        ("redirect_uri", RU_NAME.as_str()),
+        ("response_type", "code"),
+        ("scope", &scopes.join(" ")),
+        ("state", state),
+    ]
+    .iter()
+    .map(|(name, value)| format!("{name}={}", urlencoding::encode(value)))
+    .collect::<Vec<_>>()
+    .join("&");
+    Ok(format!("{}?{query}", *AUTHORIZE_URL))
+}
 
-    if !response.status().is_success() {
-        return Err(EbayAuthError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
+/// Trade the `code` from the consent redirect for tokens.
+pub async fn exchange_authorization_code(code: &str) -> Result<UserGrant, EbayAuthError> {
+    basic_auth_header()?;
+    if RU_NAME.is_empty() {
+        return Err(EbayAuthError::MissingRuName);
     }
+    let body = [
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", RU_NAME.as_str()),
+    ];
+    // A code is single-use, so a failed exchange is only retried when rate-limited.
+    let payload = post_token(&body, Replay::Unsafe).await?;
+    let refresh_token = payload
+        .refresh_token
+        .clone()
+        .ok_or_else(|| EbayApiError::decode("token", "no refresh_token in grant"))?;
+    Ok(UserGrant {
+        refresh_token,
+        refresh_token_expires_in: payload.refresh_token_expires_in.map(Duration::from_secs),
+        access: payload.into(),
+    })
+}
 
-    let payload: TokenResponse = response
+async fn request_token(params: &[(&str, &str)]) -> Result<AccessToken, EbayAuthError> {
+    // Refreshing a token twice just mints two tokens, so failures are retried.
+    Ok(post_token(params, Replay::Safe).await?.into())
+}
+
+async fn post_token(
+    params: &[(&str, &str)],
+    replay: Replay,
+) -> Result<TokenResponse, EbayAuthError> {
+    let client = build_client();
+    let response = execute("token", replay, || {
+        client
+            .post(OAUTH_TOKEN_URL.as_str())
+            .basic_auth(APP_ID.as_str(), Some(APP_SECRET.as_str()))
+            .form(&params)
+    })
+    .await?;
+
+    Ok(response
         .json()
         .await
-        .map_err(|err| EbayAuthError::Request(err.to_string()))?;
-    Ok(payload.access_token)
+        .map_err(|err| EbayApiError::decode("token", err))?)
+}
+
+impl From<TokenResponse> for AccessToken {
+    fn from(payload: TokenResponse) -> Self {
+        Self {
+            access_token: payload.access_token,
+            expires_in: Duration::from_secs(payload.expires_in),
+        }
+    }
 }
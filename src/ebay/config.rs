@@ -15,6 +15,12 @@ pub static APP_SECRET: Lazy<String> =
 pub static EBAY_REFRESH_TOKEN: Lazy<String> =
     Lazy::new(|| env::var("EBAY_REFRESH_TOKEN").unwrap_or_default());
 
+/// Scopes requested for user tokens: listing inventory and reading policies.
+pub const USER_SCOPES: &[&str] = &[
+    "https://api.ebay.com/oauth/api_scope/sell.inventory",
+    "https://api.ebay.com/oauth/api_scope/sell.account",
+];
+
 pub static DEFAULT_CATEGORY_TREE_ID: Lazy<String> =
     Lazy::new(|| env::var("EBAY_CATEGORY_TREE_ID").unwrap_or_else(|_| "0".to_string()));
 
@@ -28,3 +34,14 @@ pub static ROOT: Lazy<String> = Lazy::new(|| {
 
 pub static OAUTH_TOKEN_URL: Lazy<String> =
     Lazy::new(|| format!("{}/identity/v1/oauth2/token", *ROOT));
+
+/// The RuName eBay issued for the app's redirect URL, sent as `redirect_uri`.
+pub static RU_NAME: Lazy<String> = Lazy::new(|| env::var("EBAY_RU_NAME").unwrap_or_default());
+
+pub static AUTHORIZE_URL: Lazy<String> = Lazy::new(|| {
+    if EBAY_ENV.as_str().eq_ignore_ascii_case("PROD") {
+        "https://auth.ebay.com/oauth2/authorize".to_string()
+    } else {
+        "https://auth.sandbox.ebay.com/oauth2/authorize".to_string()
+    }
+});
@@ -0,0 +1,294 @@
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Header eBay stamps on every response; quote it when opening a support case.
+pub const REQUEST_ID_HEADER: &str = "x-ebay-c-request-id";
+
+/// eBay's `errorId` for "too many requests" on endpoints that do not use 429.
+const RATE_LIMIT_ERROR_ID: u64 = 2001;
+
+/// What went wrong, coarsely, for deciding whether a retry can help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EbayErrorKind {
+    /// 401/403, or an OAuth grant eBay refused: the token or app credentials.
+    Auth,
+    /// Any other 4xx: eBay rejected the request as sent.
+    Validation,
+    /// 429, or eBay's call-limit error.
+    RateLimited,
+    /// 5xx.
+    Server,
+    /// No response: connection, TLS or timeout.
+    Transport,
+    /// A success status with a body that did not parse.
+    Decode,
+}
+
+impl EbayErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EbayErrorKind::Auth => "auth",
+            EbayErrorKind::Validation => "validation",
+            EbayErrorKind::RateLimited => "rate_limited",
+            EbayErrorKind::Server => "server",
+            EbayErrorKind::Transport => "transport",
+            EbayErrorKind::Decode => "decode",
+        }
+    }
+}
+
+/// One entry of eBay's `errors[]` array.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EbayErrorDetail {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long_message: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<EbayErrorParameter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EbayErrorParameter {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub value: String,
+}
+
+/// A failed eBay API call: the HTTP status, eBay's parsed `errors[]` and the
+/// `x-ebay-c-request-id` of the response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EbayApiError {
+    /// The eBay operation, e.g. `createOffer`.
+    pub operation: &'static str,
+    pub kind: EbayErrorKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<EbayErrorDetail>,
+    /// Text for failures without `errors[]`: OAuth's `error_description`, a
+    /// transport error, or the start of an unparseable body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Error bodies: the Sell APIs' `errors[]`, or OAuth's `error` pair.
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    errors: Vec<EbayErrorDetail>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+impl EbayApiError {
+    /// Read a non-success response into an error.
+    pub async fn from_response(operation: &'static str, response: Response) -> Self {
+        let status = response.status();
+        let request_id = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let body = response.text().await.unwrap_or_default();
+        Self::from_parts(operation, status, request_id, &body)
+    }
+
+    pub fn from_parts(
+        operation: &'static str,
+        status: StatusCode,
+        request_id: Option<String>,
+        body: &str,
+    ) -> Self {
+        let parsed = serde_json::from_str::<ErrorBody>(body).ok();
+        let (errors, oauth_error, message) = match parsed {
+            Some(parsed) => {
+                let message = match (&parsed.error, parsed.error_description) {
+                    (Some(code), Some(description)) => Some(format!("{code}: {description}")),
+                    (Some(code), None) => Some(code.clone()),
+                    (None, description) => description,
+                };
+                (parsed.errors, parsed.error.is_some(), message)
+            }
+            None => (Vec::new(), false, snippet(body)),
+        };
+        let rate_limited = status == StatusCode::TOO_MANY_REQUESTS
+            || errors
+                .iter()
+                .any(|error| error.error_id == Some(RATE_LIMIT_ERROR_ID));
+        let kind = if rate_limited {
+            EbayErrorKind::RateLimited
+        } else if status.is_server_error() {
+            EbayErrorKind::Server
+        } else if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+            || (oauth_error && status.is_client_error())
+        {
+            EbayErrorKind::Auth
+        } else {
+            EbayErrorKind::Validation
+        };
+        Self {
+            operation,
+            kind,
+            status: Some(status.as_u16()),
+            request_id,
+            errors,
+            message,
+        }
+    }
+
+    /// The request never got a response.
+    pub fn transport(operation: &'static str, err: reqwest::Error) -> Self {
+        Self {
+            operation,
+            kind: EbayErrorKind::Transport,
+            status: None,
+            request_id: None,
+            errors: Vec::new(),
+            message: Some(err.to_string()),
+        }
+    }
+
+    /// A success response whose body was not what the operation returns.
+    pub fn decode(operation: &'static str, err: impl fmt::Display) -> Self {
+        Self {
+            operation,
+            kind: EbayErrorKind::Decode,
+            status: None,
+            request_id: None,
+            errors: Vec::new(),
+            message: Some(err.to_string()),
+        }
+    }
+
+    /// Whether the same call may succeed later: rate limits, 5xx and
+    /// transport failures.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.kind,
+            EbayErrorKind::RateLimited | EbayErrorKind::Server | EbayErrorKind::Transport
+        )
+    }
+
+    pub fn error_ids(&self) -> Vec<u64> {
+        self.errors
+            .iter()
+            .filter_map(|error| error.error_id)
+            .collect()
+    }
+}
+
+impl fmt::Display for EbayApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "eBay {} failed", self.operation)?;
+        match self.status {
+            Some(status) => write!(f, " (HTTP {status}, {})", self.kind.as_str())?,
+            None => write!(f, " ({})", self.kind.as_str())?,
+        }
+        for error in &self.errors {
+            let text = error
+                .long_message
+                .as_deref()
+                .or(error.message.as_deref())
+                .unwrap_or("no message");
+            match error.error_id {
+                Some(id) => write!(f, "; {id}: {text}")?,
+                None => write!(f, "; {text}")?,
+            }
+            let parameters = error
+                .parameters
+                .iter()
+                .map(|parameter| format!("{}={}", parameter.name, parameter.value))
+                .collect::<Vec<_>>();
+            if !parameters.is_empty() {
+                write!(f, " [{}]", parameters.join(", "))?;
+            }
+        }
+        if let Some(message) = &self.message {
+            write!(f, "; {message}")?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request id {request_id})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for EbayApiError {}
+
+/// The first line of a body that is not JSON, e.g. an HTML gateway page.
+fn snippet(body: &str) -> Option<String> {
+    let line = body.lines().map(str::trim).find(|line| !line.is_empty())?;
+    Some(line.chars().take(200).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sell_api_errors_are_parsed() {
+        let body = r#"{"errors":[{"errorId":25709,"domain":"API_INVENTORY","category":"REQUEST",
+            "message":"Invalid value for fulfillmentPolicyId.",
+            "longMessage":"Invalid value for fulfillmentPolicyId. The policy does not exist.",
+            "parameters":[{"name":"fulfillmentPolicyId","value":"123"}]}]}"#;
+        let err = EbayApiError::from_parts(
+            "publishOffer",
+            StatusCode::BAD_REQUEST,
+            Some("req-9".into()),
+            body,
+        );
+        assert_eq!(err.kind, EbayErrorKind::Validation);
+        assert_eq!(err.error_ids(), vec![25709]);
+        assert_eq!(err.errors[0].parameters[0].name, "fulfillmentPolicyId");
+        let text = err.to_string();
+        assert!(text.contains("HTTP 400"));
+        assert!(text.contains("25709: Invalid value for fulfillmentPolicyId. The policy"));
+        assert!(text.contains("fulfillmentPolicyId=123"));
+        assert!(text.contains("request id req-9"));
+    }
+
+    #[test]
+    fn failures_are_classified() {
+        let kind = |status: StatusCode, body: &str| {
+            EbayApiError::from_parts("op", status, None, body).kind
+        };
+        assert_eq!(kind(StatusCode::UNAUTHORIZED, ""), EbayErrorKind::Auth);
+        assert_eq!(
+            kind(
+                StatusCode::BAD_REQUEST,
+                r#"{"error":"invalid_grant","error_description":"the provided authorization refresh token is invalid"}"#
+            ),
+            EbayErrorKind::Auth
+        );
+        assert_eq!(
+            kind(StatusCode::TOO_MANY_REQUESTS, ""),
+            EbayErrorKind::RateLimited
+        );
+        assert_eq!(
+            kind(StatusCode::FORBIDDEN, r#"{"errors":[{"errorId":2001}]}"#),
+            EbayErrorKind::RateLimited
+        );
+        assert_eq!(
+            kind(StatusCode::BAD_GATEWAY, "<html>\n<h1>502</h1>"),
+            EbayErrorKind::Server
+        );
+        let gateway =
+            EbayApiError::from_parts("op", StatusCode::BAD_GATEWAY, None, "<html>\n<h1>502</h1>");
+        assert_eq!(gateway.message.as_deref(), Some("<html>"));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::ebay::error::{EbayApiError, EbayErrorKind};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use reqwest::{RequestBuilder, Response, header::RETRY_AFTER};
+use serde::Serialize;
+use std::{
+    env,
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+use tracing::warn;
+
+/// Whether repeating a call after an unknown outcome is harmless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replay {
+    /// GET, PUT, DELETE and calls such as `publishOffer` that converge.
+    Safe,
+    /// A repeat could create a second entity; only retried when rate-limited.
+    Unsafe,
+}
+
+/// How eBay calls are retried: `Retry-After` when eBay sends it, else
+/// exponential backoff with jitter.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Requests per call, counting retries.
+    pub max_attempts: u32,
+    /// Requests per stage after which its calls stop retrying, so a
+    /// struggling eBay cannot stall the stage.
+    pub stage_max_attempts: u32,
+    pub base_delay: Duration,
+    /// Longest single wait; a `Retry-After` beyond it ends the call.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// `EBAY_RETRY_MAX_ATTEMPTS` (default `3`), `EBAY_STAGE_MAX_ATTEMPTS`
+    /// (default `8`), `EBAY_RETRY_BASE_MS` and `EBAY_RETRY_MAX_MS` (defaults
+    /// `250`/`5000`).
+    pub fn from_env() -> Self {
+        let number = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        let base_ms = number("EBAY_RETRY_BASE_MS", 250);
+        Self {
+            max_attempts: number("EBAY_RETRY_MAX_ATTEMPTS", 3) as u32,
+            stage_max_attempts: number("EBAY_STAGE_MAX_ATTEMPTS", 8) as u32,
+            base_delay: Duration::from_millis(base_ms),
+            max_delay: Duration::from_millis(number("EBAY_RETRY_MAX_MS", 5_000).max(base_ms)),
+        }
+    }
+
+    /// Exponential backoff with "equal jitter", as for job retries.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(16);
+        let capped = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(exp))
+            .min(self.max_delay);
+        let half = capped / 2;
+        let jitter_ms = rand::rng().random_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter_ms)
+    }
+}
+
+static POLICY: Lazy<RetryPolicy> = Lazy::new(RetryPolicy::from_env);
+
+/// eBay requests made during one stage, for its `StageReport`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StageCalls {
+    /// Calls the stage made.
+    pub calls: u32,
+    /// Requests sent, counting retries.
+    pub attempts: u32,
+}
+
+#[derive(Default)]
+struct StageCounts {
+    calls: AtomicU32,
+    attempts: AtomicU32,
+}
+
+tokio::task_local! {
+    static STAGE: Arc<StageCounts>;
+}
+
+/// Run one pipeline stage, returning its output and the eBay requests it made.
+pub async fn stage_scope<F: Future>(fut: F) -> (F::Output, StageCalls) {
+    let counts = Arc::new(StageCounts::default());
+    let output = STAGE.scope(counts.clone(), fut).await;
+    let calls = StageCalls {
+        calls: counts.calls.load(Ordering::Relaxed),
+        attempts: counts.attempts.load(Ordering::Relaxed),
+    };
+    (output, calls)
+}
+
+/// Send the request `build` makes, retrying under the process-wide policy.
+/// `build` runs once per attempt, since a request cannot be sent twice.
+pub async fn execute(
+    operation: &'static str,
+    replay: Replay,
+    build: impl Fn() -> RequestBuilder,
+) -> Result<Response, EbayApiError> {
+    execute_with(&POLICY, operation, replay, build).await
+}
+
+/// [`execute`] under `policy`. Rate-limited calls are retried whatever
+/// `replay` says; 5xx and transport failures only when it is `Safe`.
+pub async fn execute_with(
+    policy: &RetryPolicy,
+    operation: &'static str,
+    replay: Replay,
+    build: impl Fn() -> RequestBuilder,
+) -> Result<Response, EbayApiError> {
+    let _ = STAGE.try_with(|stage| stage.calls.fetch_add(1, Ordering::Relaxed));
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let stage_attempts = STAGE
+            .try_with(|stage| stage.attempts.fetch_add(1, Ordering::Relaxed) + 1)
+            .unwrap_or(0);
+        crate::usage::record_ebay_call();
+        let (err, retry_after) = match build().send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                (
+                    EbayApiError::from_response(operation, response).await,
+                    retry_after,
+                )
+            }
+            Err(err) => (EbayApiError::transport(operation, err), None),
+        };
+
+        let retryable = match err.kind {
+            EbayErrorKind::RateLimited => true,
+            EbayErrorKind::Server | EbayErrorKind::Transport => replay == Replay::Safe,
+            _ => false,
+        };
+        let delay = retry_after.unwrap_or_else(|| policy.backoff(attempt));
+        if !retryable
+            || attempt >= policy.max_attempts
+            || stage_attempts >= policy.stage_max_attempts
+            || delay > policy.max_delay
+        {
+            return Err(err);
+        }
+        warn!(
+            target = "hermes.ebay",
+            operation,
+            attempt,
+            delay_ms = delay.as_millis() as u64,
+            error = %err,
+            "ebay_call_retrying"
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::StatusCode, routing::any};
+    use std::sync::atomic::AtomicUsize;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            stage_max_attempts: 4,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1_500),
+        }
+    }
+
+    /// A local stand-in for eBay that answers with `statuses` in turn, then 200.
+    async fn server(
+        statuses: &'static [(u16, Option<&'static str>)],
+    ) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = Router::new().route(
+            "/{*path}",
+            any(move || {
+                let hit = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match statuses.get(hit) {
+                        Some((status, retry_after)) => {
+                            let mut response = axum::response::Response::new(
+                                axum::body::Body::from(r#"{"errors":[{"errorId":25001}]}"#),
+                            );
+                            *response.status_mut() = StatusCode::from_u16(*status).unwrap();
+                            if let Some(value) = retry_after {
+                                response
+                                    .headers_mut()
+                                    .insert("retry-after", value.parse().unwrap());
+                            }
+                            response
+                        }
+                        None => axum::response::Response::new(axum::body::Body::from("{}")),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/offer", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, hits)
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_within_the_stage_budget() {
+        let (url, hits) = server(&[(503, None), (429, Some("1"))]).await;
+        let client = reqwest::Client::new();
+        let (result, calls) =
+            stage_scope(execute_with(&policy(), "getOffers", Replay::Safe, || {
+                client.get(&url)
+            }))
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            calls,
+            StageCalls {
+                calls: 1,
+                attempts: 3
+            }
+        );
+
+        // A second call in the same stage runs out of the shared budget.
+        let (url, hits) = server(&[(503, None), (503, None), (503, None), (503, None)]).await;
+        let (result, calls) = stage_scope(async {
+            let first =
+                execute_with(&policy(), "getOffers", Replay::Safe, || client.get(&url)).await;
+            let second =
+                execute_with(&policy(), "getOffers", Replay::Safe, || client.get(&url)).await;
+            (first, second)
+        })
+        .await;
+        assert_eq!(result.0.unwrap_err().status, Some(503));
+        assert_eq!(result.1.unwrap_err().status, Some(503));
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            calls,
+            StageCalls {
+                calls: 2,
+                attempts: 4
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn unsafe_calls_and_long_waits_are_not_retried() {
+        let client = reqwest::Client::new();
+        let (url, hits) = server(&[(500, None)]).await;
+        let err = execute_with(&policy(), "createOffer", Replay::Unsafe, || {
+            client.post(&url)
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind, EbayErrorKind::Server);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let (url, hits) = server(&[(429, None)]).await;
+        assert!(
+            execute_with(&policy(), "createOffer", Replay::Unsafe, || client
+                .post(&url))
+            .await
+            .is_ok(),
+            "a rate-limited request was never processed, so it is safe to resend"
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        let (url, hits) = server(&[(429, Some("60"))]).await;
+        let err = execute_with(&policy(), "getOffers", Replay::Safe, || client.get(&url))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, EbayErrorKind::RateLimited);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let (url, hits) = server(&[(400, None)]).await;
+        let err = execute_with(&policy(), "getOffers", Replay::Safe, || client.get(&url))
+            .await
+            .unwrap_err();
+        assert_eq!(err.error_ids(), vec![25001]);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,20 +1,15 @@
 #![allow(dead_code)]
 
 use crate::ebay::config::ROOT;
+use crate::ebay::error::EbayApiError;
+use crate::ebay::executor::{Replay, execute};
 use crate::ebay::listing::PackageWeightAndSizePayload;
 use crate::http::build_client;
 use reqwest::Client;
 use serde::Serialize;
 use std::collections::BTreeMap;
-use thiserror::Error;
 use urlencoding::encode;
 
-#[derive(Debug, Error)]
-pub enum EbayInventoryError {
-    #[error("request failed: {0}")]
-    Request(String),
-}
-
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InventoryItemRequest {
@@ -51,24 +46,14 @@ pub async fn upsert_inventory_item(
     sku: &str,
     payload: &InventoryItemRequest,
     access_token: &str,
-) -> Result<(), EbayInventoryError> {
+) -> Result<(), EbayApiError> {
     let client = build_client();
     let encoded_sku = encode(sku);
     let url = format!("{}/sell/inventory/v1/inventory_item/{}", *ROOT, encoded_sku);
-    let response = client
-        .put(url)
-        .bearer_auth(access_token)
-        .json(payload)
-        .send()
-        .await
-        .map_err(|err| EbayInventoryError::Request(err.to_string()))?;
-
-    if !response.status().is_success() {
-        return Err(EbayInventoryError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    execute("createOrReplaceInventoryItem", Replay::Safe, || {
+        client.put(&url).bearer_auth(access_token).json(payload)
+    })
+    .await?;
 
     Ok(())
 }
@@ -77,23 +62,14 @@ pub async fn upsert_inventory_location(
     merchant_location_key: &str,
     payload: &InventoryLocationRequest,
     access_token: &str,
-) -> Result<(), EbayInventoryError> {
+) -> Result<(), EbayApiError> {
     let client = build_client();
     let encoded_key = encode(merchant_location_key);
     let url = format!("{}/sell/inventory/v1/location/{}", *ROOT, encoded_key);
-    let response = client
-        .put(url)
-        .bearer_auth(access_token)
-        .json(payload)
-        .send()
-        .await
-        .map_err(|err| EbayInventoryError::Request(err.to_string()))?;
-    if !response.status().is_success() {
-        return Err(EbayInventoryError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    execute("createInventoryLocation", Replay::Safe, || {
+        client.put(&url).bearer_auth(access_token).json(payload)
+    })
+    .await?;
     Ok(())
 }
 #[derive(Debug, Clone, Serialize)]
@@ -1,9 +1,9 @@
 #![allow(dead_code)]
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListingPolicies {
     pub fulfillment_policy_id: String,
@@ -25,23 +25,23 @@ pub struct EbayListingDraft {
     pub images: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageWeightAndSizePayload {
     pub package_weight: WeightPayload,
     pub package_size: DimensionsPayload,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WeightPayload {
     pub value: f64,
-    pub unit: &'static str,
+    pub unit: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DimensionsPayload {
     pub height: f64,
     pub length: f64,
     pub width: f64,
-    pub unit: &'static str,
+    pub unit: String,
 }
@@ -2,12 +2,16 @@
 
 pub mod auth;
 pub mod config;
+pub mod error;
+pub mod executor;
 pub mod inventory;
 pub mod listing;
 pub mod offers;
 pub mod taxonomy;
+pub mod token_cache;
 
 pub use auth::{get_app_access_token, get_user_access_token_from_refresh};
+pub use error::{EbayApiError, EbayErrorKind};
 pub use listing::{EbayListingDraft, ListingPolicies};
 pub use offers::{CreateOfferRequest, UpdateOfferRequest};
 pub use taxonomy::{EbayCondition, TaxonomyResponse};
@@ -2,6 +2,8 @@
 #![allow(non_snake_case)]
 
 use crate::ebay::config::ROOT;
+use crate::ebay::error::EbayApiError;
+use crate::ebay::executor::{Replay, execute};
 use crate::ebay::listing::{ListingPolicies, PackageWeightAndSizePayload};
 use crate::http::build_client;
 use reqwest::Client;
@@ -9,10 +11,13 @@ use serde::Serialize;
 use std::collections::BTreeMap;
 use thiserror::Error;
 
+/// eBay's `errorId` for an offer that already exists for the SKU and marketplace.
+const OFFER_EXISTS_ERROR_ID: u64 = 25002;
+
 #[derive(Debug, Error)]
 pub enum EbayOfferError {
-    #[error("request failed: {0}")]
-    Request(String),
+    #[error(transparent)]
+    Api(#[from] EbayApiError),
     #[error("entity already exists")]
     EntityExists,
 }
@@ -85,22 +90,18 @@ pub async fn create_offer(
 ) -> Result<String, EbayOfferError> {
     let client = build_client();
     let url = format!("{}/sell/inventory/v1/offer", *ROOT);
-    let response = client
-        .post(url)
-        .bearer_auth(access_token)
-        .json(request)
-        .send()
-        .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
-    if response.status() == 409 {
-        return Err(EbayOfferError::EntityExists);
-    }
-    if !response.status().is_success() {
-        return Err(EbayOfferError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    // A repeated create could make a second offer, so only rate limits retry.
+    let response = execute("createOffer", Replay::Unsafe, || {
+        client.post(&url).bearer_auth(access_token).json(request)
+    })
+    .await
+    .map_err(|err| {
+        if err.status == Some(409) || err.error_ids().contains(&OFFER_EXISTS_ERROR_ID) {
+            EbayOfferError::EntityExists
+        } else {
+            err.into()
+        }
+    })?;
     #[derive(serde::Deserialize)]
     struct OfferResponse {
         offerId: String,
@@ -108,25 +109,17 @@ pub async fn create_offer(
     let payload: OfferResponse = response
         .json()
         .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
+        .map_err(|err| EbayApiError::decode("createOffer", err))?;
     Ok(payload.offerId)
 }
 
 pub async fn publish_offer(offer_id: &str, access_token: &str) -> Result<String, EbayOfferError> {
     let client = build_client();
     let url = format!("{}/sell/inventory/v1/offer/{offer_id}/publish", *ROOT);
-    let response = client
-        .post(url)
-        .bearer_auth(access_token)
-        .send()
-        .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
-    if !response.status().is_success() {
-        return Err(EbayOfferError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    let response = execute("publishOffer", Replay::Safe, || {
+        client.post(&url).bearer_auth(access_token)
+    })
+    .await?;
     #[derive(serde::Deserialize)]
     struct PublishResponse {
         listingId: Option<String>,
@@ -134,7 +127,7 @@ pub async fn publish_offer(offer_id: &str, access_token: &str) -> Result<String,
     let payload: PublishResponse = response
         .json()
         .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
+        .map_err(|err| EbayApiError::decode("publishOffer", err))?;
     Ok(payload.listingId.unwrap_or_default())
 }
 
@@ -155,23 +148,17 @@ pub async fn get_offers_by_sku(
 ) -> Result<Vec<OfferSummary>, EbayOfferError> {
     let client = build_client();
     let url = format!("{}/sell/inventory/v1/offer", *ROOT);
-    let response = client
-        .get(url)
-        .bearer_auth(access_token)
-        .query(&[("sku", sku)])
-        .send()
-        .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
-    if !response.status().is_success() {
-        return Err(EbayOfferError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    let response = execute("getOffers", Replay::Safe, || {
+        client
+            .get(&url)
+            .bearer_auth(access_token)
+            .query(&[("sku", sku)])
+    })
+    .await?;
     let payload: OfferSearchResponse = response
         .json()
         .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
+        .map_err(|err| EbayApiError::decode("getOffers", err))?;
     Ok(payload.offers.unwrap_or_default())
 }
 
@@ -182,54 +169,29 @@ pub async fn update_offer(
 ) -> Result<(), EbayOfferError> {
     let client = build_client();
     let url = format!("{}/sell/inventory/v1/offer/{offer_id}", *ROOT);
-    let response = client
-        .put(url)
-        .bearer_auth(access_token)
-        .json(payload)
-        .send()
-        .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
-    if !response.status().is_success() {
-        return Err(EbayOfferError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    execute("updateOffer", Replay::Safe, || {
+        client.put(&url).bearer_auth(access_token).json(payload)
+    })
+    .await?;
     Ok(())
 }
 
 pub async fn delete_offer(offer_id: &str, access_token: &str) -> Result<(), EbayOfferError> {
     let client = build_client();
     let url = format!("{}/sell/inventory/v1/offer/{offer_id}", *ROOT);
-    let response = client
-        .delete(url)
-        .bearer_auth(access_token)
-        .send()
-        .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
-    if !response.status().is_success() {
-        return Err(EbayOfferError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    execute("deleteOffer", Replay::Safe, || {
+        client.delete(&url).bearer_auth(access_token)
+    })
+    .await?;
     Ok(())
 }
 
 pub async fn withdraw_offer(offer_id: &str, access_token: &str) -> Result<(), EbayOfferError> {
     let client = Client::new();
     let url = format!("{}/sell/inventory/v1/offer/{offer_id}/withdraw", *ROOT);
-    let response = client
-        .post(url)
-        .bearer_auth(access_token)
-        .send()
-        .await
-        .map_err(|err| EbayOfferError::Request(err.to_string()))?;
-    if !response.status().is_success() {
-        return Err(EbayOfferError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    execute("withdrawOffer", Replay::Safe, || {
+        client.post(&url).bearer_auth(access_token)
+    })
+    .await?;
     Ok(())
 }
@@ -2,16 +2,11 @@
 #![allow(non_snake_case)]
 
 use crate::ebay::config::{DEFAULT_CATEGORY_TREE_ID, ROOT};
+use crate::ebay::error::EbayApiError;
+use crate::ebay::executor::{Replay, execute};
 use crate::http::build_client;
 use reqwest::Client;
 use serde::Deserialize;
-use thiserror::Error;
-
-#[derive(Debug, Error)]
-pub enum EbayTaxonomyError {
-    #[error("taxonomy request failed: {0}")]
-    Request(String),
-}
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct TaxonomyResponse {
@@ -90,29 +85,22 @@ pub enum EbayCondition {
 pub async fn fetch_category_aspects(
     category_id: &str,
     access_token: &str,
-) -> Result<TaxonomyResponse, EbayTaxonomyError> {
+) -> Result<TaxonomyResponse, EbayApiError> {
     let client = build_client();
     let url = format!(
         "{}/commerce/taxonomy/v1/category_tree/{}/get_item_aspects_for_category",
         *ROOT, *DEFAULT_CATEGORY_TREE_ID
     );
-    let response = client
-        .get(url)
-        .query(&[("category_id", category_id)])
-        .bearer_auth(access_token)
-        .send()
-        .await
-        .map_err(|err| EbayTaxonomyError::Request(err.to_string()))?;
-
-    if !response.status().is_success() {
-        return Err(EbayTaxonomyError::Request(format!(
-            "HTTP {}",
-            response.status()
-        )));
-    }
+    let response = execute("getItemAspectsForCategory", Replay::Safe, || {
+        client
+            .get(&url)
+            .query(&[("category_id", category_id)])
+            .bearer_auth(access_token)
+    })
+    .await?;
 
     response
         .json::<TaxonomyResponse>()
         .await
-        .map_err(|err| EbayTaxonomyError::Request(err.to_string()))
+        .map_err(|err| EbayApiError::decode("getItemAspectsForCategory", err))
 }
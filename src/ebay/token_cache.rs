@@ -0,0 +1,379 @@
+use crate::ebay::auth::{AccessToken, EbayAuthError, refresh_user_access_token};
+use crate::seller_accounts::TokenCipher;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedToken {
+    access_token: String,
+    /// When to stop handing the token out and fetch a new one.
+    refresh_at: DateTime<Utc>,
+}
+
+impl CachedToken {
+    /// Refreshed `refresh_ahead` before it expires, or halfway through its
+    /// life if that is sooner.
+    fn new(token: AccessToken, refresh_ahead: Duration) -> Self {
+        let lead = refresh_ahead.min(token.expires_in / 2);
+        let lifetime =
+            chrono::Duration::from_std(token.expires_in - lead).unwrap_or(chrono::Duration::zero());
+        Self {
+            access_token: token.access_token,
+            refresh_at: Utc::now() + lifetime,
+        }
+    }
+
+    fn is_fresh(&self) -> bool {
+        Utc::now() < self.refresh_at
+    }
+}
+
+/// One cache entry; its lock is held while the token is refreshed, which is
+/// what makes the refresh single-flight.
+type Slot = Arc<tokio::sync::Mutex<Option<CachedToken>>>;
+
+/// Redis, and the cipher that keeps access tokens in it unreadable.
+#[derive(Clone)]
+struct Shared {
+    client: redis::Client,
+    cipher: Arc<TokenCipher>,
+}
+
+impl Shared {
+    /// The cache key is the associated data, so a value only opens under
+    /// the key it was stored at.
+    fn seal(&self, key: &str, token: &CachedToken) -> String {
+        let value = serde_json::to_string(token).expect("token serializes");
+        self.cipher.seal(key, &value)
+    }
+
+    fn open(&self, key: &str, sealed: &str) -> Option<CachedToken> {
+        let value = self.cipher.open(key, sealed).ok()?;
+        serde_json::from_str(&value).ok()
+    }
+}
+
+/// eBay user access tokens, reused until shortly before they expire;
+/// concurrent runs needing the same token wait on one refresh.
+#[derive(Clone)]
+pub struct TokenCache {
+    refresh_ahead: Duration,
+    slots: Arc<Mutex<HashMap<String, Slot>>>,
+    shared: Option<Shared>,
+}
+
+impl TokenCache {
+    /// `EBAY_TOKEN_REFRESH_AHEAD_SECS` defaults to `300`. With `redis`,
+    /// tokens are shared between replicas unless
+    /// `EBAY_TOKEN_CACHE_BACKEND=memory`.
+    pub fn from_env(redis: Option<redis::Client>) -> Self {
+        let refresh_ahead = env::var("EBAY_TOKEN_REFRESH_AHEAD_SECS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(300);
+        let backend = env::var("EBAY_TOKEN_CACHE_BACKEND")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let cipher = env::var("EBAY_TOKEN_ENCRYPTION_KEY")
+            .ok()
+            .and_then(|secret| TokenCipher::from_hex(&secret));
+        Self::new(
+            Duration::from_secs(refresh_ahead),
+            redis.filter(|_| backend != "memory"),
+            cipher,
+        )
+    }
+
+    /// Tokens go to `redis`, under `hermes:ebay_token:{hash}`, only when
+    /// `cipher` can seal them.
+    fn new(
+        refresh_ahead: Duration,
+        redis: Option<redis::Client>,
+        cipher: Option<TokenCipher>,
+    ) -> Self {
+        let shared = match (redis, cipher) {
+            (Some(client), Some(cipher)) => Some(Shared {
+                client,
+                cipher: Arc::new(cipher),
+            }),
+            (Some(_), None) => {
+                info!(
+                    target = "hermes.ebay",
+                    reason = "EBAY_TOKEN_ENCRYPTION_KEY not set",
+                    "ebay_token_cache_per_replica"
+                );
+                None
+            }
+            (None, _) => None,
+        };
+        Self {
+            refresh_ahead,
+            slots: Arc::default(),
+            shared,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn for_tests(refresh_ahead: Duration) -> Self {
+        Self::new(refresh_ahead, None, None)
+    }
+
+    /// A user access token for `refresh_token` and `scopes`, from the cache
+    /// or freshly minted by eBay.
+    pub async fn user_token(
+        &self,
+        refresh_token: &str,
+        scopes: &[&str],
+    ) -> Result<String, EbayAuthError> {
+        self.get_or_refresh(refresh_token, scopes, || {
+            refresh_user_access_token(refresh_token, scopes)
+        })
+        .await
+    }
+
+    pub async fn get_or_refresh<F, Fut>(
+        &self,
+        refresh_token: &str,
+        scopes: &[&str],
+        refresh: F,
+    ) -> Result<String, EbayAuthError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<AccessToken, EbayAuthError>>,
+    {
+        let key = cache_key(refresh_token, scopes);
+        let slot = self.slot(&key);
+        let mut cached = slot.lock().await;
+        if let Some(token) = cached.as_ref().filter(|token| token.is_fresh()) {
+            return Ok(token.access_token.clone());
+        }
+        if let Some(token) = self.load_shared(&key).await {
+            let access_token = token.access_token.clone();
+            *cached = Some(token);
+            return Ok(access_token);
+        }
+        let token = CachedToken::new(refresh().await?, self.refresh_ahead);
+        self.store_shared(&key, &token).await;
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// Forget the token for `refresh_token` and `scopes`, e.g. after eBay
+    /// refused it, so the next caller mints a new one.
+    pub async fn invalidate(&self, refresh_token: &str, scopes: &[&str]) {
+        let key = cache_key(refresh_token, scopes);
+        let slot = self.slot(&key);
+        *slot.lock().await = None;
+        if let Some(shared) = &self.shared {
+            let attempt = async {
+                let mut conn = shared.client.get_multiplexed_async_connection().await?;
+                redis::cmd("DEL")
+                    .arg(redis_key(&key))
+                    .query_async::<()>(&mut conn)
+                    .await
+            };
+            if let Err(err) = attempt.await {
+                warn!(target = "hermes.ebay", error = %err, "ebay_token_cache_redis_failed");
+            }
+        }
+    }
+
+    fn slot(&self, key: &str) -> Slot {
+        let mut slots = self.slots.lock().expect("token cache lock");
+        slots.entry(key.to_string()).or_default().clone()
+    }
+
+    /// A fresh token another replica stored, if any. Redis errors and values
+    /// that do not open are treated as a miss.
+    async fn load_shared(&self, key: &str) -> Option<CachedToken> {
+        let shared = self.shared.as_ref()?;
+        let attempt = async {
+            let mut conn = shared.client.get_multiplexed_async_connection().await?;
+            redis::cmd("GET")
+                .arg(redis_key(key))
+                .query_async::<Option<String>>(&mut conn)
+                .await
+        };
+        match attempt.await {
+            Ok(raw) => raw
+                .and_then(|raw| shared.open(key, &raw))
+                .filter(CachedToken::is_fresh),
+            Err(err) => {
+                warn!(target = "hermes.ebay", error = %err, "ebay_token_cache_redis_failed");
+                None
+            }
+        }
+    }
+
+    async fn store_shared(&self, key: &str, token: &CachedToken) {
+        let Some(shared) = &self.shared else {
+            return;
+        };
+        let ttl = (token.refresh_at - Utc::now()).num_seconds();
+        if ttl <= 0 {
+            return;
+        }
+        let attempt = async {
+            let mut conn = shared.client.get_multiplexed_async_connection().await?;
+            redis::cmd("SET")
+                .arg(redis_key(key))
+                .arg(shared.seal(key, token))
+                .arg("EX")
+                .arg(ttl)
+                .query_async::<()>(&mut conn)
+                .await
+        };
+        if let Err(err) = attempt.await {
+            warn!(target = "hermes.ebay", error = %err, "ebay_token_cache_redis_failed");
+        }
+    }
+}
+
+/// Hex SHA-256 of the refresh token and the sorted scopes, so the refresh
+/// token itself is never a cache key.
+fn cache_key(refresh_token: &str, scopes: &[&str]) -> String {
+    let mut scopes = scopes.to_vec();
+    scopes.sort_unstable();
+    let mut hasher = Sha256::new();
+    hasher.update(refresh_token);
+    for scope in scopes {
+        hasher.update(b"\n");
+        hasher.update(scope);
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn redis_key(key: &str) -> String {
+    format!("hermes:ebay_token:{key}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn minted(calls: &AtomicUsize, expires_in: Duration) -> AccessToken {
+        let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        AccessToken {
+            access_token: format!("token-{n}"),
+            expires_in,
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_refresh() {
+        let cache = TokenCache::for_tests(Duration::from_secs(300));
+        let calls = AtomicUsize::new(0);
+        let fetch = || async {
+            let token = minted(&calls, Duration::from_secs(7200));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(token)
+        };
+        let scopes = ["sell.inventory", "sell.account"];
+        let tokens = tokio::join!(
+            cache.get_or_refresh("refresh-a", &scopes, fetch),
+            cache.get_or_refresh("refresh-a", &scopes, fetch),
+            cache.get_or_refresh("refresh-a", &scopes, fetch),
+            cache.get_or_refresh("refresh-a", &scopes, fetch),
+        );
+        for token in [tokens.0, tokens.1, tokens.2, tokens.3] {
+            assert_eq!(token.unwrap(), "token-1");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Scope order does not matter; another refresh token gets its own entry.
+        let reordered = ["sell.account", "sell.inventory"];
+        let token = cache.get_or_refresh("refresh-a", &reordered, fetch).await;
+        assert_eq!(token.unwrap(), "token-1");
+        let token = cache.get_or_refresh("refresh-b", &scopes, fetch).await;
+        assert_eq!(token.unwrap(), "token-2");
+
+        cache.invalidate("refresh-a", &scopes).await;
+        let token = cache.get_or_refresh("refresh-a", &scopes, fetch).await;
+        assert_eq!(token.unwrap(), "token-3");
+    }
+
+    #[test]
+    fn shared_tokens_are_sealed_under_their_cache_key() {
+        let redis = || Some(redis::Client::open("redis://127.0.0.1/").unwrap());
+        let cipher = || TokenCipher::from_hex(&"ab".repeat(32));
+        let refresh_ahead = Duration::from_secs(300);
+        assert!(
+            TokenCache::new(refresh_ahead, redis(), None)
+                .shared
+                .is_none()
+        );
+
+        let cache = TokenCache::new(refresh_ahead, redis(), cipher());
+        let shared = cache.shared.as_ref().unwrap();
+        let token = CachedToken::new(
+            AccessToken {
+                access_token: "v^1.1#secret-access".into(),
+                expires_in: Duration::from_secs(7200),
+            },
+            refresh_ahead,
+        );
+        let key = cache_key("refresh", &["sell.inventory"]);
+        let sealed = shared.seal(&key, &token);
+        assert!(!sealed.contains("secret-access"));
+        let opened = shared.open(&key, &sealed).unwrap();
+        assert_eq!(opened.access_token, token.access_token);
+
+        let other = cache_key("refresh", &["sell.account"]);
+        assert!(shared.open(&other, &sealed).is_none());
+        assert!(
+            shared
+                .open(&key, &serde_json::to_string(&token).unwrap())
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn tokens_are_refreshed_ahead_of_expiry() {
+        let cache = TokenCache::for_tests(Duration::from_secs(300));
+        let calls = AtomicUsize::new(0);
+        let short = || async { Ok(minted(&calls, Duration::from_millis(200))) };
+        let scopes = ["sell.inventory"];
+        assert_eq!(
+            cache
+                .get_or_refresh("refresh", &scopes, short)
+                .await
+                .unwrap(),
+            "token-1"
+        );
+        assert_eq!(
+            cache
+                .get_or_refresh("refresh", &scopes, short)
+                .await
+                .unwrap(),
+            "token-1"
+        );
+        // Halfway through a lifetime shorter than the lead time, it is renewed.
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(
+            cache
+                .get_or_refresh("refresh", &scopes, short)
+                .await
+                .unwrap(),
+            "token-2"
+        );
+
+        let failing = || async { Err(EbayAuthError::MissingCredentials) };
+        assert!(
+            cache
+                .get_or_refresh("other", &scopes, failing)
+                .await
+                .is_err()
+        );
+    }
+}
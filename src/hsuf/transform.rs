@@ -68,13 +68,13 @@ pub fn estimate_package(product: &Product) -> Option<PackageWeightAndSizePayload
     Some(PackageWeightAndSizePayload {
         package_weight: crate::ebay::listing::WeightPayload {
             value: round_two(weight.max(0.1)),
-            unit: "POUND",
+            unit: "POUND".into(),
         },
         package_size: crate::ebay::listing::DimensionsPayload {
             height: round_one(height),
             length: round_one(length),
             width: round_one(width),
-            unit: "INCH",
+            unit: "INCH".into(),
         },
     })
 }
@@ -1,7 +1,13 @@
-use reqwest::Client;
+use reqwest::{Client, ClientBuilder};
 use std::time::Duration;
 
 pub fn build_client() -> Client {
+    client_builder().build().unwrap_or_else(|_| Client::new())
+}
+
+/// A builder with the `HTTP_TIMEOUT_SECS`/`HTTP_CONNECT_TIMEOUT_SECS`
+/// timeouts, for clients that need more settings on top.
+pub fn client_builder() -> ClientBuilder {
     let timeout = std::env::var("HTTP_TIMEOUT_SECS")
         .ok()
         .and_then(|v| v.parse::<u64>().ok())
@@ -13,6 +19,4 @@ pub fn build_client() -> Client {
     Client::builder()
         .timeout(Duration::from_secs(timeout))
         .connect_timeout(Duration::from_secs(connect))
-        .build()
-        .unwrap_or_else(|_| Client::new())
 }
@@ -1,24 +1,892 @@
-use crate::models::ListingResponse;
+use crate::{
+    models::ApiError,
+    retention::{Evicted, RetentionPolicy},
+    security::AuthContext,
+};
+use axum::{
+    Json, RequestExt,
+    body::{Body, to_bytes},
+    extract::{OriginalUri, Request, State},
+    http::{HeaderName, HeaderValue, Method, StatusCode, header, response::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
 use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+use uuid::Uuid;
 
-pub async fn redis_get(client: &redis::Client, key: &str) -> Option<ListingResponse> {
-    let mut conn = match client.get_multiplexed_async_connection().await {
-        Ok(c) => c,
-        Err(_) => return None,
+pub const KEY_HEADER: &str = "Idempotency-Key";
+/// Set to `true` on responses served from the cache instead of the handler.
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdempotencyError {
+    /// The key was already used by this org for a different request body.
+    #[error("idempotency_key_reused")]
+    KeyReused,
+    /// Another request with this key is still running.
+    #[error("request_in_progress")]
+    InProgress,
+    /// The endpoint streams its response, so it has none to store or replay.
+    #[error("idempotency_key_unsupported")]
+    Unsupported,
+    /// The in-memory cache is full of reservations still running.
+    #[error("idempotency_cache_full")]
+    CacheFull,
+    /// Redis could not record the reservation, so duplicates cannot be ruled out.
+    #[error("idempotency_store_unavailable")]
+    Unavailable,
+}
+
+impl IntoResponse for IdempotencyError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            IdempotencyError::KeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            IdempotencyError::InProgress => StatusCode::CONFLICT,
+            IdempotencyError::Unsupported => StatusCode::BAD_REQUEST,
+            IdempotencyError::CacheFull | IdempotencyError::Unavailable => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        };
+        let payload = ApiError {
+            error: "idempotency".to_string(),
+            detail: Some(self.to_string()),
+        };
+        (status, Json(payload)).into_response()
+    }
+}
+
+/// An `Idempotency-Key` scoped to the org that sent it, plus a fingerprint of
+/// the request it was sent with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    scoped: String,
+    fingerprint: String,
+}
+
+impl IdempotencyKey {
+    pub fn new(org_id: &str, key: &str, method: &Method, path: &str, body: &[u8]) -> Self {
+        Self {
+            scoped: format!("hermes:idempotency:{org_id}:{key}"),
+            fingerprint: fingerprint(method, path, body),
+        }
+    }
+}
+
+/// Hex SHA-256 of the method, path and canonical JSON body. Going through
+/// `serde_json::Value` sorts object keys, so field order and whitespace do
+/// not matter; bodies that are not JSON are hashed as sent.
+fn fingerprint(method: &Method, path: &str, body: &[u8]) -> String {
+    let canonical = serde_json::from_slice::<serde_json::Value>(body)
+        .and_then(|value| serde_json::to_vec(&value))
+        .unwrap_or_else(|_| body.to_vec());
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b"\n");
+    hasher.update(path);
+    hasher.update(b"\n");
+    hasher.update(canonical);
+    hex::encode(hasher.finalize())
+}
+
+/// A successful response as first sent, replayed verbatim for repeats.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl StoredResponse {
+    /// `None` for bodies that cannot be stored as text.
+    fn capture(parts: &Parts, body: &[u8]) -> Option<Self> {
+        let headers = parts
+            .headers
+            .iter()
+            .filter(|(name, _)| {
+                *name != header::CONTENT_LENGTH && *name != header::TRANSFER_ENCODING
+            })
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        Some(Self {
+            status: parts.status.as_u16(),
+            headers,
+            body: String::from_utf8(body.to_vec()).ok()?,
+        })
+    }
+}
+
+impl IntoResponse for StoredResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let headers = response.headers_mut();
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::try_from(name), HeaderValue::try_from(value))
+            {
+                headers.append(name, value);
+            }
+        }
+        headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+        response
+    }
+}
+
+/// Middleware for mutating routes: a request carrying `Idempotency-Key` runs
+/// at most once per org, and repeats get the first successful response back
+/// with `Idempotent-Replayed: true`. Failed responses are not stored, so the
+/// key can be retried. Must run inside `require_api_auth`.
+pub async fn idempotent(
+    State(store): State<IdempotencyStore>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request
+        .headers()
+        .get(KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+    else {
+        return next.run(request).await;
+    };
+    let Some(org_id) = request
+        .extensions()
+        .get::<AuthContext>()
+        .map(|context| context.org_id.clone())
+    else {
+        return next.run(request).await;
+    };
+
+    // Buffer the body (within `DefaultBodyLimit`) so it can be fingerprinted
+    // and still handed to the handler.
+    let (parts, body) = request.with_limited_body().into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let payload = ApiError {
+                error: "request".to_string(),
+                detail: Some("body_too_large".to_string()),
+            };
+            return (StatusCode::PAYLOAD_TOO_LARGE, Json(payload)).into_response();
+        }
     };
-    let s: Option<String> = conn.get(key).await.ok();
-    s.and_then(|v| serde_json::from_str(&v).ok())
+    // Nested routers strip their prefix; fingerprint the path the client sent.
+    let path = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or(parts.uri.path(), |uri| uri.path())
+        .to_string();
+    let key = IdempotencyKey::new(&org_id, &key, &parts.method, &path, &bytes);
+    let lease = match store.reserve(&key).await {
+        Ok(Reservation::Replay(stored)) => return stored.into_response(),
+        Ok(Reservation::Acquired(lease)) => lease,
+        Err(err) => return err.into_response(),
+    };
+
+    // Until `complete`, an error or a dropped request frees the key.
+    let response = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+    if !response.status().is_success() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    if let Some(stored) = StoredResponse::capture(&parts, &bytes) {
+        lease.complete(stored).await;
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Refuse `Idempotency-Key` on routes that cannot honour it, rather than
+/// letting a client believe its retries are safe.
+pub async fn reject_key(request: Request, next: Next) -> Response {
+    if request.headers().contains_key(KEY_HEADER) {
+        return IdempotencyError::Unsupported.into_response();
+    }
+    next.run(request).await
+}
+
+/// What is stored under a key: a reservation held by the request currently
+/// running, or the response it produced.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Entry {
+    Pending {
+        fingerprint: String,
+        lease_id: Uuid,
+    },
+    Completed {
+        fingerprint: String,
+        response: StoredResponse,
+    },
+}
+
+impl Entry {
+    /// How `key` is answered when this entry is already stored under it.
+    fn replay(self, key: &IdempotencyKey) -> Result<StoredResponse, IdempotencyError> {
+        match self {
+            Entry::Completed {
+                fingerprint,
+                response,
+            } if fingerprint == key.fingerprint => Ok(response),
+            Entry::Pending { fingerprint, .. } if fingerprint == key.fingerprint => {
+                Err(IdempotencyError::InProgress)
+            }
+            _ => Err(IdempotencyError::KeyReused),
+        }
+    }
+}
+
+/// Result of [`IdempotencyStore::reserve`].
+pub enum Reservation {
+    /// The key already has a response for this request; return it as is.
+    Replay(StoredResponse),
+    /// The caller now owns the key and should run the request.
+    Acquired(Box<Lease>),
 }
 
-pub async fn redis_set(
+/// Where `Idempotency-Key` reservations and responses live: Redis when
+/// `REDIS_URL` is set, so replicas share them, otherwise process memory.
+#[derive(Clone)]
+pub enum IdempotencyStore {
+    Memory(MemoryCache),
+    Redis {
+        client: redis::Client,
+        ttl: Duration,
+        lease: Duration,
+    },
+}
+
+impl IdempotencyStore {
+    /// Responses are kept per `RetentionPolicy::idempotency_from_env`;
+    /// reservations expire after `IDEMPOTENCY_LEASE_SECS` (default `300`) in
+    /// case their owner dies mid-request.
+    pub fn from_env(redis: Option<redis::Client>) -> Self {
+        let policy = RetentionPolicy::idempotency_from_env();
+        let lease = std::env::var("IDEMPOTENCY_LEASE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(300));
+        match redis {
+            Some(client) => Self::Redis {
+                client,
+                ttl: policy.ttl,
+                lease,
+            },
+            None => Self::Memory(MemoryCache::new(policy, lease)),
+        }
+    }
+
+    /// Atomically claim `key` for this request, or learn why it cannot be.
+    pub async fn reserve(&self, key: &IdempotencyKey) -> Result<Reservation, IdempotencyError> {
+        let pending = Entry::Pending {
+            fingerprint: key.fingerprint.clone(),
+            lease_id: Uuid::new_v4(),
+        };
+        let existing = match self {
+            Self::Memory(cache) => cache.reserve(key, &pending)?,
+            Self::Redis { client, lease, .. } => {
+                redis_reserve(client, key, &pending, *lease).await?
+            }
+        };
+        if let Some(existing) = existing {
+            return existing.replay(key).map(Reservation::Replay);
+        }
+        let renewal = match self {
+            Self::Memory(_) => None,
+            Self::Redis { client, lease, .. } => {
+                Some(spawn_renewal(client.clone(), key, &pending, *lease))
+            }
+        };
+        Ok(Reservation::Acquired(Box::new(Lease {
+            store: self.clone(),
+            key: key.clone(),
+            pending: Some(pending),
+            renewal,
+        })))
+    }
+
+    /// Drop expired in-memory entries; Redis expires its own keys.
+    pub async fn sweep(&self) -> Evicted {
+        match self {
+            Self::Memory(cache) => cache.sweep(),
+            Self::Redis { .. } => Evicted::default(),
+        }
+    }
+
+    async fn complete(&self, key: &IdempotencyKey, response: StoredResponse) {
+        let entry = Entry::Completed {
+            fingerprint: key.fingerprint.clone(),
+            response,
+        };
+        match self {
+            Self::Memory(cache) => cache.insert(key, entry),
+            Self::Redis { client, ttl, .. } => {
+                if let Ok(mut conn) = client.get_multiplexed_async_connection().await
+                    && let Ok(json) = serde_json::to_string(&entry)
+                {
+                    let _: Result<(), _> = conn.set_ex(&key.scoped, json, ttl.as_secs()).await;
+                }
+            }
+        }
+    }
+}
+
+/// `SET NX` the pending entry; otherwise return what holds the key.
+/// If Redis cannot answer, the request is refused rather than run
+/// unprotected, since a duplicate could publish twice.
+async fn redis_reserve(
     client: &redis::Client,
-    key: &str,
-    value: &ListingResponse,
-    ttl_secs: usize,
-) {
-    if let Ok(mut conn) = client.get_multiplexed_async_connection().await
-        && let Ok(json) = serde_json::to_string(value)
-    {
-        let _: Result<(), _> = conn.set_ex(key, json, ttl_secs as u64).await;
+    key: &IdempotencyKey,
+    pending: &Entry,
+    lease: Duration,
+) -> Result<Option<Entry>, IdempotencyError> {
+    let unavailable = |err: redis::RedisError| {
+        warn!(target = "hermes.api", error = %err, "idempotency_store_unavailable");
+        IdempotencyError::Unavailable
+    };
+    let json = serde_json::to_string(pending).map_err(|_| IdempotencyError::Unavailable)?;
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(unavailable)?;
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(&key.scoped)
+        .arg(&json)
+        .arg("NX")
+        .arg("PX")
+        .arg(lease.as_millis().max(1) as u64)
+        .query_async(&mut conn)
+        .await
+        .map_err(unavailable)?;
+    if acquired.is_some() {
+        return Ok(None);
+    }
+    let existing: Option<String> = conn.get(&key.scoped).await.map_err(unavailable)?;
+    // A key that vanished between SET and GET was just released; treat it as
+    // still busy rather than racing for it again.
+    Ok(Some(
+        existing
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_else(|| pending.clone()),
+    ))
+}
+
+/// Extend a Redis reservation every half lease while its request runs, so a
+/// slow publish never lets a retry take the key. Aborted when the
+/// [`Lease`] is completed or dropped.
+fn spawn_renewal(
+    client: redis::Client,
+    key: &IdempotencyKey,
+    pending: &Entry,
+    lease: Duration,
+) -> tokio::task::AbortHandle {
+    let scoped = key.scoped.clone();
+    let json = serde_json::to_string(pending).unwrap_or_default();
+    let lease_ms = lease.as_millis().max(1) as u64;
+    tokio::spawn(async move {
+        let script = redis::Script::new(
+            "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('PEXPIRE', KEYS[1], ARGV[2]) end return 0",
+        );
+        loop {
+            tokio::time::sleep(lease / 2).await;
+            let renewed = match client.get_multiplexed_async_connection().await {
+                Ok(mut conn) => script
+                    .key(&scoped)
+                    .arg(&json)
+                    .arg(lease_ms)
+                    .invoke_async::<i64>(&mut conn)
+                    .await
+                    .map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            match renewed {
+                Ok(1) => {}
+                Ok(_) => return,
+                Err(err) => {
+                    warn!(target = "hermes.api", error = %err, "idempotency_lease_renew_failed")
+                }
+            }
+        }
+    })
+    .abort_handle()
+}
+
+/// Ownership of a reserved key. Call [`Lease::complete`] with the response;
+/// dropping it instead (an error, or the client going away) frees the key so
+/// the request can be retried.
+pub struct Lease {
+    store: IdempotencyStore,
+    key: IdempotencyKey,
+    pending: Option<Entry>,
+    renewal: Option<tokio::task::AbortHandle>,
+}
+
+impl Lease {
+    pub async fn complete(mut self, response: StoredResponse) {
+        self.pending = None;
+        if let Some(renewal) = self.renewal.take() {
+            renewal.abort();
+        }
+        self.store.complete(&self.key, response).await;
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if let Some(renewal) = self.renewal.take() {
+            renewal.abort();
+        }
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        match &self.store {
+            IdempotencyStore::Memory(cache) => cache.release(&self.key, &pending),
+            IdempotencyStore::Redis { client, .. } => {
+                let Ok(json) = serde_json::to_string(&pending) else {
+                    return;
+                };
+                let client = client.clone();
+                let scoped = self.key.scoped.clone();
+                tokio::spawn(async move {
+                    // Compare-and-delete so an expired lease never frees a key
+                    // someone else has since reserved.
+                    let script = redis::Script::new(
+                        "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) end return 0",
+                    );
+                    if let Ok(mut conn) = client.get_multiplexed_async_connection().await {
+                        let _ = script
+                            .key(scoped)
+                            .arg(json)
+                            .invoke_async::<i64>(&mut conn)
+                            .await;
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// In-process idempotency cache used when Redis is not configured. Responses
+/// expire after `policy.ttl` and reservations after `lease`. Past
+/// `policy.max_entries` the oldest responses go first; reservations are never
+/// evicted, since that would let a retry run a second time, so a cache full
+/// of them refuses new keys instead.
+#[derive(Clone)]
+pub struct MemoryCache {
+    inner: Arc<Mutex<CacheInner>>,
+    policy: RetentionPolicy,
+    lease: Duration,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<String, CachedEntry>,
+    /// Keys holding a `Completed` entry, oldest first.
+    completed: BTreeMap<u64, String>,
+    next_seq: u64,
+}
+
+struct CachedEntry {
+    entry: Entry,
+    stored_at: Instant,
+    seq: u64,
+}
+
+impl CacheInner {
+    /// Store `entry` under `key`, replacing whatever was there.
+    fn put(&mut self, key: &str, entry: Entry) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        if matches!(entry, Entry::Completed { .. }) {
+            self.completed.insert(seq, key.to_string());
+        }
+        let cached = CachedEntry {
+            entry,
+            stored_at: Instant::now(),
+            seq,
+        };
+        if let Some(old) = self.entries.insert(key.to_string(), cached) {
+            self.completed.remove(&old.seq);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(old) = self.entries.remove(key) {
+            self.completed.remove(&old.seq);
+        }
+    }
+
+    /// Drop up to `count` of the oldest responses, returning how many went.
+    fn evict_oldest(&mut self, count: usize) -> usize {
+        let mut evicted = 0;
+        while evicted < count {
+            let Some((_, key)) = self.completed.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+            evicted += 1;
+        }
+        evicted
+    }
+}
+
+impl MemoryCache {
+    pub fn new(policy: RetentionPolicy, lease: Duration) -> Self {
+        Self {
+            inner: Arc::default(),
+            policy,
+            lease,
+        }
+    }
+
+    fn is_live(&self, cached: &CachedEntry) -> bool {
+        let ttl = match cached.entry {
+            Entry::Pending { .. } => self.lease,
+            Entry::Completed { .. } => self.policy.ttl,
+        };
+        cached.stored_at.elapsed() < ttl
+    }
+
+    /// Store `pending` under a free key; otherwise return what holds it.
+    fn reserve(
+        &self,
+        key: &IdempotencyKey,
+        pending: &Entry,
+    ) -> Result<Option<Entry>, IdempotencyError> {
+        let mut guard = self.inner.lock().expect("idempotency lock");
+        match guard.entries.get(&key.scoped) {
+            Some(cached) if self.is_live(cached) => return Ok(Some(cached.entry.clone())),
+            Some(_) => guard.remove(&key.scoped),
+            None => {}
+        }
+        let mut evicted = Evicted::default();
+        if guard.entries.len() >= self.policy.max_entries {
+            let excess = guard.entries.len() + 1 - self.policy.max_entries;
+            evicted.over_capacity = guard.evict_oldest(excess);
+            if guard.entries.len() >= self.policy.max_entries {
+                // Only reservations are left; expired ones can still go.
+                evicted.expired = self.drop_expired(&mut guard);
+            }
+            if guard.entries.len() >= self.policy.max_entries {
+                drop(guard);
+                evicted.record("idempotency");
+                return Err(IdempotencyError::CacheFull);
+            }
+        }
+        guard.put(&key.scoped, pending.clone());
+        drop(guard);
+        evicted.record("idempotency");
+        Ok(None)
+    }
+
+    fn insert(&self, key: &IdempotencyKey, entry: Entry) {
+        let mut guard = self.inner.lock().expect("idempotency lock");
+        guard.put(&key.scoped, entry);
+        let excess = guard.entries.len().saturating_sub(self.policy.max_entries);
+        if excess > 0 {
+            let over_capacity = guard.evict_oldest(excess);
+            drop(guard);
+            Evicted {
+                expired: 0,
+                over_capacity,
+            }
+            .record("idempotency");
+        }
+    }
+
+    fn drop_expired(&self, inner: &mut CacheInner) -> usize {
+        let before = inner.entries.len();
+        inner.entries.retain(|_, cached| self.is_live(cached));
+        let CacheInner {
+            entries, completed, ..
+        } = inner;
+        completed.retain(|_, key| entries.contains_key(key));
+        before - inner.entries.len()
+    }
+
+    /// Remove `key` if it still holds this exact reservation.
+    fn release(&self, key: &IdempotencyKey, pending: &Entry) {
+        let Entry::Pending { lease_id, .. } = pending else {
+            return;
+        };
+        let mut guard = self.inner.lock().expect("idempotency lock");
+        if matches!(
+            guard.entries.get(&key.scoped),
+            Some(CachedEntry { entry: Entry::Pending { lease_id: held, .. }, .. }) if held == lease_id
+        ) {
+            guard.remove(&key.scoped);
+        }
+    }
+
+    /// Drop expired entries (and any excess responses), returning what was
+    /// removed.
+    fn sweep(&self) -> Evicted {
+        let mut guard = self.inner.lock().expect("idempotency lock");
+        let expired = self.drop_expired(&mut guard);
+        let excess = guard.entries.len().saturating_sub(self.policy.max_entries);
+        Evicted {
+            expired,
+            over_capacity: guard.evict_oldest(excess),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Extension, Router, middleware, routing::post};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    fn response(id: &str) -> StoredResponse {
+        StoredResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: id.to_string(),
+        }
+    }
+
+    fn key(org_id: &str, key: &str, body: serde_json::Value) -> IdempotencyKey {
+        let body = serde_json::to_vec(&body).unwrap();
+        IdempotencyKey::new(org_id, key, &Method::POST, "/listings", &body)
+    }
+
+    fn memory(ttl: Duration, max_entries: usize) -> IdempotencyStore {
+        IdempotencyStore::Memory(MemoryCache::new(
+            RetentionPolicy { ttl, max_entries },
+            Duration::from_secs(60),
+        ))
+    }
+
+    async fn run(store: &IdempotencyStore, key: &IdempotencyKey, id: &str) {
+        match store.reserve(key).await {
+            Ok(Reservation::Acquired(lease)) => lease.complete(response(id)).await,
+            _ => panic!("expected to acquire {key:?}"),
+        }
+    }
+
+    async fn replayed(store: &IdempotencyStore, key: &IdempotencyKey) -> Option<String> {
+        match store.reserve(key).await {
+            Ok(Reservation::Replay(response)) => Some(response.body),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_cache_expires_and_caps_entries() {
+        let store = memory(Duration::from_millis(50), 2);
+        let body = serde_json::json!({ "sku": "a" });
+        for name in ["a", "b", "c"] {
+            run(&store, &key("org", name, body.clone()), name).await;
+        }
+        let c = key("org", "c", body.clone());
+        assert_eq!(replayed(&store, &c).await.as_deref(), Some("c"));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(replayed(&store, &c).await, None, "expired responses rerun");
+        let evicted = store.sweep().await;
+        // `a` was evicted for capacity when `c` was reserved, and the rerun of
+        // `c` above released its key again, so only `b` is left to expire.
+        assert_eq!((evicted.expired, evicted.over_capacity), (1, 0));
+        assert_eq!(
+            replayed(&store, &key("org", "a", body)).await,
+            None,
+            "oldest evicted at capacity"
+        );
+    }
+
+    #[tokio::test]
+    async fn running_requests_are_never_evicted() {
+        let store = memory(Duration::from_secs(60), 2);
+        let body = serde_json::json!({ "sku": "a" });
+        let lease = |name: &str| {
+            let key = key("org", name, body.clone());
+            let store = store.clone();
+            async move {
+                match store.reserve(&key).await {
+                    Ok(Reservation::Acquired(lease)) => lease,
+                    _ => panic!("expected to acquire {key:?}"),
+                }
+            }
+        };
+        let a = lease("a").await;
+        let _b = lease("b").await;
+
+        // Full of running requests: a new key is refused, not let through.
+        let c = key("org", "c", body.clone());
+        assert!(matches!(
+            store.reserve(&c).await,
+            Err(IdempotencyError::CacheFull)
+        ));
+        assert!(matches!(
+            store.reserve(&key("org", "b", body.clone())).await,
+            Err(IdempotencyError::InProgress)
+        ));
+
+        // Once `a` completes its response may go, but `b` stays reserved.
+        a.complete(response("a")).await;
+        run(&store, &c, "c").await;
+        assert_eq!(replayed(&store, &c).await.as_deref(), Some("c"));
+        assert!(matches!(
+            store.reserve(&key("org", "b", body.clone())).await,
+            Err(IdempotencyError::InProgress)
+        ));
+        assert_eq!(replayed(&store, &key("org", "a", body)).await, None);
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_org_and_request_body() {
+        let store = memory(Duration::from_secs(60), 10);
+        let body = serde_json::json!({ "sku": "a", "marketplace": "EBAY_US" });
+        run(&store, &key("org-a", "k1", body.clone()), "first").await;
+
+        let reordered = serde_json::json!({ "marketplace": "EBAY_US", "sku": "a" });
+        assert_eq!(
+            replayed(&store, &key("org-a", "k1", reordered))
+                .await
+                .as_deref(),
+            Some("first")
+        );
+        assert!(matches!(
+            store.reserve(&key("org-b", "k1", body)).await,
+            Ok(Reservation::Acquired(_))
+        ));
+        assert!(matches!(
+            store
+                .reserve(&key("org-a", "k1", serde_json::json!({ "sku": "b" })))
+                .await,
+            Err(IdempotencyError::KeyReused)
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_held_key_is_in_progress_until_released() {
+        let store = memory(Duration::from_secs(60), 10);
+        let k = key("org", "k1", serde_json::json!({ "sku": "a" }));
+        let Ok(Reservation::Acquired(lease)) = store.reserve(&k).await else {
+            panic!("first request acquires the key");
+        };
+        assert!(matches!(
+            store.reserve(&k).await,
+            Err(IdempotencyError::InProgress)
+        ));
+
+        drop(lease);
+        let Ok(Reservation::Acquired(lease)) = store.reserve(&k).await else {
+            panic!("a failed request frees the key for a retry");
+        };
+        lease.complete(response("done")).await;
+        assert_eq!(replayed(&store, &k).await.as_deref(), Some("done"));
+    }
+
+    #[tokio::test]
+    async fn middleware_replays_status_headers_and_body() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new()
+            .route(
+                "/jobs/listings",
+                post(move |body: String| {
+                    let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move {
+                        if body.contains("bad") {
+                            return (StatusCode::BAD_REQUEST, "rejected").into_response();
+                        }
+                        (StatusCode::ACCEPTED, [("X-Job", n.to_string())], "queued").into_response()
+                    }
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                memory(Duration::from_secs(60), 10),
+                idempotent,
+            ))
+            .layer(Extension(AuthContext {
+                org_id: "org".into(),
+                api_key_id: "key-01".into(),
+                scopes: Vec::new(),
+                plan: None,
+                request_id: None,
+            }));
+        let send = |key: &str, body: &str| {
+            app.clone().oneshot(
+                Request::post("/jobs/listings")
+                    .header(KEY_HEADER, key)
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        let first = send("k1", r#"{"sku":"a"}"#).await.unwrap();
+        assert_eq!(first.status(), StatusCode::ACCEPTED);
+        assert!(first.headers().get(REPLAYED_HEADER).is_none());
+
+        let again = send("k1", r#"{ "sku": "a" }"#).await.unwrap();
+        assert_eq!(again.status(), StatusCode::ACCEPTED);
+        assert_eq!(again.headers()[REPLAYED_HEADER], "true");
+        assert_eq!(again.headers()["X-Job"], "1");
+        let body = to_bytes(again.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"queued");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let reused = send("k1", r#"{"sku":"b"}"#).await.unwrap();
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        for _ in 0..2 {
+            let failed = send("k2", r#"{"sku":"bad"}"#).await.unwrap();
+            assert_eq!(failed.status(), StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3, "failures are not replayed");
+    }
+
+    #[tokio::test]
+    async fn streaming_routes_refuse_keys() {
+        let app = Router::new()
+            .route("/listings/stream", post(|| async { "streamed" }))
+            .route_layer(middleware::from_fn(reject_key));
+        let send = |key: Option<&str>| {
+            let mut request = Request::post("/listings/stream");
+            if let Some(key) = key {
+                request = request.header(KEY_HEADER, key);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        let refused = send(Some("k1")).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::BAD_REQUEST);
+        let body = to_bytes(refused.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("idempotency_key_unsupported"));
+        assert_eq!(send(None).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unreachable_redis_refuses_instead_of_running_unprotected() {
+        let store = IdempotencyStore::Redis {
+            client: redis::Client::open("redis://127.0.0.1:1").unwrap(),
+            ttl: Duration::from_secs(60),
+            lease: Duration::from_secs(60),
+        };
+        let k = key("org", "k", serde_json::json!({ "sku": "a" }));
+        let Err(err) = store.reserve(&k).await else {
+            panic!("expected the reservation to be refused");
+        };
+        assert!(matches!(err, IdempotencyError::Unavailable));
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
     }
 }
@@ -0,0 +1,62 @@
+use super::JobInfo;
+use crate::models::StageReport;
+use serde::Serialize;
+use std::{collections::HashMap, sync::Mutex};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Progress of a running job as seen by `GET /jobs/{id}/events`.
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum JobEvent {
+    /// A pipeline stage finished.
+    Stage(StageReport),
+    /// The job changed state; terminal states end the stream.
+    State(JobInfo),
+}
+
+impl JobEvent {
+    /// SSE `event:` name: `stage`, or the job's new state.
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Stage(_) => "stage",
+            JobEvent::State(info) => info.state.name(),
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, JobEvent::State(info) if info.state.is_terminal())
+    }
+}
+
+/// Per-job broadcast channels, created on first subscription so jobs nobody
+/// watches cost nothing.
+#[derive(Default)]
+pub struct JobEvents {
+    channels: Mutex<HashMap<Uuid, broadcast::Sender<JobEvent>>>,
+}
+
+impl JobEvents {
+    pub fn subscribe(&self, id: Uuid) -> broadcast::Receiver<JobEvent> {
+        let mut guard = self.channels.lock().expect("job events lock");
+        guard.retain(|_, tx| tx.receiver_count() > 0);
+        guard
+            .entry(id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub fn publish(&self, id: Uuid, event: JobEvent) {
+        let mut guard = self.channels.lock().expect("job events lock");
+        let is_final = event.is_final();
+        if let Some(tx) = guard.get(&id) {
+            let _ = tx.send(event);
+        }
+        if is_final {
+            // Dropping the sender ends every subscriber's stream once drained.
+            guard.remove(&id);
+        }
+    }
+}
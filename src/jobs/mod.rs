@@ -0,0 +1,1302 @@
+mod events;
+mod query;
+mod retry;
+mod scheduler;
+mod store;
+mod webhook;
+
+pub use events::JobEvent;
+pub use query::{DEFAULT_PAGE_SIZE, JobCursor, JobFilter, JobPage, MAX_PAGE_SIZE};
+pub use retry::{JobAttempt, RetryPolicy};
+pub use scheduler::OrgQueueStats;
+pub use store::{JobCommit, JobRecord, JobStore, JobStoreError};
+pub use webhook::{WebhookDelivery, WebhookSender};
+
+use crate::{
+    models::{ListingRequest, StageReport},
+    pipeline::{BoxFuture, Pipeline, PipelineError, PipelineErrorKind, PreparedListing, RunHooks},
+    retention::RetentionPolicy,
+    security::AuthContext,
+};
+use chrono::{DateTime, Utc};
+use events::JobEvents;
+use scheduler::Scheduler;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    sync::{Mutex, broadcast::error::RecvError, mpsc},
+    task::{JoinHandle, JoinSet},
+    time::sleep,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// How often a watcher checks the store for a final state published by
+// another replica.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct JobQueue {
+    scheduler: Arc<Scheduler>,
+    store: JobStore,
+    policy: Arc<RetryPolicy>,
+    webhooks: Arc<WebhookSender>,
+    events: Arc<JobEvents>,
+    retention: RetentionPolicy,
+    worker_id: Arc<str>,
+    // Jobs sitting in this process's scheduler or currently running, so the
+    // recovery sweep does not hand the same job to a worker twice.
+    dispatched: Arc<Mutex<HashSet<Uuid>>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: Uuid,
+    pub request: ListingRequest,
+    pub context: AuthContext,
+    #[serde(default)]
+    pub options: JobOptions,
+}
+
+/// Per-job settings that are not part of the pipeline request.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct JobOptions {
+    /// Receives a signed POST of the `JobInfo` once the job reaches a
+    /// terminal state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// Build the listing right away but only push and publish it to eBay
+    /// at this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    /// Built through `build_listing`; `push_inventory` and `publish_offer`
+    /// run at `publish_at`.
+    Scheduled {
+        publish_at: DateTime<Utc>,
+    },
+    Completed {
+        result: crate::models::ListingResponse,
+    },
+    Failed {
+        error: String,
+        stage: Option<String>,
+    },
+    /// The last attempt hit a transient error; runs again at `retry_at`.
+    Retrying {
+        retry_at: DateTime<Utc>,
+        error: String,
+        stage: Option<String>,
+    },
+    /// Retries are exhausted; only `POST /jobs/{id}/retry` runs it again.
+    DeadLettered {
+        error: String,
+        stage: Option<String>,
+    },
+    /// Stopped on request; `at_stage` is the stage that never started, or
+    /// `None` when the job was still waiting in the queue (a scheduled job
+    /// reports `push_inventory`).
+    Cancelled {
+        at_stage: Option<String>,
+    },
+}
+
+impl JobState {
+    pub const NAMES: &[&str] = &[
+        "queued",
+        "running",
+        "scheduled",
+        "completed",
+        "failed",
+        "retrying",
+        "dead_lettered",
+        "cancelled",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Scheduled { .. } => "scheduled",
+            JobState::Completed { .. } => "completed",
+            JobState::Failed { .. } => "failed",
+            JobState::Retrying { .. } => "retrying",
+            JobState::DeadLettered { .. } => "dead_lettered",
+            JobState::Cancelled { .. } => "cancelled",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobState::Completed { .. }
+                | JobState::Failed { .. }
+                | JobState::DeadLettered { .. }
+                | JobState::Cancelled { .. }
+        )
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    #[serde(flatten)]
+    pub state: JobState,
+    pub attempts: Vec<JobAttempt>,
+    /// Set once a cancel is acknowledged for a running job; it stops at the
+    /// next stage boundary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel_requested_at: Option<DateTime<Utc>>,
+    /// Queue depth and in-flight count for the job's org on this replica.
+    pub org_queue: OrgQueueStats,
+}
+
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("job not found")]
+    NotFound,
+    #[error("{0}")]
+    Invalid(&'static str),
+    #[error("{0}")]
+    Conflict(&'static str),
+    #[error(transparent)]
+    Store(#[from] JobStoreError),
+}
+
+impl JobQueue {
+    /// Start `JOB_WORKERS` workers and a recovery sweep that re-dispatches
+    /// `Queued` and orphaned `Running` jobs found in `store` (e.g. after a
+    /// restart).
+    pub fn spawn(pipeline: Pipeline, store: JobStore) -> (Self, JoinHandle<()>) {
+        Self::start(pipeline, store, WebhookSender::from_env())
+    }
+
+    fn start(
+        pipeline: Pipeline,
+        store: JobStore,
+        webhooks: WebhookSender,
+    ) -> (Self, JoinHandle<()>) {
+        let queue = Self {
+            scheduler: Arc::new(Scheduler::new(
+                queue_capacity_from_env(),
+                max_per_org_from_env(),
+            )),
+            store,
+            policy: Arc::new(RetryPolicy::from_env()),
+            webhooks: Arc::new(webhooks),
+            events: Arc::new(JobEvents::default()),
+            retention: RetentionPolicy::jobs_from_env(),
+            worker_id: Arc::from(Uuid::new_v4().to_string()),
+            dispatched: Arc::new(Mutex::new(HashSet::new())),
+        };
+        let workers = worker_count_from_env();
+        let supervisor = queue.clone();
+
+        let handle = tokio::spawn(async move {
+            let mut set = JoinSet::new();
+            let sweeper = supervisor.clone();
+            set.spawn(async move { sweeper.recovery_loop().await });
+            let sweeper = supervisor.clone();
+            set.spawn(async move { sweeper.retention_loop().await });
+            for _ in 0..workers {
+                let worker = supervisor.clone();
+                let pipeline = pipeline.clone();
+                set.spawn(async move {
+                    loop {
+                        let job = worker.scheduler.next().await;
+                        let org_id = job.context.org_id.clone();
+                        worker.process(&pipeline, job).await;
+                        worker.scheduler.finish(&org_id).await;
+                    }
+                });
+            }
+            while set.join_next().await.is_some() {}
+        });
+
+        (queue, handle)
+    }
+
+    pub async fn enqueue_listing(
+        &self,
+        request: ListingRequest,
+        context: AuthContext,
+        options: JobOptions,
+    ) -> Result<Uuid, JobError> {
+        if let Some(url) = &options.callback_url {
+            self.webhooks
+                .validate(&context.org_id, url)
+                .map_err(JobError::Invalid)?;
+        }
+        if options.publish_at.is_some() && request.dry_run {
+            return Err(JobError::Invalid("publish_at_with_dry_run"));
+        }
+        let id = Uuid::new_v4();
+        let job = Job {
+            id,
+            request,
+            context,
+            options,
+        };
+        self.store.insert(&JobRecord::queued(job.clone())).await?;
+        self.dispatched.lock().await.insert(id);
+        self.scheduler.push(job).await;
+        Ok(id)
+    }
+
+    pub async fn get(&self, id: Uuid, org_id: &str) -> Result<JobInfo, JobError> {
+        let record = self.owned(id, org_id).await?;
+        Ok(self.info(record).await)
+    }
+
+    /// Load a job on behalf of `org_id`. Other orgs' jobs are reported as
+    /// missing so their ids cannot be probed.
+    async fn owned(&self, id: Uuid, org_id: &str) -> Result<JobRecord, JobError> {
+        match self.store.get(id).await? {
+            Some(record) if record.job.context.org_id == org_id => Ok(record),
+            _ => Err(JobError::NotFound),
+        }
+    }
+
+    /// One page of the jobs matching `filter`, newest first.
+    pub async fn list(
+        &self,
+        filter: &JobFilter,
+        cursor: Option<JobCursor>,
+        limit: usize,
+    ) -> Result<JobPage, JobError> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let records = self.store.list(filter, cursor, limit).await?;
+        Ok(query::paginate(records, limit))
+    }
+
+    /// Callback delivery attempts for a job, oldest first.
+    pub async fn deliveries(
+        &self,
+        id: Uuid,
+        org_id: &str,
+    ) -> Result<Vec<WebhookDelivery>, JobError> {
+        self.owned(id, org_id).await?;
+        Ok(self.store.deliveries(id).await?)
+    }
+
+    /// Put a dead-lettered job back in the queue with a fresh retry budget.
+    pub async fn requeue(&self, id: Uuid, org_id: &str) -> Result<JobInfo, JobError> {
+        let record = self.owned(id, org_id).await?;
+        if !matches!(record.state, JobState::DeadLettered { .. }) {
+            return Err(JobError::Conflict("job_not_dead_lettered"));
+        }
+        self.store.reopen(id).await?;
+        let record = self
+            .store
+            .modify(id, |record| {
+                record.state = JobState::Queued;
+                record.retry_window_start = record.attempts.len();
+            })
+            .await?
+            .ok_or(JobError::NotFound)?;
+        info!(target = "hermes.jobs", job_id = %id, "job_requeued");
+        if self.dispatched.lock().await.insert(id) {
+            self.scheduler.push(record.job.clone()).await;
+        }
+        Ok(self.announce(record).await)
+    }
+
+    /// Cancel a job. Waiting jobs are cancelled immediately; a running job
+    /// stops before its next stage and is guaranteed not to publish. Fails
+    /// with a conflict once the job has started `publish_offer` or finished.
+    pub async fn cancel(&self, id: Uuid, org_id: &str) -> Result<JobInfo, JobError> {
+        let record = self.owned(id, org_id).await?;
+        if record.state.is_terminal() {
+            return Err(JobError::Conflict("job_already_finished"));
+        }
+        if self.store.commit(id, JobCommit::Cancel).await? != JobCommit::Cancel {
+            return Err(JobError::Conflict("job_already_publishing"));
+        }
+        let now = Utc::now();
+        let record = self
+            .store
+            .modify(id, |record| {
+                record.cancel_requested_at.get_or_insert(now);
+                match record.state {
+                    JobState::Queued | JobState::Retrying { .. } => {
+                        record.state = JobState::Cancelled { at_stage: None };
+                    }
+                    JobState::Scheduled { .. } => {
+                        record.state = JobState::Cancelled {
+                            at_stage: Some("push_inventory".into()),
+                        };
+                    }
+                    _ => {}
+                }
+            })
+            .await?
+            .ok_or(JobError::NotFound)?;
+        if matches!(record.state, JobState::Cancelled { .. }) && self.scheduler.remove(id).await {
+            self.dispatched.lock().await.remove(&id);
+        }
+        info!(target = "hermes.jobs", job_id = %id, "job_cancel_acknowledged");
+        Ok(self.announce(record).await)
+    }
+
+    async fn info(&self, record: JobRecord) -> JobInfo {
+        JobInfo {
+            id: record.job.id.to_string(),
+            org_queue: self.scheduler.org_stats(&record.job.context.org_id).await,
+            state: record.state,
+            attempts: record.attempts,
+            cancel_requested_at: record.cancel_requested_at,
+        }
+    }
+
+    /// Stream a job's progress: its current state first, then stage reports
+    /// and state changes until it finishes. Only runs on this replica publish
+    /// stage reports, so the store is also polled for the final state.
+    pub async fn watch(
+        &self,
+        id: Uuid,
+        org_id: &str,
+    ) -> Result<mpsc::Receiver<JobEvent>, JobError> {
+        let record = self.owned(id, org_id).await?;
+        let mut events = self.events.subscribe(id);
+        // Re-read after subscribing so a transition in between is not missed.
+        let record = self.store.get(id).await?.unwrap_or(record);
+        let current = JobEvent::State(self.info(record).await);
+        let (tx, rx) = mpsc::channel(16);
+        let queue = self.clone();
+        tokio::spawn(async move {
+            let done = current.is_final();
+            if tx.send(current).await.is_err() || done {
+                return;
+            }
+            let mut poll = tokio::time::interval(WATCH_POLL_INTERVAL);
+            poll.tick().await;
+            loop {
+                let event = tokio::select! {
+                    received = events.recv() => match received {
+                        Ok(event) => event,
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = poll.tick() => match queue.store.get(id).await {
+                        Ok(Some(record)) if record.state.is_terminal() => {
+                            JobEvent::State(queue.info(record).await)
+                        }
+                        _ => continue,
+                    },
+                };
+                let done = event.is_final();
+                if tx.send(event).await.is_err() || done {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    /// Publish a state change to watchers and, once the job is terminal,
+    /// deliver its callback in the background.
+    async fn announce(&self, record: JobRecord) -> JobInfo {
+        let id = record.job.id;
+        let pending = record.callback_pending.then(|| record.clone());
+        let info = self.info(record).await;
+        self.events.publish(id, JobEvent::State(info.clone()));
+        if let Some(record) = pending {
+            tokio::spawn(self.clone().deliver_callback(record));
+        }
+        info
+    }
+
+    /// Deliver a finished job's callback under its claim, so only one worker
+    /// sends it, then mark it done. A worker that dies mid-delivery leaves
+    /// it pending for recovery to resume once the claim lapses.
+    async fn deliver_callback(self, record: JobRecord) {
+        let id = record.job.id;
+        match self.store.claim(id, &self.worker_id, job_lease()).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(err) => {
+                warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_claim_failed");
+                return;
+            }
+        }
+        let job = record.job.clone();
+        let info = self.info(record).await;
+        self.webhooks.deliver(&self.store, &job, &info).await;
+        if let Err(err) = self
+            .store
+            .modify(id, |record| record.callback_pending = false)
+            .await
+        {
+            warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_state_persist_failed");
+        }
+        if let Err(err) = self.store.release(id, &self.worker_id).await {
+            warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_release_failed");
+        }
+    }
+
+    async fn recovery_loop(self) {
+        let interval = recovery_interval_from_env();
+        loop {
+            self.recover().await;
+            sleep(interval).await;
+        }
+    }
+
+    async fn retention_loop(self) {
+        let interval = crate::retention::sweep_interval_from_env();
+        loop {
+            sleep(interval).await;
+            let evicted = self.store.evict(&self.retention).await;
+            evicted.record("jobs");
+            if evicted != Default::default() {
+                info!(
+                    target = "hermes.jobs",
+                    expired = evicted.expired,
+                    over_capacity = evicted.over_capacity,
+                    "finished_jobs_evicted"
+                );
+            }
+        }
+    }
+
+    async fn recover(&self) {
+        let records = match self.store.recoverable().await {
+            Ok(records) => records,
+            Err(err) => {
+                warn!(target = "hermes.jobs", backend = self.store.backend_name(), error = %err, "job_recovery_scan_failed");
+                return;
+            }
+        };
+        for record in records {
+            let id = record.job.id;
+            if record.callback_pending {
+                info!(target = "hermes.jobs", job_id = %id, "job_callback_resumed");
+                tokio::spawn(self.clone().deliver_callback(record));
+                continue;
+            }
+            if !self.dispatched.lock().await.insert(id) {
+                continue;
+            }
+            info!(target = "hermes.jobs", job_id = %id, "job_recovered");
+            self.scheduler.push(record.job).await;
+        }
+    }
+
+    async fn process(&self, pipeline: &Pipeline, job: Job) {
+        let id = job.id;
+        let Some(record) = self.try_claim(id).await else {
+            self.dispatched.lock().await.remove(&id);
+            return;
+        };
+
+        let started_at = Utc::now();
+        let hooks = JobHooks {
+            store: self.store.clone(),
+            events: self.events.clone(),
+            id,
+            worker_id: self.worker_id.clone(),
+            lost: AtomicBool::new(false),
+        };
+        let request = job.request.clone();
+        let context = Some(job.context.clone());
+        let outcome = match (record.prepared.clone(), job.options.publish_at) {
+            (Some(prepared), _) => RunOutcome::Finished(
+                pipeline
+                    .publish_prepared(request, context, prepared, &hooks)
+                    .await,
+            ),
+            (None, Some(publish_at)) => match pipeline.prepare(request, context, &hooks).await {
+                Ok(prepared) => RunOutcome::Prepared(Box::new(prepared), publish_at),
+                Err(err) => RunOutcome::Finished(Err(err)),
+            },
+            (None, None) => {
+                RunOutcome::Finished(pipeline.run_with_hooks(request, context, &hooks).await)
+            }
+        };
+        if hooks.lost.load(Ordering::SeqCst) {
+            // Another worker took the job over; its run owns the record now.
+            warn!(target = "hermes.jobs", job_id = %id, "job_claim_lost");
+            self.dispatched.lock().await.remove(&id);
+            return;
+        }
+        let window_attempt = record.attempts_in_window() + 1;
+        let mut resume_in = None;
+        let mut prepared = None;
+        let (state, error, stage) = match outcome {
+            // Nothing past `build_listing` has run, so there is no later stage
+            // left to stop at after the cancel landed.
+            RunOutcome::Prepared(..) | RunOutcome::Finished(Ok(_)) if hooks.cancelled().await => {
+                (JobState::Cancelled { at_stage: None }, None, None)
+            }
+            RunOutcome::Prepared(listing, publish_at) => {
+                let delay = (publish_at - Utc::now()).to_std().unwrap_or_default();
+                info!(target = "hermes.jobs", job_id = %id, %publish_at, "job_publication_scheduled");
+                resume_in = Some(delay);
+                prepared = Some(*listing);
+                (JobState::Scheduled { publish_at }, None, None)
+            }
+            RunOutcome::Finished(Ok(resp)) => (JobState::Completed { result: resp }, None, None),
+            RunOutcome::Finished(Err(err)) if err.kind() == PipelineErrorKind::Cancelled => {
+                info!(target = "hermes.jobs", job_id = %id, stage = err.stage(), "job_cancelled");
+                (
+                    JobState::Cancelled {
+                        at_stage: Some(err.stage().to_string()),
+                    },
+                    None,
+                    None,
+                )
+            }
+            RunOutcome::Finished(Err(err)) => {
+                let error = err.detail().to_string();
+                let stage = Some(err.stage().to_string());
+                let state = if !self.policy.is_retryable(&err) {
+                    JobState::Failed {
+                        error: error.clone(),
+                        stage: stage.clone(),
+                    }
+                } else if window_attempt < self.policy.max_attempts {
+                    let delay = self.policy.backoff(window_attempt);
+                    resume_in = Some(delay);
+                    JobState::Retrying {
+                        retry_at: Utc::now()
+                            + chrono::Duration::from_std(delay).unwrap_or_default(),
+                        error: error.clone(),
+                        stage: stage.clone(),
+                    }
+                } else {
+                    warn!(target = "hermes.jobs", job_id = %id, attempts = window_attempt, error = %error, "job_dead_lettered");
+                    JobState::DeadLettered {
+                        error: error.clone(),
+                        stage: stage.clone(),
+                    }
+                };
+                (state, Some(error), stage)
+            }
+        };
+        let attempt = JobAttempt {
+            attempt: record.attempts.len() + 1,
+            started_at,
+            finished_at: Utc::now(),
+            error,
+            stage,
+        };
+        let written = self
+            .store
+            .modify(id, |record| {
+                record.attempts.push(attempt.clone());
+                record.state = state.clone();
+                if let Some(listing) = &prepared {
+                    record.prepared = Some(listing.clone());
+                    // Publication gets its own retry budget.
+                    record.retry_window_start = record.attempts.len();
+                }
+            })
+            .await;
+        // Released first, so the callback delivery can take the claim.
+        if let Err(err) = self.store.release(id, &self.worker_id).await {
+            warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_release_failed");
+        }
+        match written {
+            Ok(Some(record)) => {
+                self.announce(record).await;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_state_persist_failed")
+            }
+        }
+
+        match resume_in {
+            Some(delay) => {
+                if let Err(err) = self.store.reopen(id).await {
+                    warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_reopen_failed");
+                }
+                info!(target = "hermes.jobs", job_id = %id, attempt = window_attempt, delay_ms = delay.as_millis() as u64, "job_resume_scheduled");
+                let queue = self.clone();
+                tokio::spawn(async move {
+                    sleep(delay).await;
+                    queue.scheduler.push(job).await;
+                });
+            }
+            None => {
+                self.dispatched.lock().await.remove(&id);
+            }
+        }
+    }
+
+    /// Claim the job and mark it `Running`. Returns `None` when another
+    /// worker owns it, its retry is not due yet, or it already finished.
+    async fn try_claim(&self, id: Uuid) -> Option<JobRecord> {
+        match self.store.claim(id, &self.worker_id, job_lease()).await {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => {
+                warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_claim_failed");
+                return None;
+            }
+        }
+        // The runnable check lives inside the compare-and-set so a cancel
+        // landing between read and write is never overwritten with `Running`.
+        let now = Utc::now();
+        let mut runnable = false;
+        let claimed = self
+            .store
+            .update(id, |record| {
+                runnable = match &record.state {
+                    JobState::Retrying { retry_at, .. } => *retry_at <= now,
+                    JobState::Scheduled { publish_at } => *publish_at <= now,
+                    state => !state.is_terminal(),
+                };
+                if runnable {
+                    record.state = JobState::Running;
+                }
+                runnable
+            })
+            .await;
+        match claimed {
+            Ok(Some(record)) if runnable => {
+                self.announce(record.clone()).await;
+                Some(record)
+            }
+            Ok(_) => {
+                let _ = self.store.release(id, &self.worker_id).await;
+                None
+            }
+            Err(err) => {
+                warn!(target = "hermes.jobs", job_id = %id, error = %err, "job_state_persist_failed");
+                let _ = self.store.release(id, &self.worker_id).await;
+                None
+            }
+        }
+    }
+}
+
+/// What one worker pass over a job produced.
+enum RunOutcome {
+    Finished(Result<crate::models::ListingResponse, PipelineError>),
+    /// Built and waiting for its `publish_at` time.
+    Prepared(Box<PreparedListing>, DateTime<Utc>),
+}
+
+/// Stops a job's pipeline once a cancel is recorded, and claims the
+/// `publish` decision before `publish_offer` so the two can never overlap.
+struct JobHooks {
+    store: JobStore,
+    events: Arc<JobEvents>,
+    id: Uuid,
+    worker_id: Arc<str>,
+    /// Set once renewing the claim failed because another worker holds it.
+    lost: AtomicBool,
+}
+
+impl JobHooks {
+    async fn cancelled(&self) -> bool {
+        matches!(
+            self.store.committed(self.id).await,
+            Ok(Some(JobCommit::Cancel))
+        )
+    }
+}
+
+impl RunHooks for JobHooks {
+    fn before_stage<'a>(&'a self, stage: &'static str) -> BoxFuture<'a, Result<(), PipelineError>> {
+        Box::pin(async move {
+            // Renew between stages so a long run never outlives its lease and
+            // lets a second worker reach `publish_offer` too.
+            match self
+                .store
+                .renew(self.id, &self.worker_id, job_lease())
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.lost.store(true, Ordering::SeqCst);
+                    return Err(PipelineError::internal("jobs", "job_claim_lost"));
+                }
+                Err(err) => return Err(PipelineError::internal("jobs", err.to_string())),
+            }
+            let decision = if stage == "publish_offer" {
+                self.store
+                    .commit(self.id, JobCommit::Publish)
+                    .await
+                    .map(Some)
+            } else {
+                self.store.committed(self.id).await
+            };
+            match decision {
+                Ok(Some(JobCommit::Cancel)) => Err(PipelineError::cancelled(stage)),
+                Ok(_) => Ok(()),
+                Err(err) => Err(PipelineError::internal("jobs", err.to_string())),
+            }
+        })
+    }
+
+    fn after_stage(&self, report: &StageReport) {
+        self.events
+            .publish(self.id, JobEvent::Stage(report.clone()));
+    }
+}
+
+fn queue_capacity_from_env() -> usize {
+    std::env::var("QUEUE_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(64)
+}
+
+fn worker_count_from_env() -> usize {
+    std::env::var("JOB_WORKERS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(4)
+}
+
+fn max_per_org_from_env() -> usize {
+    std::env::var("JOB_MAX_PER_ORG")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(2)
+}
+
+fn job_lease() -> chrono::Duration {
+    let secs = std::env::var("JOB_LEASE_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(600);
+    chrono::Duration::seconds(secs)
+}
+
+fn recovery_interval_from_env() -> Duration {
+    let secs = std::env::var("JOB_RECOVERY_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ImagesSource, MarketplaceId};
+
+    pub(super) fn sample_job() -> Job {
+        Job {
+            id: Uuid::new_v4(),
+            request: ListingRequest {
+                images_source: ImagesSource::Single("https://example.com/a.jpg".into()),
+                sku: "job-sku-001".into(),
+                merchant_location_key: "loc-1".into(),
+                fulfillment_policy_id: "fulfill-123".into(),
+                payment_policy_id: "payment-123".into(),
+                return_policy_id: "return-123".into(),
+                marketplace: MarketplaceId::EbayUs,
+                llm_provider: None,
+                llm_listing_model: None,
+                llm_category_model: None,
+                use_signed_urls: false,
+                overrides: None,
+                dry_run: true,
+            },
+            context: AuthContext {
+                org_id: "demo-org".into(),
+                api_key_id: "key-01".into(),
+                scopes: Vec::new(),
+                plan: None,
+                request_id: None,
+            },
+            options: JobOptions::default(),
+        }
+    }
+
+    async fn wait_for_terminal(queue: &JobQueue, id: Uuid) -> JobState {
+        for _ in 0..200 {
+            if let Ok(info) = queue.get(id, "demo-org").await
+                && info.state.is_terminal()
+            {
+                return info.state;
+            }
+            sleep(Duration::from_millis(25)).await;
+        }
+        panic!("job {id} did not finish");
+    }
+
+    #[tokio::test]
+    async fn enqueued_job_completes() {
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), JobStore::memory());
+        let job = sample_job();
+        let id = queue
+            .enqueue_listing(job.request, job.context, job.options)
+            .await
+            .expect("enqueue");
+        let state = wait_for_terminal(&queue, id).await;
+        assert!(matches!(state, JobState::Completed { .. }));
+    }
+
+    #[tokio::test]
+    async fn requeue_only_accepts_dead_lettered_jobs() {
+        let store = JobStore::memory();
+        let mut record = JobRecord::queued(sample_job());
+        record.state = JobState::DeadLettered {
+            error: "HTTP 503".into(),
+            stage: Some("publish_offer".into()),
+        };
+        record.attempts = (1..=3)
+            .map(|attempt| JobAttempt {
+                attempt,
+                started_at: Utc::now(),
+                finished_at: Utc::now(),
+                error: Some("HTTP 503".into()),
+                stage: Some("publish_offer".into()),
+            })
+            .collect();
+        let id = record.job.id;
+        store.insert(&record).await.unwrap();
+
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), store);
+        queue.requeue(id, "demo-org").await.expect("requeue");
+        let state = wait_for_terminal(&queue, id).await;
+        assert!(matches!(state, JobState::Completed { .. }));
+        let info = queue.get(id, "demo-org").await.unwrap();
+        assert_eq!(info.attempts.len(), 4);
+
+        assert!(matches!(
+            queue.requeue(id, "demo-org").await,
+            Err(JobError::Conflict("job_not_dead_lettered"))
+        ));
+        assert!(matches!(
+            queue.requeue(Uuid::new_v4(), "demo-org").await,
+            Err(JobError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn acknowledged_cancel_blocks_publish() {
+        let store = JobStore::memory();
+        let record = JobRecord::queued(sample_job());
+        let id = record.job.id;
+        store.insert(&record).await.unwrap();
+        store
+            .claim(id, "worker", chrono::Duration::minutes(5))
+            .await
+            .unwrap();
+        let hooks = JobHooks {
+            store: store.clone(),
+            events: Arc::default(),
+            id,
+            worker_id: Arc::from("worker"),
+            lost: AtomicBool::new(false),
+        };
+        assert!(hooks.before_stage("build_listing").await.is_ok());
+
+        assert_eq!(
+            store.commit(id, JobCommit::Cancel).await.unwrap(),
+            JobCommit::Cancel
+        );
+        for stage in ["push_inventory", "publish_offer"] {
+            let err = hooks.before_stage(stage).await.expect_err("cancelled");
+            assert_eq!(err.kind(), PipelineErrorKind::Cancelled);
+            assert_eq!(err.stage(), stage);
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_is_rejected_once_publishing() {
+        let store = JobStore::memory();
+        let mut record = JobRecord::queued(sample_job());
+        record.state = JobState::Running;
+        let id = record.job.id;
+        store.insert(&record).await.unwrap();
+        store
+            .claim(id, "other-worker", chrono::Duration::minutes(5))
+            .await
+            .unwrap();
+        store.commit(id, JobCommit::Publish).await.unwrap();
+
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), store);
+        assert!(matches!(
+            queue.cancel(id, "demo-org").await,
+            Err(JobError::Conflict("job_already_publishing"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn restarted_worker_resumes_queued_and_orphaned_jobs() {
+        let store = JobStore::memory();
+        let queued = JobRecord::queued(sample_job());
+        let mut orphaned = JobRecord::queued(sample_job());
+        orphaned.state = JobState::Running;
+        store.insert(&queued).await.unwrap();
+        store.insert(&orphaned).await.unwrap();
+
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), store);
+        for id in [queued.job.id, orphaned.job.id] {
+            let state = wait_for_terminal(&queue, id).await;
+            assert!(matches!(state, JobState::Completed { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn terminal_jobs_post_signed_callbacks() {
+        let receiver = webhook::tests::Receiver::start("s3cret", 1).await;
+        let (queue, _worker) = JobQueue::start(
+            Pipeline::demo(),
+            JobStore::memory(),
+            webhook::tests::sender("demo-org", "s3cret"),
+        );
+        let job = sample_job();
+        let options = JobOptions {
+            callback_url: Some(receiver.url.clone()),
+            ..Default::default()
+        };
+        let id = queue
+            .enqueue_listing(job.request, job.context, options)
+            .await
+            .expect("enqueue");
+        wait_for_terminal(&queue, id).await;
+
+        let mut deliveries = Vec::new();
+        for _ in 0..200 {
+            deliveries = queue.deliveries(id, "demo-org").await.unwrap();
+            if deliveries.iter().any(|d| d.delivered) {
+                break;
+            }
+            sleep(Duration::from_millis(25)).await;
+        }
+        assert_eq!(deliveries.len(), 2, "first attempt fails, retry lands");
+        assert_eq!(deliveries[0].status, Some(500));
+        assert!(deliveries[1].delivered);
+        assert_eq!(deliveries[0].delivery_id, deliveries[1].delivery_id);
+
+        let received = receiver.received.lock().await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].event, "job.completed");
+        assert_eq!(received[0].body["id"], id.to_string());
+        assert_eq!(received[0].body["state"], "completed");
+    }
+
+    #[tokio::test]
+    async fn restarted_worker_resumes_pending_callbacks() {
+        let receiver = webhook::tests::Receiver::start("s3cret", 0).await;
+        let store = JobStore::memory();
+        let mut job = sample_job();
+        job.options.callback_url = Some(receiver.url.clone());
+        let mut record = JobRecord::queued(job);
+        record.state = JobState::Running;
+        let id = record.job.id;
+        store.insert(&record).await.unwrap();
+        store
+            .modify(id, |record| {
+                record.state = JobState::Cancelled { at_stage: None }
+            })
+            .await
+            .unwrap();
+        // The previous worker failed once, then died during its backoff.
+        let failed = WebhookDelivery {
+            delivery_id: Uuid::new_v4(),
+            event: "job.cancelled".into(),
+            attempt: 1,
+            attempted_at: Utc::now(),
+            status: Some(500),
+            error: Some("HTTP 500".into()),
+            delivered: false,
+        };
+        store.push_delivery(id, &failed).await.unwrap();
+
+        let (queue, _worker) = JobQueue::start(
+            Pipeline::demo(),
+            store.clone(),
+            webhook::tests::sender("demo-org", "s3cret"),
+        );
+        let mut deliveries = Vec::new();
+        for _ in 0..200 {
+            deliveries = queue.deliveries(id, "demo-org").await.unwrap();
+            if deliveries.iter().any(|d| d.delivered) {
+                break;
+            }
+            sleep(Duration::from_millis(25)).await;
+        }
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[1].attempt, 2);
+        assert_eq!(deliveries[1].delivery_id, failed.delivery_id);
+        assert_eq!(receiver.received.lock().await.len(), 1);
+        for _ in 0..200 {
+            if !store.get(id).await.unwrap().unwrap().callback_pending {
+                return;
+            }
+            sleep(Duration::from_millis(25)).await;
+        }
+        panic!("the callback stayed pending");
+    }
+
+    #[tokio::test]
+    async fn rejects_callbacks_without_a_signing_secret() {
+        let (queue, _worker) = JobQueue::start(
+            Pipeline::demo(),
+            JobStore::memory(),
+            webhook::tests::sender("other-org", "s3cret"),
+        );
+        let job = sample_job();
+        let options = JobOptions {
+            callback_url: Some("https://hooks.example.com/jobs".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            queue
+                .enqueue_listing(job.request, job.context, options)
+                .await,
+            Err(JobError::Invalid("callback_secret_not_configured"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn watchers_see_each_stage_then_the_final_state() {
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), JobStore::memory());
+        let job = sample_job();
+        let record = JobRecord::queued(job.clone());
+        let id = job.id;
+        queue.store.insert(&record).await.unwrap();
+        let mut events = queue.watch(id, "demo-org").await.expect("watch");
+        assert_eq!(events.recv().await.unwrap().name(), "queued");
+
+        queue.dispatched.lock().await.insert(id);
+        queue.scheduler.push(job).await;
+        let mut names = Vec::new();
+        while let Some(event) = events.recv().await {
+            names.push(match &event {
+                JobEvent::Stage(report) => report.name.clone(),
+                JobEvent::State(info) => info.state.name().to_string(),
+            });
+        }
+        assert_eq!(names.first().map(String::as_str), Some("running"));
+        assert_eq!(names.get(1).map(String::as_str), Some("resolve_images"));
+        assert!(names.iter().any(|name| name == "build_listing"));
+        assert_eq!(names.last().map(String::as_str), Some("completed"));
+    }
+
+    #[tokio::test]
+    async fn lists_an_orgs_jobs_page_by_page() {
+        let store = JobStore::memory();
+        let mut failed = Vec::new();
+        for n in 0..5 {
+            let mut record = JobRecord::queued(sample_job());
+            record.created_at += chrono::Duration::seconds(n);
+            if n % 2 == 0 {
+                record.state = JobState::Failed {
+                    error: "HTTP 400".into(),
+                    stage: Some("publish_offer".into()),
+                };
+                failed.push(record.job.id.to_string());
+            }
+            store.insert(&record).await.unwrap();
+        }
+        let mut foreign = JobRecord::queued(sample_job());
+        foreign.job.context.org_id = "other-org".into();
+        foreign.state = JobState::Failed {
+            error: "HTTP 400".into(),
+            stage: None,
+        };
+        store.insert(&foreign).await.unwrap();
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), store);
+
+        let filter = JobFilter {
+            org_id: "demo-org".into(),
+            states: vec!["failed".into()],
+            ..Default::default()
+        };
+        let first = queue.list(&filter, None, 2).await.unwrap();
+        let cursor = first.next_cursor.as_deref().and_then(JobCursor::decode);
+        assert!(cursor.is_some());
+        let second = queue.list(&filter, cursor, 2).await.unwrap();
+        assert!(second.next_cursor.is_none());
+
+        let ids: Vec<String> = first
+            .jobs
+            .iter()
+            .chain(&second.jobs)
+            .map(|j| j.id.clone())
+            .collect();
+        failed.reverse();
+        assert_eq!(ids, failed, "newest first, other orgs excluded");
+        assert_eq!(first.jobs[0].stage.as_deref(), Some("publish_offer"));
+    }
+
+    #[tokio::test]
+    async fn evicts_expired_and_excess_finished_jobs() {
+        let store = JobStore::memory();
+        let finished = |age_secs: i64| {
+            let mut record = JobRecord::queued(sample_job());
+            record.state = JobState::Failed {
+                error: "HTTP 400".into(),
+                stage: None,
+            };
+            record.updated_at = Utc::now() - chrono::Duration::seconds(age_secs);
+            record
+        };
+        let expired = finished(7_200);
+        let oldest_kept = finished(120);
+        let newest = finished(60);
+        let mut stale_queued = JobRecord::queued(sample_job());
+        stale_queued.updated_at = Utc::now() - chrono::Duration::seconds(7_200);
+        for record in [&expired, &oldest_kept, &newest, &stale_queued] {
+            store.insert(record).await.unwrap();
+        }
+
+        let policy = RetentionPolicy {
+            ttl: Duration::from_secs(3_600),
+            max_entries: 1,
+        };
+        let evicted = store.evict(&policy).await;
+        assert_eq!((evicted.expired, evicted.over_capacity), (1, 1));
+        for (record, kept) in [
+            (&expired, false),
+            (&oldest_kept, false),
+            (&newest, true),
+            (&stale_queued, true),
+        ] {
+            assert_eq!(store.get(record.job.id).await.unwrap().is_some(), kept);
+        }
+    }
+
+    #[tokio::test]
+    async fn other_orgs_cannot_see_or_touch_a_job() {
+        let store = JobStore::memory();
+        let mut record = JobRecord::queued(sample_job());
+        record.state = JobState::DeadLettered {
+            error: "HTTP 503".into(),
+            stage: Some("publish_offer".into()),
+        };
+        let id = record.job.id;
+        store.insert(&record).await.unwrap();
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), store);
+
+        let intruder = "other-org";
+        assert!(matches!(
+            queue.get(id, intruder).await,
+            Err(JobError::NotFound)
+        ));
+        assert!(matches!(
+            queue.deliveries(id, intruder).await,
+            Err(JobError::NotFound)
+        ));
+        assert!(matches!(
+            queue.watch(id, intruder).await,
+            Err(JobError::NotFound)
+        ));
+        assert!(matches!(
+            queue.cancel(id, intruder).await,
+            Err(JobError::NotFound)
+        ));
+        assert!(matches!(
+            queue.requeue(id, intruder).await,
+            Err(JobError::NotFound)
+        ));
+
+        // Nothing above changed the job; its owner still sees it untouched.
+        let info = queue.get(id, "demo-org").await.expect("owner lookup");
+        assert!(matches!(info.state, JobState::DeadLettered { .. }));
+        assert!(info.cancel_requested_at.is_none());
+    }
+
+    fn stage_names(state: &JobState) -> Vec<String> {
+        match state {
+            JobState::Completed { result } => {
+                result.stages.iter().map(|s| s.name.clone()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scheduled_job_publishes_at_publish_at() {
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), JobStore::memory());
+        let mut job = sample_job();
+        job.request.dry_run = false;
+        let publish_at = Utc::now() + chrono::Duration::milliseconds(600);
+        let options = JobOptions {
+            publish_at: Some(publish_at),
+            ..Default::default()
+        };
+        let id = queue
+            .enqueue_listing(job.request, job.context, options)
+            .await
+            .expect("enqueue");
+
+        let mut seen_scheduled = false;
+        for _ in 0..100 {
+            let info = queue.get(id, "demo-org").await.unwrap();
+            if matches!(info.state, JobState::Scheduled { .. }) {
+                seen_scheduled = true;
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(seen_scheduled, "held before publish_at");
+
+        let state = wait_for_terminal(&queue, id).await;
+        assert!(Utc::now() >= publish_at);
+        let names = stage_names(&state);
+        assert_eq!(names.first().map(String::as_str), Some("resolve_images"));
+        assert_eq!(names.last().map(String::as_str), Some("publish_offer"));
+    }
+
+    #[tokio::test]
+    async fn scheduled_job_publishes_after_restart_without_rebuilding() {
+        let mut job = sample_job();
+        job.request.dry_run = false;
+        let prepared = Pipeline::demo()
+            .prepare(job.request.clone(), None, &crate::pipeline::NoHooks)
+            .await
+            .expect("prepare");
+        let built = prepared.stages.len();
+        let mut record = JobRecord::queued(job);
+        record.state = JobState::Scheduled {
+            publish_at: Utc::now() - chrono::Duration::seconds(1),
+        };
+        record.prepared = Some(prepared);
+        let id = record.job.id;
+        let store = JobStore::memory();
+        store.insert(&record).await.unwrap();
+
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), store);
+        let names = stage_names(&wait_for_terminal(&queue, id).await);
+        assert_eq!(names.len(), built + 2);
+        assert_eq!(names[built..], ["push_inventory", "publish_offer"]);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_scheduled_job_stops_before_push() {
+        let store = JobStore::memory();
+        let mut record = JobRecord::queued(sample_job());
+        record.state = JobState::Scheduled {
+            publish_at: Utc::now() + chrono::Duration::hours(1),
+        };
+        let id = record.job.id;
+        store.insert(&record).await.unwrap();
+        let (queue, _worker) = JobQueue::spawn(Pipeline::demo(), store);
+
+        let info = queue.cancel(id, "demo-org").await.expect("cancel");
+        assert!(matches!(
+            info.state,
+            JobState::Cancelled { at_stage: Some(ref stage) } if stage == "push_inventory"
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+use super::{JobRecord, JobState};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 200;
+
+/// Which of an org's jobs `GET /jobs` returns.
+#[derive(Debug, Clone, Default)]
+pub struct JobFilter {
+    pub org_id: String,
+    /// Accepted state names (`JobState::name`); empty means any state.
+    pub states: Vec<String>,
+    pub sku: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl JobFilter {
+    pub fn matches(&self, record: &JobRecord) -> bool {
+        record.job.context.org_id == self.org_id
+            && (self.states.is_empty() || self.states.iter().any(|s| s == record.state.name()))
+            && self
+                .sku
+                .as_ref()
+                .is_none_or(|sku| *sku == record.job.request.sku)
+            && self.since.is_none_or(|since| record.created_at >= since)
+    }
+}
+
+/// Position after the last job of a page. Jobs are listed newest first,
+/// ordered by `(created_at, id)` so ties on the timestamp stay stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobCursor {
+    pub created_at_ms: i64,
+    pub id: Uuid,
+}
+
+impl JobCursor {
+    pub fn after(record: &JobRecord) -> Self {
+        Self {
+            created_at_ms: record.created_at.timestamp_millis(),
+            id: record.job.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}", self.created_at_ms, self.id))
+    }
+
+    pub fn decode(raw: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(raw).ok()?;
+        let text = String::from_utf8(bytes).ok()?;
+        let (ms, id) = text.split_once(':')?;
+        Some(Self {
+            created_at_ms: ms.parse().ok()?,
+            id: Uuid::parse_str(id).ok()?,
+        })
+    }
+
+    /// Whether `record` sorts after this cursor, i.e. belongs on a later page.
+    pub fn precedes(&self, record: &JobRecord) -> bool {
+        let key = (record.created_at.timestamp_millis(), record.job.id);
+        key < (self.created_at_ms, self.id)
+    }
+}
+
+/// One page of jobs plus the cursor for the next one.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobPage {
+    pub jobs: Vec<JobSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// What `GET /jobs` shows per job; `GET /jobs/{id}` has the full record.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSummary {
+    pub id: String,
+    pub sku: String,
+    pub marketplace: crate::models::MarketplaceId,
+    pub state: &'static str,
+    /// Stage that failed (or where a cancelled job stopped).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub attempts: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&JobRecord> for JobSummary {
+    fn from(record: &JobRecord) -> Self {
+        let (stage, error) = match &record.state {
+            JobState::Failed { error, stage }
+            | JobState::Retrying { error, stage, .. }
+            | JobState::DeadLettered { error, stage } => (stage.clone(), Some(error.clone())),
+            JobState::Cancelled { at_stage } => (at_stage.clone(), None),
+            _ => (None, None),
+        };
+        Self {
+            id: record.job.id.to_string(),
+            sku: record.job.request.sku.clone(),
+            marketplace: record.job.request.marketplace,
+            state: record.state.name(),
+            stage,
+            error,
+            attempts: record.attempts.len(),
+            publish_at: record.job.options.publish_at,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+/// Cut newest-first `records` (already filtered and past the cursor) down to
+/// one page.
+pub fn paginate(mut records: Vec<JobRecord>, limit: usize) -> JobPage {
+    let next_cursor = (records.len() > limit).then(|| {
+        records.truncate(limit);
+        records.last().map(JobCursor::after).map(|c| c.encode())
+    });
+    JobPage {
+        jobs: records.iter().map(JobSummary::from).collect(),
+        next_cursor: next_cursor.flatten(),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::pipeline::{PipelineError, PipelineErrorKind};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Internal failures from these stages are deterministic for a given request,
+// so re-running the pipeline cannot change the outcome.
+const TERMINAL_STAGES: &[&str] = &["build_listing", "supabase"];
+
+/// How failed jobs are retried before being dead-lettered.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Self {
+        let max_attempts = std::env::var("JOB_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(3);
+        let base_ms = std::env::var("JOB_RETRY_BASE_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(2_000);
+        let max_ms = std::env::var("JOB_RETRY_MAX_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v >= base_ms)
+            .unwrap_or(60_000.max(base_ms));
+        Self {
+            max_attempts,
+            base_delay: Duration::from_millis(base_ms),
+            max_delay: Duration::from_millis(max_ms),
+        }
+    }
+
+    /// Transient failures (eBay 5xx, network, LLM timeouts) surface as
+    /// `Internal`; bad input never succeeds on a second try. eBay errors say
+    /// for themselves, so a revoked token is not retried either.
+    pub fn is_retryable(&self, err: &PipelineError) -> bool {
+        if let Some(ebay) = err.ebay_error() {
+            return ebay.is_transient();
+        }
+        err.kind() == PipelineErrorKind::Internal && !TERMINAL_STAGES.contains(&err.stage())
+    }
+
+    /// Exponential backoff with "equal jitter": half the capped delay is
+    /// fixed, the other half random, so retries from a burst spread out.
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let exp = attempt.saturating_sub(1).min(16) as u32;
+        let capped = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(exp))
+            .min(self.max_delay);
+        let half = capped / 2;
+        let jitter_ms = rand::rng().random_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter_ms)
+    }
+}
+
+/// One pipeline run of a job, kept in the job's history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobAttempt {
+    pub attempt: usize,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ebay::EbayApiError;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1_000),
+        }
+    }
+
+    #[test]
+    fn classifies_pipeline_errors() {
+        let policy = policy();
+        assert!(policy.is_retryable(&PipelineError::internal("publish_offer", "HTTP 503")));
+        assert!(policy.is_retryable(&PipelineError::internal("push_inventory", "timeout")));
+        assert!(!policy.is_retryable(&PipelineError::invalid_input("resolve_images", "bad")));
+        assert!(!policy.is_retryable(&PipelineError::internal("build_listing", "draft")));
+
+        let ebay = |status: u16| {
+            let status = reqwest::StatusCode::from_u16(status).unwrap();
+            let err = EbayApiError::from_parts("publishOffer", status, None, "");
+            PipelineError::ebay("publish_offer", err)
+        };
+        assert!(policy.is_retryable(&ebay(503)));
+        assert!(policy.is_retryable(&ebay(429)));
+        assert!(!policy.is_retryable(&ebay(401)), "a bad token stays bad");
+        assert!(!policy.is_retryable(&ebay(400)));
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = policy();
+        for attempt in 1..=8 {
+            let delay = policy.backoff(attempt);
+            let ceiling = (policy.base_delay * 2u32.pow(attempt as u32 - 1)).min(policy.max_delay);
+            assert!(
+                delay >= ceiling / 2 && delay <= ceiling,
+                "{attempt}: {delay:?}"
+            );
+        }
+    }
+}
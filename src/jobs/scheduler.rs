@@ -0,0 +1,168 @@
+use super::Job;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::{Mutex, Notify, Semaphore};
+use uuid::Uuid;
+
+/// In-process dispatcher that hands jobs to workers round-robin across orgs,
+/// never running more than `per_org_limit` jobs for one org at a time.
+pub struct Scheduler {
+    state: Mutex<SchedulerState>,
+    ready: Notify,
+    slots: Semaphore,
+    per_org_limit: usize,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    queues: HashMap<String, VecDeque<Job>>,
+    // Orgs with queued work, in the order they will next be offered a worker.
+    rotation: VecDeque<String>,
+    in_flight: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrgQueueStats {
+    pub org_id: String,
+    pub queued: usize,
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+}
+
+impl Scheduler {
+    pub fn new(capacity: usize, per_org_limit: usize) -> Self {
+        Self {
+            state: Mutex::new(SchedulerState::default()),
+            ready: Notify::new(),
+            slots: Semaphore::new(capacity),
+            per_org_limit,
+        }
+    }
+
+    /// Queue a job, waiting for space when `capacity` jobs are already queued.
+    pub async fn push(&self, job: Job) {
+        if let Ok(permit) = self.slots.acquire().await {
+            permit.forget();
+        }
+        let mut guard = self.state.lock().await;
+        let org = job.context.org_id.clone();
+        let queue = guard.queues.entry(org.clone()).or_default();
+        queue.push_back(job);
+        if queue.len() == 1 && !guard.rotation.contains(&org) {
+            guard.rotation.push_back(org);
+        }
+        drop(guard);
+        self.ready.notify_waiters();
+    }
+
+    /// Wait for the next job an idle worker may run.
+    pub async fn next(&self) -> Job {
+        loop {
+            let notified = self.ready.notified();
+            if let Some(job) = self.try_next().await {
+                self.slots.add_permits(1);
+                return job;
+            }
+            notified.await;
+        }
+    }
+
+    async fn try_next(&self) -> Option<Job> {
+        let mut guard = self.state.lock().await;
+        let state = &mut *guard;
+        for _ in 0..state.rotation.len() {
+            let org = state.rotation.pop_front()?;
+            let running = state.in_flight.get(&org).copied().unwrap_or(0);
+            if running >= self.per_org_limit {
+                state.rotation.push_back(org);
+                continue;
+            }
+            let Some(queue) = state.queues.get_mut(&org) else {
+                continue;
+            };
+            let Some(job) = queue.pop_front() else {
+                state.queues.remove(&org);
+                continue;
+            };
+            if queue.is_empty() {
+                state.queues.remove(&org);
+            } else {
+                state.rotation.push_back(org.clone());
+            }
+            *state.in_flight.entry(org).or_insert(0) += 1;
+            return Some(job);
+        }
+        None
+    }
+
+    /// Drop a job that has not been handed to a worker yet.
+    pub async fn remove(&self, id: Uuid) -> bool {
+        let mut guard = self.state.lock().await;
+        let removed = guard.queues.values_mut().any(|queue| {
+            let before = queue.len();
+            queue.retain(|job| job.id != id);
+            queue.len() != before
+        });
+        if removed {
+            self.slots.add_permits(1);
+        }
+        removed
+    }
+
+    /// Release the org slot taken by a job returned from [`Scheduler::next`].
+    pub async fn finish(&self, org_id: &str) {
+        let mut guard = self.state.lock().await;
+        if let Some(count) = guard.in_flight.get_mut(org_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                guard.in_flight.remove(org_id);
+            }
+        }
+        drop(guard);
+        self.ready.notify_waiters();
+    }
+
+    pub async fn org_stats(&self, org_id: &str) -> OrgQueueStats {
+        let guard = self.state.lock().await;
+        OrgQueueStats {
+            org_id: org_id.to_string(),
+            queued: guard.queues.get(org_id).map_or(0, VecDeque::len),
+            in_flight: guard.in_flight.get(org_id).copied().unwrap_or(0),
+            max_in_flight: self.per_org_limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job_for(org: &str, sku: &str) -> Job {
+        let mut job = crate::jobs::tests::sample_job();
+        job.request.sku = sku.into();
+        job.context.org_id = org.into();
+        job
+    }
+
+    #[tokio::test]
+    async fn rotates_between_orgs_and_respects_caps() {
+        let scheduler = Scheduler::new(16, 1);
+        for sku in ["a1", "a2", "a3"] {
+            scheduler.push(job_for("org-a", sku)).await;
+        }
+        scheduler.push(job_for("org-b", "b1")).await;
+
+        let first = scheduler.next().await;
+        assert_eq!(first.request.sku, "a1");
+        // org-a is at its cap, so org-b gets the next worker.
+        let second = scheduler.next().await;
+        assert_eq!(second.request.sku, "b1");
+        assert!(scheduler.try_next().await.is_none());
+
+        let stats = scheduler.org_stats("org-a").await;
+        assert_eq!((stats.queued, stats.in_flight), (2, 1));
+
+        scheduler.finish("org-a").await;
+        assert_eq!(scheduler.next().await.request.sku, "a2");
+    }
+}
@@ -0,0 +1,1142 @@
+use super::{
+    Job, JobState,
+    query::{JobCursor, JobFilter},
+    retry::JobAttempt,
+    webhook::WebhookDelivery,
+};
+use crate::{
+    pipeline::PreparedListing,
+    retention::{Evicted, RetentionPolicy},
+};
+use chrono::{DateTime, Duration, Utc};
+use redis::{AsyncCommands, aio::ConnectionLike};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+const KEY_PREFIX: &str = "hermes:jobs";
+
+/// How often `modify` re-reads a record that keeps changing under it.
+/// Remove ids scored before `ARGV[1]` from the org index `KEYS[1]` unless
+/// they are in the active set `KEYS[2]`; returns how many were removed.
+const TRIM_SCRIPT: &str = r#"
+local removed = 0
+for _, id in ipairs(redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', '(' .. ARGV[1])) do
+  if redis.call('SISMEMBER', KEYS[2], id) == 0 then
+    redis.call('ZREM', KEYS[1], id)
+    removed = removed + 1
+  end
+end
+return removed
+"#;
+
+const MODIFY_ATTEMPTS: usize = 16;
+
+/// Compare-and-set for `modify`: write the record and its indexes only if it
+/// is still what was read. KEYS: record, org, active, commit. ARGV: expected
+/// JSON, new JSON, id, created_at (ms), TTL (`0` while the job is active).
+const MODIFY_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then return 0 end
+redis.call('SET', KEYS[1], ARGV[2])
+redis.call('ZADD', KEYS[2], ARGV[4], ARGV[3])
+if tonumber(ARGV[5]) > 0 then
+  redis.call('SREM', KEYS[3], ARGV[3])
+  redis.call('EXPIRE', KEYS[1], ARGV[5])
+  redis.call('EXPIRE', KEYS[4], ARGV[5])
+else
+  redis.call('SADD', KEYS[3], ARGV[3])
+  redis.call('PERSIST', KEYS[1])
+end
+return 1
+"#;
+
+#[derive(Debug, Error)]
+pub enum JobStoreError {
+    #[error("redis error: {0}")]
+    Redis(String),
+    #[error("invalid job record: {0}")]
+    Deserialize(String),
+    #[error("job {0} kept changing; update abandoned")]
+    Contended(Uuid),
+}
+
+/// Persisted job payload plus its latest state transition.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub job: Job,
+    pub state: JobState,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub attempts: Vec<JobAttempt>,
+    /// Index into `attempts` where the current retry budget starts; moved
+    /// forward when a dead-lettered job is requeued by hand.
+    #[serde(default)]
+    pub retry_window_start: usize,
+    #[serde(default)]
+    pub cancel_requested_at: Option<DateTime<Utc>>,
+    /// Built listing awaiting publication, for jobs with `publish_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prepared: Option<PreparedListing>,
+    /// Set when a job with a `callback_url` finishes and cleared once the
+    /// callback is delivered or given up on, so a restart resumes it.
+    #[serde(default)]
+    pub callback_pending: bool,
+}
+
+/// Irreversible decision about a job's fate; the first one recorded wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobCommit {
+    Cancel,
+    Publish,
+}
+
+impl JobCommit {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Cancel => "cancel",
+            Self::Publish => "publish",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cancel" => Some(Self::Cancel),
+            "publish" => Some(Self::Publish),
+            _ => None,
+        }
+    }
+}
+
+impl JobRecord {
+    pub fn queued(job: Job) -> Self {
+        let now = Utc::now();
+        Self {
+            job,
+            state: JobState::Queued,
+            created_at: now,
+            updated_at: now,
+            attempts: Vec::new(),
+            retry_window_start: 0,
+            cancel_requested_at: None,
+            prepared: None,
+            callback_pending: false,
+        }
+    }
+
+    /// Work only a claim holder does: a run in progress or a pending
+    /// callback. Recovery takes it over once the claim lapses.
+    fn is_claimed_work(&self) -> bool {
+        matches!(self.state, JobState::Running) || self.callback_pending
+    }
+
+    /// Finished with nothing left to do, so the record may expire.
+    fn is_settled(&self) -> bool {
+        self.state.is_terminal() && !self.callback_pending
+    }
+
+    /// Attempts made since the job was (re)queued.
+    pub fn attempts_in_window(&self) -> usize {
+        self.attempts.len().saturating_sub(self.retry_window_start)
+    }
+
+    /// Whether a worker should pick this job up now.
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        match &self.state {
+            JobState::Queued => true,
+            JobState::Retrying { retry_at, .. } => *retry_at <= now,
+            JobState::Scheduled { publish_at } => *publish_at <= now,
+            _ => false,
+        }
+    }
+}
+
+/// Storage backend for job records and worker claims.
+///
+/// `Memory` keeps everything in-process (tests, single-replica demos);
+/// `Redis` survives restarts and lets any replica resume unfinished jobs.
+#[derive(Clone)]
+pub enum JobStore {
+    Memory(MemoryJobStore),
+    Redis(RedisJobStore),
+}
+
+impl JobStore {
+    pub fn memory() -> Self {
+        Self::Memory(MemoryJobStore::default())
+    }
+
+    /// Redis when a client is configured, unless `JOB_BACKEND=memory`.
+    /// Finished Redis records expire after `JOB_RETENTION_SECS`.
+    pub fn from_env(redis: Option<redis::Client>) -> Self {
+        let backend = std::env::var("JOB_BACKEND")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        match redis {
+            Some(client) if backend != "memory" => Self::Redis(RedisJobStore::new(
+                client,
+                RetentionPolicy::jobs_from_env().ttl,
+            )),
+            _ => Self::memory(),
+        }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        match self {
+            Self::Memory(_) => "memory",
+            Self::Redis(_) => "redis",
+        }
+    }
+
+    pub async fn insert(&self, record: &JobRecord) -> Result<(), JobStoreError> {
+        match self {
+            Self::Memory(store) => {
+                store.insert(record).await;
+                Ok(())
+            }
+            Self::Redis(store) => store.insert(record).await,
+        }
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<JobRecord>, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store.get(id).await),
+            Self::Redis(store) => store.get(id).await,
+        }
+    }
+
+    /// Apply `f` to the stored record and persist it, returning the result.
+    /// `f` runs again on the fresh record if another writer got there first,
+    /// so it should only set fields, not consume anything.
+    pub async fn modify<F>(&self, id: Uuid, mut f: F) -> Result<Option<JobRecord>, JobStoreError>
+    where
+        F: FnMut(&mut JobRecord),
+    {
+        self.update(id, |record| {
+            f(record);
+            true
+        })
+        .await
+    }
+
+    /// Like [`Self::modify`], but `f` decides from the current record whether
+    /// to write; when it returns `false` the stored record is left untouched
+    /// and returned as is. A write that finishes a job with a callback marks
+    /// the callback pending in the same write.
+    pub async fn update<F>(&self, id: Uuid, mut f: F) -> Result<Option<JobRecord>, JobStoreError>
+    where
+        F: FnMut(&mut JobRecord) -> bool,
+    {
+        let f = move |record: &mut JobRecord| {
+            let was_terminal = record.state.is_terminal();
+            let write = f(record);
+            if write
+                && !was_terminal
+                && record.state.is_terminal()
+                && record.job.options.callback_url.is_some()
+            {
+                record.callback_pending = true;
+            }
+            write
+        };
+        match self {
+            Self::Memory(store) => Ok(store.update(id, f).await),
+            Self::Redis(store) => store.update(id, f).await,
+        }
+    }
+
+    /// Take an exclusive, expiring claim on a job for `worker_id`.
+    pub async fn claim(
+        &self,
+        id: Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<bool, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store.claim(id, worker_id, lease).await),
+            Self::Redis(store) => store.claim(id, worker_id, lease).await,
+        }
+    }
+
+    /// Extend `worker_id`'s claim by `lease`; `false` once it lapsed or
+    /// another worker holds it.
+    pub async fn renew(
+        &self,
+        id: Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<bool, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store.renew(id, worker_id, lease).await),
+            Self::Redis(store) => store.renew(id, worker_id, lease).await,
+        }
+    }
+
+    /// Drop `worker_id`'s claim, leaving one another worker took over alone.
+    pub async fn release(&self, id: Uuid, worker_id: &str) -> Result<(), JobStoreError> {
+        match self {
+            Self::Memory(store) => {
+                store.release(id, worker_id).await;
+                Ok(())
+            }
+            Self::Redis(store) => store.release(id, worker_id).await,
+        }
+    }
+
+    /// Record `commit` unless another decision was recorded first, returning
+    /// whichever one is in effect.
+    pub async fn commit(&self, id: Uuid, commit: JobCommit) -> Result<JobCommit, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store.commit(id, commit).await),
+            Self::Redis(store) => store.commit(id, commit).await,
+        }
+    }
+
+    pub async fn committed(&self, id: Uuid) -> Result<Option<JobCommit>, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store.commits.lock().await.get(&id).copied()),
+            Self::Redis(store) => store.committed(id).await,
+        }
+    }
+
+    /// Drop a `Publish` decision so a retried attempt can still be cancelled.
+    pub async fn reopen(&self, id: Uuid) -> Result<(), JobStoreError> {
+        match self {
+            Self::Memory(store) => {
+                let mut guard = store.commits.lock().await;
+                if guard.get(&id) == Some(&JobCommit::Publish) {
+                    guard.remove(&id);
+                }
+                Ok(())
+            }
+            Self::Redis(store) => store.reopen(id).await,
+        }
+    }
+
+    /// Jobs matching `filter`, newest first, starting after `cursor`. Returns
+    /// up to `limit + 1` records so the caller can tell whether more follow.
+    pub async fn list(
+        &self,
+        filter: &JobFilter,
+        cursor: Option<JobCursor>,
+        limit: usize,
+    ) -> Result<Vec<JobRecord>, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store.list(filter, cursor, limit).await),
+            Self::Redis(store) => store.list(filter, cursor, limit).await,
+        }
+    }
+
+    /// Drop finished jobs older than `policy.ttl`, then the oldest finished
+    /// jobs beyond `policy.max_entries`. Redis expires finished records on
+    /// its own, so there this only trims the org indexes by age and
+    /// `max_entries` applies to the in-memory backend alone.
+    pub async fn evict(&self, policy: &RetentionPolicy) -> Evicted {
+        match self {
+            Self::Memory(store) => store.evict(policy).await,
+            Self::Redis(store) => store.evict(policy).await.unwrap_or_else(|err| {
+                warn!(target = "hermes.jobs", error = %err, "job_index_trim_failed");
+                Evicted::default()
+            }),
+        }
+    }
+
+    /// Append a callback delivery attempt to the job's delivery log.
+    pub async fn push_delivery(
+        &self,
+        id: Uuid,
+        delivery: &WebhookDelivery,
+    ) -> Result<(), JobStoreError> {
+        match self {
+            Self::Memory(store) => {
+                let mut guard = store.deliveries.lock().await;
+                guard.entry(id).or_default().push(delivery.clone());
+                Ok(())
+            }
+            Self::Redis(store) => store.push_delivery(id, delivery).await,
+        }
+    }
+
+    pub async fn deliveries(&self, id: Uuid) -> Result<Vec<WebhookDelivery>, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store
+                .deliveries
+                .lock()
+                .await
+                .get(&id)
+                .cloned()
+                .unwrap_or_default()),
+            Self::Redis(store) => store.deliveries(id).await,
+        }
+    }
+
+    /// Jobs a worker should pick up: everything still `Queued`, retries whose
+    /// backoff has elapsed, scheduled jobs whose `publish_at` has come, plus
+    /// `Running` jobs and pending callbacks whose claim has lapsed (the
+    /// owning worker died).
+    pub async fn recoverable(&self) -> Result<Vec<JobRecord>, JobStoreError> {
+        match self {
+            Self::Memory(store) => Ok(store.recoverable().await),
+            Self::Redis(store) => store.recoverable().await,
+        }
+    }
+}
+
+/// The worker holding a job and when its lease ends.
+struct Claim {
+    owner: String,
+    expires: DateTime<Utc>,
+}
+
+#[derive(Clone, Default)]
+pub struct MemoryJobStore {
+    records: Arc<Mutex<HashMap<Uuid, JobRecord>>>,
+    claims: Arc<Mutex<HashMap<Uuid, Claim>>>,
+    commits: Arc<Mutex<HashMap<Uuid, JobCommit>>>,
+    deliveries: Arc<Mutex<HashMap<Uuid, Vec<WebhookDelivery>>>>,
+}
+
+impl MemoryJobStore {
+    async fn insert(&self, record: &JobRecord) {
+        let mut guard = self.records.lock().await;
+        guard.insert(record.job.id, record.clone());
+    }
+
+    async fn get(&self, id: Uuid) -> Option<JobRecord> {
+        let guard = self.records.lock().await;
+        guard.get(&id).cloned()
+    }
+
+    async fn update<F>(&self, id: Uuid, mut f: F) -> Option<JobRecord>
+    where
+        F: FnMut(&mut JobRecord) -> bool,
+    {
+        let mut guard = self.records.lock().await;
+        let record = guard.get_mut(&id)?;
+        let mut updated = record.clone();
+        if f(&mut updated) {
+            updated.updated_at = Utc::now();
+            *record = updated;
+        }
+        Some(record.clone())
+    }
+
+    async fn claim(&self, id: Uuid, worker_id: &str, lease: Duration) -> bool {
+        let mut guard = self.claims.lock().await;
+        let now = Utc::now();
+        match guard.get(&id) {
+            Some(claim) if claim.expires > now => false,
+            _ => {
+                let claim = Claim {
+                    owner: worker_id.to_string(),
+                    expires: now + lease,
+                };
+                guard.insert(id, claim);
+                true
+            }
+        }
+    }
+
+    async fn renew(&self, id: Uuid, worker_id: &str, lease: Duration) -> bool {
+        let mut guard = self.claims.lock().await;
+        let now = Utc::now();
+        match guard.get_mut(&id) {
+            Some(claim) if claim.owner == worker_id && claim.expires > now => {
+                claim.expires = now + lease;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    async fn release(&self, id: Uuid, worker_id: &str) {
+        let mut guard = self.claims.lock().await;
+        if guard.get(&id).is_some_and(|claim| claim.owner == worker_id) {
+            guard.remove(&id);
+        }
+    }
+
+    async fn commit(&self, id: Uuid, commit: JobCommit) -> JobCommit {
+        let mut guard = self.commits.lock().await;
+        *guard.entry(id).or_insert(commit)
+    }
+
+    async fn list(
+        &self,
+        filter: &JobFilter,
+        cursor: Option<JobCursor>,
+        limit: usize,
+    ) -> Vec<JobRecord> {
+        let guard = self.records.lock().await;
+        let mut out = guard
+            .values()
+            .filter(|record| filter.matches(record))
+            .filter(|record| cursor.is_none_or(|c| c.precedes(record)))
+            .cloned()
+            .collect::<Vec<_>>();
+        out.sort_by_key(|record| {
+            std::cmp::Reverse((record.created_at.timestamp_millis(), record.job.id))
+        });
+        out.truncate(limit + 1);
+        out
+    }
+
+    async fn evict(&self, policy: &RetentionPolicy) -> Evicted {
+        let cutoff = Utc::now() - Duration::from_std(policy.ttl).unwrap_or(Duration::MAX);
+        let mut records = self.records.lock().await;
+        let mut finished = records
+            .values()
+            .filter(|record| record.state.is_terminal())
+            .map(|record| (record.updated_at, record.job.id))
+            .collect::<Vec<_>>();
+        finished.sort();
+        let expired = finished.partition_point(|(updated_at, _)| *updated_at < cutoff);
+        let over_capacity = (finished.len() - expired).saturating_sub(policy.max_entries);
+        let removed = &finished[..expired + over_capacity];
+        for (_, id) in removed {
+            records.remove(id);
+        }
+        drop(records);
+
+        let mut claims = self.claims.lock().await;
+        let mut commits = self.commits.lock().await;
+        let mut deliveries = self.deliveries.lock().await;
+        for (_, id) in removed {
+            claims.remove(id);
+            commits.remove(id);
+            deliveries.remove(id);
+        }
+        Evicted {
+            expired,
+            over_capacity,
+        }
+    }
+
+    async fn recoverable(&self) -> Vec<JobRecord> {
+        let now = Utc::now();
+        let claims = self.claims.lock().await;
+        let records = self.records.lock().await;
+        let mut out = records
+            .values()
+            .filter(|record| {
+                if record.is_claimed_work() {
+                    claims
+                        .get(&record.job.id)
+                        .is_none_or(|claim| claim.expires <= now)
+                } else {
+                    record.is_due(now)
+                }
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        out.sort_by_key(|record| record.created_at);
+        out
+    }
+}
+
+#[derive(Clone)]
+pub struct RedisJobStore {
+    client: redis::Client,
+    // How long a finished job's keys live before Redis expires them.
+    retention: std::time::Duration,
+}
+
+impl RedisJobStore {
+    pub fn new(client: redis::Client, retention: std::time::Duration) -> Self {
+        Self { client, retention }
+    }
+
+    fn retention_secs(&self) -> i64 {
+        self.retention.as_secs().max(1) as i64
+    }
+
+    fn record_key(id: Uuid) -> String {
+        format!("{KEY_PREFIX}:{id}")
+    }
+
+    fn claim_key(id: Uuid) -> String {
+        format!("{KEY_PREFIX}:claim:{id}")
+    }
+
+    fn commit_key(id: Uuid) -> String {
+        format!("{KEY_PREFIX}:commit:{id}")
+    }
+
+    fn deliveries_key(id: Uuid) -> String {
+        format!("{KEY_PREFIX}:deliveries:{id}")
+    }
+
+    /// Sorted set of an org's job ids scored by `created_at` (ms).
+    fn org_key(org_id: &str) -> String {
+        format!("{KEY_PREFIX}:org:{org_id}")
+    }
+
+    fn active_key() -> String {
+        format!("{KEY_PREFIX}:active")
+    }
+
+    /// Trim every org index of jobs created before `policy.ttl` ago.
+    async fn evict(&self, policy: &RetentionPolicy) -> Result<Evicted, JobStoreError> {
+        let mut conn = self.conn().await?;
+        let mut org_keys = Vec::new();
+        {
+            let mut iter = conn
+                .scan_match::<_, String>(Self::org_key("*"))
+                .await
+                .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+            while let Some(key) = iter.next_item().await {
+                org_keys.push(key);
+            }
+        }
+        let cutoff = Utc::now() - Duration::from_std(policy.ttl).unwrap_or(Duration::MAX);
+        let mut expired = 0;
+        for key in org_keys {
+            expired += self.trim_org(&mut conn, &key, cutoff).await?;
+        }
+        Ok(Evicted {
+            expired,
+            over_capacity: 0,
+        })
+    }
+
+    /// `ZREMRANGEBYSCORE` would also drop jobs that are still active, such
+    /// as a publication scheduled far ahead, so those are skipped.
+    async fn trim_org<C: ConnectionLike>(
+        &self,
+        conn: &mut C,
+        org_key: &str,
+        cutoff: DateTime<Utc>,
+    ) -> Result<usize, JobStoreError> {
+        redis::Script::new(TRIM_SCRIPT)
+            .key(org_key)
+            .key(Self::active_key())
+            .arg(cutoff.timestamp_millis())
+            .invoke_async::<usize>(conn)
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn conn(&self) -> Result<redis::aio::MultiplexedConnection, JobStoreError> {
+        self.client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn write(
+        &self,
+        conn: &mut redis::aio::MultiplexedConnection,
+        record: &JobRecord,
+    ) -> Result<(), JobStoreError> {
+        let json = serde_json::to_string(record)
+            .map_err(|err| JobStoreError::Deserialize(err.to_string()))?;
+        let id = record.job.id;
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .set(Self::record_key(id), json)
+            .ignore()
+            .zadd(
+                Self::org_key(&record.job.context.org_id),
+                id.to_string(),
+                record.created_at.timestamp_millis(),
+            )
+            .ignore();
+        if record.is_settled() {
+            let ttl = self.retention_secs();
+            pipe.srem(Self::active_key(), id.to_string())
+                .ignore()
+                .expire(Self::record_key(id), ttl)
+                .ignore()
+                .expire(Self::commit_key(id), ttl)
+                .ignore();
+        } else {
+            // A requeued job must not inherit the expiry of its finished state.
+            pipe.sadd(Self::active_key(), id.to_string())
+                .ignore()
+                .persist(Self::record_key(id))
+                .ignore();
+        }
+        pipe.query_async::<()>(conn)
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn insert(&self, record: &JobRecord) -> Result<(), JobStoreError> {
+        let mut conn = self.conn().await?;
+        self.write(&mut conn, record).await
+    }
+
+    async fn read<C: ConnectionLike>(
+        &self,
+        conn: &mut C,
+        id: Uuid,
+    ) -> Result<Option<JobRecord>, JobStoreError> {
+        self.read_raw(conn, id)
+            .await?
+            .map(|value| parse_record(&value))
+            .transpose()
+    }
+
+    async fn read_raw<C: ConnectionLike>(
+        &self,
+        conn: &mut C,
+        id: Uuid,
+    ) -> Result<Option<String>, JobStoreError> {
+        redis::cmd("GET")
+            .arg(Self::record_key(id))
+            .query_async(conn)
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<JobRecord>, JobStoreError> {
+        let mut conn = self.conn().await?;
+        self.read(&mut conn, id).await
+    }
+
+    async fn update<F>(&self, id: Uuid, f: F) -> Result<Option<JobRecord>, JobStoreError>
+    where
+        F: FnMut(&mut JobRecord) -> bool,
+    {
+        let mut conn = self.conn().await?;
+        self.update_on(&mut conn, id, f).await
+    }
+
+    /// Read, apply `f` and compare-and-set, starting over whenever another
+    /// writer changed the record in between. A Lua script rather than
+    /// `WATCH`, which a shared multiplexed connection cannot scope.
+    async fn update_on<C, F>(
+        &self,
+        conn: &mut C,
+        id: Uuid,
+        mut f: F,
+    ) -> Result<Option<JobRecord>, JobStoreError>
+    where
+        C: ConnectionLike,
+        F: FnMut(&mut JobRecord) -> bool,
+    {
+        let script = redis::Script::new(MODIFY_SCRIPT);
+        for _ in 0..MODIFY_ATTEMPTS {
+            let Some(expected) = self.read_raw(conn, id).await? else {
+                return Ok(None);
+            };
+            let mut record = parse_record(&expected)?;
+            if !f(&mut record) {
+                return parse_record(&expected).map(Some);
+            }
+            record.updated_at = Utc::now();
+            let json = serde_json::to_string(&record)
+                .map_err(|err| JobStoreError::Deserialize(err.to_string()))?;
+            let ttl = if record.is_settled() {
+                self.retention_secs()
+            } else {
+                0
+            };
+            let written: i64 = script
+                .key(Self::record_key(id))
+                .key(Self::org_key(&record.job.context.org_id))
+                .key(Self::active_key())
+                .key(Self::commit_key(id))
+                .arg(expected)
+                .arg(json)
+                .arg(id.to_string())
+                .arg(record.created_at.timestamp_millis())
+                .arg(ttl)
+                .invoke_async(conn)
+                .await
+                .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+            if written == 1 {
+                return Ok(Some(record));
+            }
+        }
+        Err(JobStoreError::Contended(id))
+    }
+
+    async fn claim(
+        &self,
+        id: Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<bool, JobStoreError> {
+        let mut conn = self.conn().await?;
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(Self::claim_key(id))
+            .arg(worker_id)
+            .arg("NX")
+            .arg("PX")
+            .arg(lease.num_milliseconds().max(1))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+        Ok(acquired.is_some())
+    }
+
+    async fn renew(
+        &self,
+        id: Uuid,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<bool, JobStoreError> {
+        let script = redis::Script::new(
+            "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('PEXPIRE', KEYS[1], ARGV[2]) end return 0",
+        );
+        let mut conn = self.conn().await?;
+        script
+            .key(Self::claim_key(id))
+            .arg(worker_id)
+            .arg(lease.num_milliseconds().max(1))
+            .invoke_async::<i64>(&mut conn)
+            .await
+            .map(|renewed| renewed == 1)
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn release(&self, id: Uuid, worker_id: &str) -> Result<(), JobStoreError> {
+        // Compare-and-delete so a lapsed worker never frees a claim another
+        // worker has since taken.
+        let script = redis::Script::new(
+            "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) end return 0",
+        );
+        let mut conn = self.conn().await?;
+        script
+            .key(Self::claim_key(id))
+            .arg(worker_id)
+            .invoke_async::<i64>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn commit(&self, id: Uuid, commit: JobCommit) -> Result<JobCommit, JobStoreError> {
+        let mut conn = self.conn().await?;
+        let set: bool = conn
+            .set_nx(Self::commit_key(id), commit.as_str())
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+        if set {
+            return Ok(commit);
+        }
+        let existing: Option<String> = conn
+            .get(Self::commit_key(id))
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+        Ok(existing
+            .as_deref()
+            .and_then(JobCommit::parse)
+            .unwrap_or(commit))
+    }
+
+    async fn committed(&self, id: Uuid) -> Result<Option<JobCommit>, JobStoreError> {
+        let mut conn = self.conn().await?;
+        let existing: Option<String> = conn
+            .get(Self::commit_key(id))
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+        Ok(existing.as_deref().and_then(JobCommit::parse))
+    }
+
+    async fn reopen(&self, id: Uuid) -> Result<(), JobStoreError> {
+        // Compare-and-delete so a `cancel` recorded meanwhile survives.
+        let script = redis::Script::new(
+            "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) end return 0",
+        );
+        let mut conn = self.conn().await?;
+        script
+            .key(Self::commit_key(id))
+            .arg(JobCommit::Publish.as_str())
+            .invoke_async::<i64>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn list(
+        &self,
+        filter: &JobFilter,
+        cursor: Option<JobCursor>,
+        limit: usize,
+    ) -> Result<Vec<JobRecord>, JobStoreError> {
+        const BATCH: isize = 100;
+        let key = Self::org_key(&filter.org_id);
+        let max = cursor.map_or("+inf".to_string(), |c| c.created_at_ms.to_string());
+        let min = filter.since.map_or("-inf".to_string(), |since| {
+            since.timestamp_millis().to_string()
+        });
+        let mut conn = self.conn().await?;
+        let mut out = Vec::new();
+        let mut offset = 0;
+        loop {
+            // Equal scores come back in descending member order, which matches
+            // the `(created_at, id)` ordering cursors rely on.
+            let ids: Vec<String> = conn
+                .zrevrangebyscore_limit(&key, &max, &min, offset, BATCH)
+                .await
+                .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+            if ids.is_empty() {
+                return Ok(out);
+            }
+            offset += ids.len() as isize;
+            for raw in ids {
+                let Ok(id) = Uuid::parse_str(&raw) else {
+                    continue;
+                };
+                let Some(record) = self.read(&mut conn, id).await? else {
+                    let _: Result<(), _> = conn.zrem(&key, &raw).await;
+                    continue;
+                };
+                if cursor.is_some_and(|c| !c.precedes(&record)) || !filter.matches(&record) {
+                    continue;
+                }
+                out.push(record);
+                if out.len() > limit {
+                    return Ok(out);
+                }
+            }
+        }
+    }
+
+    async fn push_delivery(
+        &self,
+        id: Uuid,
+        delivery: &WebhookDelivery,
+    ) -> Result<(), JobStoreError> {
+        // A list of its own, so logging never races state updates on the record.
+        let json = serde_json::to_string(delivery)
+            .map_err(|err| JobStoreError::Deserialize(err.to_string()))?;
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .rpush(Self::deliveries_key(id), json)
+            .ignore()
+            .expire(Self::deliveries_key(id), self.retention_secs())
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))
+    }
+
+    async fn deliveries(&self, id: Uuid) -> Result<Vec<WebhookDelivery>, JobStoreError> {
+        let mut conn = self.conn().await?;
+        let raw: Vec<String> = conn
+            .lrange(Self::deliveries_key(id), 0, -1)
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+        raw.iter()
+            .map(|value| {
+                serde_json::from_str(value)
+                    .map_err(|err| JobStoreError::Deserialize(err.to_string()))
+            })
+            .collect()
+    }
+
+    async fn recoverable(&self) -> Result<Vec<JobRecord>, JobStoreError> {
+        let mut conn = self.conn().await?;
+        let ids: Vec<String> = conn
+            .smembers(Self::active_key())
+            .await
+            .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+        let now = Utc::now();
+        let mut out = Vec::new();
+        for raw in ids {
+            let Ok(id) = Uuid::parse_str(&raw) else {
+                continue;
+            };
+            let Some(record) = self.read(&mut conn, id).await? else {
+                let _: Result<(), _> = conn.srem(Self::active_key(), &raw).await;
+                continue;
+            };
+            let pick = if record.is_claimed_work() {
+                let claimed: bool = conn
+                    .exists(Self::claim_key(id))
+                    .await
+                    .map_err(|err| JobStoreError::Redis(err.to_string()))?;
+                !claimed
+            } else {
+                record.is_due(now)
+            };
+            if pick {
+                out.push(record);
+            }
+        }
+        out.sort_by_key(|record| record.created_at);
+        Ok(out)
+    }
+}
+
+fn parse_record(raw: &str) -> Result<JobRecord, JobStoreError> {
+    serde_json::from_str(raw).map_err(|err| JobStoreError::Deserialize(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::tests::sample_job;
+    use crate::redis_stand_in::RedisStandIn;
+
+    /// Strings, sets and sorted sets, enough for [`MODIFY_SCRIPT`] and
+    /// [`TRIM_SCRIPT`].
+    const COMMANDS: &str = r#"
+        store = {}
+        sets = {}
+        zsets = {}
+        redis = { call = function(cmd, key, ...)
+          local args = {...}
+          if cmd == 'GET' then
+            return store[key] or false
+          elseif cmd == 'SET' then
+            store[key] = args[1]
+            return 'OK'
+          elseif cmd == 'SADD' then
+            sets[key] = sets[key] or {}
+            sets[key][args[1]] = true
+            return 1
+          elseif cmd == 'SREM' then
+            if sets[key] then sets[key][args[1]] = nil end
+            return 1
+          elseif cmd == 'SISMEMBER' then
+            return (sets[key] and sets[key][args[1]]) and 1 or 0
+          elseif cmd == 'ZADD' then
+            zsets[key] = zsets[key] or {}
+            zsets[key][args[2]] = tonumber(args[1])
+            return 1
+          elseif cmd == 'ZREM' then
+            if zsets[key] then zsets[key][args[1]] = nil end
+            return 1
+          elseif cmd == 'ZRANGEBYSCORE' then
+            local below = tonumber(string.sub(args[2], 2))
+            local out = {}
+            for member, score in pairs(zsets[key] or {}) do
+              if score < below then table.insert(out, member) end
+            end
+            return out
+          elseif cmd == 'EXPIRE' or cmd == 'PERSIST' then
+            return 1
+          end
+          error('unsupported command ' .. cmd)
+        end }
+    "#;
+
+    fn set(lua: &mlua::Lua, key: &str, value: &str) {
+        let store: mlua::Table = lua.globals().get("store").unwrap();
+        store.set(key, value).unwrap();
+    }
+
+    fn is_active(redis: &RedisStandIn, id: Uuid) -> bool {
+        let sets: mlua::Table = redis.lua.globals().get("sets").unwrap();
+        sets.get::<_, Option<mlua::Table>>(RedisJobStore::active_key())
+            .unwrap()
+            .is_some_and(|set| set.get::<_, bool>(id.to_string()).unwrap_or(false))
+    }
+
+    #[tokio::test]
+    async fn claims_are_renewed_and_released_only_by_their_owner() {
+        let store = JobStore::memory();
+        let id = Uuid::new_v4();
+        let lease = Duration::minutes(5);
+        assert!(store.claim(id, "a", lease).await.unwrap());
+        assert!(!store.claim(id, "b", lease).await.unwrap());
+        assert!(!store.renew(id, "b", lease).await.unwrap());
+
+        store.release(id, "b").await.unwrap();
+        assert!(
+            !store.claim(id, "c", lease).await.unwrap(),
+            "still held by a"
+        );
+        assert!(store.renew(id, "a", lease).await.unwrap());
+
+        store.release(id, "a").await.unwrap();
+        assert!(store.claim(id, "c", lease).await.unwrap());
+        assert!(!store.renew(id, "a", lease).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn concurrent_modifies_do_not_lose_writes() {
+        let store = RedisJobStore::new(
+            redis::Client::open("redis://127.0.0.1/").unwrap(),
+            std::time::Duration::from_secs(60),
+        );
+        let mut record = JobRecord::queued(sample_job());
+        record.state = JobState::Running;
+        let id = record.job.id;
+        let key = RedisJobStore::record_key(id);
+        let mut conn = RedisStandIn::new(COMMANDS);
+        set(&conn.lua, &key, &serde_json::to_string(&record).unwrap());
+
+        // A cancel lands between the worker's read and its write.
+        let mut cancelled = record.clone();
+        cancelled.cancel_requested_at = Some(Utc::now());
+        let raced = serde_json::to_string(&cancelled).unwrap();
+        conn.on_next_command(move |lua| set(lua, &key, &raced));
+
+        let mut runs = 0;
+        let updated = store
+            .update_on(&mut conn, id, |record| {
+                runs += 1;
+                record.state = JobState::Queued;
+                true
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(runs, 2, "the stale write was refused and redone");
+        assert!(matches!(updated.state, JobState::Queued));
+        assert!(
+            updated.cancel_requested_at.is_some(),
+            "the concurrent cancel survived"
+        );
+        let stored = store.read(&mut conn, id).await.unwrap().unwrap();
+        assert!(stored.cancel_requested_at.is_some());
+        assert!(is_active(&conn, id));
+
+        assert!(
+            store
+                .update_on(&mut conn, Uuid::new_v4(), |_| true)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn trimming_an_org_index_keeps_recent_and_active_jobs() {
+        let store = RedisJobStore::new(
+            redis::Client::open("redis://127.0.0.1:1").unwrap(),
+            std::time::Duration::from_secs(60),
+        );
+        let mut conn = RedisStandIn::new(COMMANDS);
+        let org = RedisJobStore::org_key("acme");
+        let now = Utc::now();
+        let (old, old_active, recent) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        conn.lua
+            .load(format!(
+                "zsets['{org}'] = {{ ['{old}'] = {}, ['{old_active}'] = {}, ['{recent}'] = {} }}
+                 sets['{}'] = {{ ['{old_active}'] = true }}",
+                (now - Duration::days(3)).timestamp_millis(),
+                (now - Duration::days(3)).timestamp_millis(),
+                now.timestamp_millis(),
+                RedisJobStore::active_key(),
+            ))
+            .exec()
+            .unwrap();
+
+        let removed = store
+            .trim_org(&mut conn, &org, now - Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+        let remaining: Vec<String> = conn
+            .lua
+            .load(format!(
+                "local out = {{}} for m in pairs(zsets['{org}']) do table.insert(out, m) end return out"
+            ))
+            .eval()
+            .unwrap();
+        assert!(!remaining.contains(&old.to_string()));
+        assert!(remaining.contains(&old_active.to_string()));
+        assert!(remaining.contains(&recent.to_string()));
+    }
+}
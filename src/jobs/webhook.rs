@@ -0,0 +1,534 @@
+use super::{Job, JobInfo, JobState, JobStore, RetryPolicy};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::sleep;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub const SIGNATURE_HEADER: &str = "X-Hermes-Signature";
+/// Unix seconds the delivery was signed at; part of the signed message.
+pub const TIMESTAMP_HEADER: &str = "X-Hermes-Timestamp";
+pub const EVENT_HEADER: &str = "X-Hermes-Event";
+pub const DELIVERY_HEADER: &str = "X-Hermes-Delivery";
+
+/// One attempt to POST a job's terminal state to its `callback_url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub delivery_id: Uuid,
+    pub event: String,
+    pub attempt: usize,
+    pub attempted_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub delivered: bool,
+}
+
+/// Signs and delivers job callbacks, retrying failed deliveries with the
+/// same backoff shape as job retries. Loopback, private and link-local
+/// destinations are refused, checked on the addresses a callback host
+/// resolves to when it is delivered, unless `allow_private` is set.
+pub struct WebhookSender {
+    http: reqwest::Client,
+    secrets: HashMap<String, String>,
+    fallback_secret: Option<String>,
+    policy: RetryPolicy,
+    allow_private: bool,
+}
+
+impl WebhookSender {
+    pub fn new(
+        secrets: HashMap<String, String>,
+        fallback_secret: Option<String>,
+        policy: RetryPolicy,
+        allow_private: bool,
+    ) -> Self {
+        // Redirects are not followed, so a callback cannot bounce the worker
+        // to an address the resolver never saw.
+        let mut builder = crate::http::client_builder().redirect(reqwest::redirect::Policy::none());
+        if !allow_private {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        Self {
+            http: builder.build().unwrap_or_else(|_| reqwest::Client::new()),
+            secrets,
+            fallback_secret,
+            policy,
+            allow_private,
+        }
+    }
+
+    /// Per-org secrets come from `WEBHOOK_SECRETS` (`org:secret,...`);
+    /// `WEBHOOK_SECRET` covers orgs without their own entry.
+    /// `WEBHOOK_ALLOW_PRIVATE=1` lets callbacks reach private addresses, for
+    /// local test harnesses only.
+    pub fn from_env() -> Self {
+        let mut secrets = HashMap::new();
+        let raw = std::env::var("WEBHOOK_SECRETS").unwrap_or_default();
+        for entry in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match entry.split_once(':') {
+                Some((org, secret)) if !org.trim().is_empty() && !secret.trim().is_empty() => {
+                    secrets.insert(org.trim().to_string(), secret.trim().to_string());
+                }
+                _ => warn!(
+                    target = "hermes.jobs",
+                    "ignored malformed WEBHOOK_SECRETS entry"
+                ),
+            }
+        }
+        let fallback_secret = std::env::var("WEBHOOK_SECRET")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let max_attempts = std::env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(5);
+        let base_ms = std::env::var("WEBHOOK_RETRY_BASE_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(1_000);
+        let policy = RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(base_ms),
+            max_delay: Duration::from_millis(60_000.max(base_ms)),
+        };
+        let allow_private = std::env::var("WEBHOOK_ALLOW_PRIVATE")
+            .map(|v| matches!(v.trim(), "1" | "true"))
+            .unwrap_or(false);
+        Self::new(secrets, fallback_secret, policy, allow_private)
+    }
+
+    pub fn secret_for(&self, org_id: &str) -> Option<&str> {
+        self.secrets
+            .get(org_id)
+            .or(self.fallback_secret.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Reject callback URLs that could never be delivered to, before the job
+    /// is accepted.
+    pub fn validate(&self, org_id: &str, url: &str) -> Result<(), &'static str> {
+        let parsed = reqwest::Url::parse(url).map_err(|_| "invalid_callback_url")?;
+        if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+            return Err("invalid_callback_url");
+        }
+        if !self.allow_private && (is_localhost(&parsed) || !is_public_literal(&parsed)) {
+            return Err("callback_url_not_allowed");
+        }
+        if self.secret_for(org_id).is_none() {
+            return Err("callback_secret_not_configured");
+        }
+        Ok(())
+    }
+
+    /// POST `info` to the job's callback URL until it is acknowledged with a
+    /// 2xx or attempts run out, logging every attempt in `store` and picking
+    /// up after any attempts already logged there.
+    pub async fn deliver(&self, store: &JobStore, job: &Job, info: &JobInfo) {
+        let Some(url) = job.options.callback_url.as_deref() else {
+            return;
+        };
+        let Some(event) = event_name(&info.state) else {
+            return;
+        };
+        let Some(secret) = self.secret_for(&job.context.org_id) else {
+            warn!(target = "hermes.jobs", job_id = %job.id, "webhook_secret_missing");
+            return;
+        };
+        let body = match serde_json::to_vec(info) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!(target = "hermes.jobs", job_id = %job.id, error = %err, "webhook_encode_failed");
+                return;
+            }
+        };
+        // A delivery resumed after a restart keeps its id and attempt count.
+        let earlier = store.deliveries(job.id).await.unwrap_or_default();
+        if earlier.iter().any(|entry| entry.delivered) {
+            return;
+        }
+        let delivery_id = earlier
+            .first()
+            .map_or_else(Uuid::new_v4, |entry| entry.delivery_id);
+
+        for attempt in earlier.len() + 1..=self.policy.max_attempts {
+            let attempted_at = Utc::now();
+            // Hosts are checked by the resolver; IP literals never reach it.
+            let literal_allowed = self.allow_private
+                || reqwest::Url::parse(url).is_ok_and(|parsed| is_public_literal(&parsed));
+            let (status, error, refused) = if !literal_allowed {
+                (None, Some(DestinationNotAllowed.to_string()), true)
+            } else {
+                let timestamp = attempted_at.timestamp();
+                let result = self
+                    .http
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(
+                        SIGNATURE_HEADER,
+                        format!("sha256={}", sign(secret, timestamp, &body)),
+                    )
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(EVENT_HEADER, event)
+                    .header(DELIVERY_HEADER, delivery_id.to_string())
+                    .body(body.clone())
+                    .send()
+                    .await;
+                match result {
+                    Ok(resp) if resp.status().is_success() => {
+                        (Some(resp.status().as_u16()), None, false)
+                    }
+                    Ok(resp) => (
+                        Some(resp.status().as_u16()),
+                        Some(format!("HTTP {}", resp.status())),
+                        false,
+                    ),
+                    Err(err) if refused_destination(&err) => {
+                        (None, Some(DestinationNotAllowed.to_string()), true)
+                    }
+                    Err(err) => (None, Some(err.to_string()), false),
+                }
+            };
+            let delivered = error.is_none();
+            let entry = WebhookDelivery {
+                delivery_id,
+                event: event.to_string(),
+                attempt,
+                attempted_at,
+                status,
+                error,
+                delivered,
+            };
+            if let Err(err) = store.push_delivery(job.id, &entry).await {
+                warn!(target = "hermes.jobs", job_id = %job.id, error = %err, "webhook_log_failed");
+            }
+            if delivered {
+                info!(target = "hermes.jobs", job_id = %job.id, event, attempt, "webhook_delivered");
+                return;
+            }
+            if refused {
+                warn!(target = "hermes.jobs", job_id = %job.id, event, "webhook_destination_refused");
+                return;
+            }
+            if attempt < self.policy.max_attempts {
+                sleep(self.policy.backoff(attempt)).await;
+            }
+        }
+        warn!(target = "hermes.jobs", job_id = %job.id, event, attempts = self.policy.max_attempts, "webhook_delivery_exhausted");
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of `<timestamp>.<body>`, sent as
+/// `X-Hermes-Signature: sha256=<hex>` next to `X-Hermes-Timestamp`, so a
+/// captured delivery cannot be replayed once receivers reject old timestamps.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut message = format!("{timestamp}.").into_bytes();
+    message.extend_from_slice(body);
+    hmac_hex(secret, &message)
+}
+
+fn hmac_hex(secret: &str, message: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(message);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("callback_destination_not_allowed")]
+struct DestinationNotAllowed;
+
+/// Resolves callback hosts, failing when any address is not public so a
+/// public name cannot be pointed at an internal service.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if addrs.iter().any(|addr| !is_public(addr.ip())) {
+                return Err(Box::new(DestinationNotAllowed) as _);
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+fn refused_destination(err: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if err.is::<DestinationNotAllowed>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// False for a URL whose host is an IP literal that is not public. Names are
+/// left to the resolver.
+fn is_public_literal(url: &reqwest::Url) -> bool {
+    let host = url.host_str().unwrap_or_default();
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .map_or(true, is_public)
+}
+
+fn is_localhost(url: &reqwest::Url) -> bool {
+    let host = url.host_str().unwrap_or_default();
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host == "localhost" || host.ends_with(".localhost")
+}
+
+/// Whether `ip` is routable on the public internet: not loopback, private,
+/// link-local, shared (CGNAT), unspecified, broadcast or multicast.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+fn event_name(state: &JobState) -> Option<&'static str> {
+    match state {
+        JobState::Completed { .. } => Some("job.completed"),
+        JobState::Failed { .. } => Some("job.failed"),
+        JobState::DeadLettered { .. } => Some("job.dead_lettered"),
+        JobState::Cancelled { .. } => Some("job.cancelled"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use axum::{Router, body::Bytes, extract::State, http::HeaderMap, routing::post};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+    use tokio::sync::Mutex;
+
+    /// A callback received by [`Receiver`], with its signature already checked.
+    pub struct Received {
+        pub event: String,
+        pub body: serde_json::Value,
+    }
+
+    /// Local-only webhook endpoint on `127.0.0.1` that answers `500` to the
+    /// first `fail_first` requests and records every correctly signed one.
+    #[derive(Clone)]
+    pub struct Receiver {
+        pub url: String,
+        pub received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    #[derive(Clone)]
+    struct ReceiverState {
+        secret: String,
+        fail_first: usize,
+        hits: Arc<AtomicUsize>,
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl Receiver {
+        pub async fn start(secret: &str, fail_first: usize) -> Self {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let state = ReceiverState {
+                secret: secret.to_string(),
+                fail_first,
+                hits: Arc::new(AtomicUsize::new(0)),
+                received: received.clone(),
+            };
+            let app = Router::new()
+                .route("/hook", post(receive))
+                .with_state(state);
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            Self {
+                url: format!("http://{addr}/hook"),
+                received,
+            }
+        }
+    }
+
+    async fn receive(
+        State(state): State<ReceiverState>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> axum::http::StatusCode {
+        if state.hits.fetch_add(1, Ordering::SeqCst) < state.fail_first {
+            return axum::http::StatusCode::INTERNAL_SERVER_ERROR;
+        }
+        let Some(timestamp) = headers
+            .get(TIMESTAMP_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|ts| (Utc::now().timestamp() - ts).abs() <= 300)
+        else {
+            return axum::http::StatusCode::UNAUTHORIZED;
+        };
+        let expected = format!("sha256={}", sign(&state.secret, timestamp, &body));
+        let presented = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        if presented != expected {
+            return axum::http::StatusCode::UNAUTHORIZED;
+        }
+        let event = headers
+            .get(EVENT_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let body = serde_json::from_slice(&body).unwrap_or_default();
+        state.received.lock().await.push(Received { event, body });
+        axum::http::StatusCode::NO_CONTENT
+    }
+
+    /// A sender for `org_id`; `Receiver` listens on loopback, so private
+    /// destinations are allowed as `WEBHOOK_ALLOW_PRIVATE=1` would.
+    pub fn sender(org_id: &str, secret: &str) -> WebhookSender {
+        sender_with(org_id, secret, true)
+    }
+
+    fn sender_with(org_id: &str, secret: &str, allow_private: bool) -> WebhookSender {
+        WebhookSender::new(
+            HashMap::from([(org_id.to_string(), secret.to_string())]),
+            None,
+            RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(20),
+            },
+            allow_private,
+        )
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            hmac_hex("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            sign("Jefe", 1700000000, b"{}"),
+            hmac_hex("Jefe", b"1700000000.{}")
+        );
+        assert_ne!(
+            sign("Jefe", 1700000000, b"{}"),
+            sign("Jefe", 1700000001, b"{}")
+        );
+    }
+
+    #[test]
+    fn validates_callback_urls() {
+        let sender = sender("demo-org", "s3cret");
+        assert!(
+            sender
+                .validate("demo-org", "https://hooks.example.com/x")
+                .is_ok()
+        );
+        assert_eq!(
+            sender.validate("demo-org", "ftp://hooks.example.com/x"),
+            Err("invalid_callback_url")
+        );
+        assert_eq!(
+            sender.validate("other-org", "https://hooks.example.com/x"),
+            Err("callback_secret_not_configured")
+        );
+
+        let strict = sender_with("demo-org", "s3cret", false);
+        for url in [
+            "http://localhost:8080/x",
+            "http://127.0.0.1/x",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.5/x",
+            "http://192.168.1.1/x",
+            "http://[::1]/x",
+            "http://[::ffff:10.0.0.1]/x",
+            "http://[fd00::1]/x",
+        ] {
+            assert_eq!(
+                strict.validate("demo-org", url),
+                Err("callback_url_not_allowed"),
+                "{url}"
+            );
+        }
+        assert!(
+            strict
+                .validate("demo-org", "https://hooks.example.com/x")
+                .is_ok()
+        );
+        assert!(strict.validate("demo-org", "http://8.8.8.8/x").is_ok());
+    }
+
+    #[tokio::test]
+    async fn private_destinations_are_refused_after_resolution() {
+        let receiver = Receiver::start("s3cret", 0).await;
+        let strict = sender_with("demo-org", "s3cret", false);
+        let store = JobStore::memory();
+        let mut job = crate::jobs::tests::sample_job();
+        job.context.org_id = "demo-org".into();
+        // A name rather than an IP literal, so only the resolver can catch it.
+        let port = reqwest::Url::parse(&receiver.url).unwrap().port().unwrap();
+        job.options.callback_url = Some(format!("http://localhost:{port}/hook"));
+        let info = JobInfo {
+            id: job.id.to_string(),
+            state: JobState::Cancelled { at_stage: None },
+            attempts: Vec::new(),
+            cancel_requested_at: None,
+            org_queue: crate::jobs::scheduler::OrgQueueStats {
+                org_id: "demo-org".into(),
+                queued: 0,
+                in_flight: 0,
+                max_in_flight: 1,
+            },
+        };
+
+        strict.deliver(&store, &job, &info).await;
+        let deliveries = store.deliveries(job.id).await.unwrap();
+        assert_eq!(deliveries.len(), 1, "refusals are not retried");
+        assert_eq!(
+            deliveries[0].error.as_deref(),
+            Some("callback_destination_not_allowed")
+        );
+        assert!(receiver.received.lock().await.is_empty());
+    }
+}
@@ -0,0 +1,1165 @@
+use crate::{
+    security::Scope,
+    supabase::{SupabaseClient, SupabaseError},
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use thiserror::Error;
+use tracing::{info, warn};
+
+#[derive(Debug, Error)]
+pub enum KeyStoreError {
+    #[error("read {path}: {message}")]
+    File { path: String, message: String },
+    #[error("parse api keys: {0}")]
+    Parse(String),
+    #[error("supabase: {0}")]
+    Supabase(#[from] SupabaseError),
+}
+
+/// Why an admin change to orgs or keys was refused.
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("key_store_read_only")]
+    ReadOnly,
+    #[error("invalid_org_id")]
+    InvalidOrgId,
+    #[error("org_exists")]
+    OrgExists,
+    #[error("org_not_found")]
+    OrgNotFound,
+    #[error("key_not_found")]
+    KeyNotFound,
+    #[error("signed_requests_disabled")]
+    SigningDisabled,
+    #[error(transparent)]
+    Store(#[from] KeyStoreError),
+}
+
+/// Why a presented key was not accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyRejection {
+    Unknown,
+    Disabled,
+    Expired,
+    /// A signed request named a known key but its signature did not match.
+    BadSignature,
+    /// A signed request arrived but `API_KEY_PEPPER` is not set.
+    SigningDisabled,
+}
+
+impl KeyRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            KeyRejection::Unknown => "invalid_api_key",
+            KeyRejection::Disabled => "api_key_disabled",
+            KeyRejection::Expired => "api_key_expired",
+            KeyRejection::BadSignature => "invalid_signature",
+            KeyRejection::SigningDisabled => "signed_requests_disabled",
+        }
+    }
+}
+
+/// One API key as stored in `API_KEYS_FILE` or the Supabase `api_keys`
+/// table. `hash` is hex HMAC-SHA256 of the secret keyed by the hex `salt`;
+/// the secret itself is never stored. Rows without `scopes` get
+/// [`Scope::DEFAULT`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyRow {
+    pub key_id: String,
+    pub org_id: String,
+    pub salt: String,
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A customer org, as stored next to its keys (`orgs` in the key file, or
+/// the Supabase `orgs` table). Orgs that only appear on keys have no row.
+/// `rate_plan` names an entry of `RATE_LIMIT_PLANS`; without one the org is
+/// on the default plan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrgRow {
+    pub org_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_plan: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// What admins see of a key: everything but the salt and hash.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyInfo {
+    pub key_id: String,
+    pub org_id: String,
+    pub scopes: Vec<Scope>,
+    pub disabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A loaded key: its row plus the decoded salt, hash and scopes.
+#[derive(Debug, Clone)]
+struct ApiKey {
+    row: ApiKeyRow,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+    scopes: Vec<Scope>,
+}
+
+impl ApiKey {
+    fn from_row(row: ApiKeyRow) -> Result<Self, KeyStoreError> {
+        let decode = |field: &str, value: &str| {
+            hex::decode(value.trim()).map_err(|err| {
+                KeyStoreError::Parse(format!("key {} has invalid {field}: {err}", row.key_id))
+            })
+        };
+        let scopes = match &row.scopes {
+            None => Scope::DEFAULT.to_vec(),
+            Some(names) => names
+                .iter()
+                .filter_map(|name| {
+                    let scope = Scope::parse(name);
+                    if scope.is_none() {
+                        warn!(target = "hermes.api", key_id = %row.key_id, scope = %name, "ignored unknown api key scope");
+                    }
+                    scope
+                })
+                .collect(),
+        };
+        Ok(Self {
+            salt: decode("salt", &row.salt)?,
+            hash: decode("hash", &row.hash)?,
+            scopes,
+            row,
+        })
+    }
+
+    /// Hash a plaintext secret under a fresh random salt, granting the
+    /// default scopes unless the row says otherwise afterwards.
+    fn from_secret(key_id: String, org_id: String, secret: &str) -> Self {
+        let mut salt = vec![0u8; 16];
+        rand::rng().fill_bytes(&mut salt);
+        let hash = mac(&salt, secret).finalize().into_bytes().to_vec();
+        Self {
+            row: ApiKeyRow {
+                key_id,
+                org_id,
+                salt: hex::encode(&salt),
+                hash: hex::encode(&hash),
+                expires_at: None,
+                disabled: false,
+                scopes: None,
+                created_at: None,
+                last_used_at: None,
+            },
+            salt,
+            hash,
+            scopes: Scope::DEFAULT.to_vec(),
+        }
+    }
+
+    /// Constant-time check of `presented` against the stored hash.
+    fn matches(&self, presented: &str) -> bool {
+        mac(&self.salt, presented).verify_slice(&self.hash).is_ok()
+    }
+
+    fn info(&self, last_used: Option<DateTime<Utc>>) -> ApiKeyInfo {
+        ApiKeyInfo {
+            key_id: self.row.key_id.clone(),
+            org_id: self.row.org_id.clone(),
+            scopes: self.scopes.clone(),
+            disabled: self.row.disabled,
+            created_at: self.row.created_at,
+            expires_at: self.row.expires_at,
+            last_used_at: self.row.last_used_at.max(last_used),
+        }
+    }
+}
+
+fn mac(salt: &[u8], secret: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt).expect("hmac accepts any key length");
+    mac.update(secret.as_bytes());
+    mac
+}
+
+/// The org and key id behind an accepted key, plus the org's rate plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedKey {
+    pub org_id: String,
+    pub key_id: String,
+    pub scopes: Vec<Scope>,
+    pub rate_plan: Option<String>,
+}
+
+/// `API_KEY_PEPPER`, a server-side secret that keys the ids derived for
+/// `DEMO_API_KEYS`, so an id logged or listed anywhere cannot be checked
+/// against guessed keys, and each key's signing secret, so nothing stored
+/// with a key is enough to sign for it. Without it a random pepper is used,
+/// env key ids change on every restart and signed requests are refused.
+struct Pepper {
+    bytes: Vec<u8>,
+    configured: bool,
+}
+
+impl Pepper {
+    fn from_env() -> Self {
+        match std::env::var("API_KEY_PEPPER")
+            .ok()
+            .filter(|pepper| !pepper.trim().is_empty())
+        {
+            Some(pepper) => Self {
+                bytes: pepper.trim().as_bytes().to_vec(),
+                configured: true,
+            },
+            None => Self::random(),
+        }
+    }
+
+    fn random() -> Self {
+        let mut bytes = vec![0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        Self {
+            bytes,
+            configured: false,
+        }
+    }
+
+    /// HMAC-SHA256 of `input` under the pepper, domain-separated by `label`.
+    fn mac(&self, label: &str, input: &str) -> Vec<u8> {
+        let mut mac = mac(&self.bytes, label);
+        mac.update(b"\n");
+        mac.update(input.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// The key `key_id` signs requests with. Only a configured pepper gives
+    /// one, since a random one would change it on every restart.
+    fn signing_secret(&self, key_id: &str) -> Option<Vec<u8>> {
+        self.configured.then(|| self.mac("signing", key_id))
+    }
+}
+
+/// Everything loaded from a source.
+#[derive(Debug, Default)]
+struct KeySet {
+    orgs: Vec<OrgRow>,
+    keys: Vec<ApiKey>,
+}
+
+impl KeySet {
+    fn has_org(&self, org_id: &str) -> bool {
+        self.orgs.iter().any(|org| org.org_id == org_id)
+            || self.keys.iter().any(|key| key.row.org_id == org_id)
+    }
+}
+
+/// `API_KEYS_FILE` contents: either a bare array of keys or
+/// `{ "orgs": [...], "keys": [...] }`, which is what admin changes write.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeyFile {
+    #[serde(default)]
+    orgs: Vec<OrgRow>,
+    #[serde(default)]
+    keys: Vec<ApiKeyRow>,
+}
+
+impl KeyFile {
+    fn parse(raw: &str) -> Result<Self, KeyStoreError> {
+        if let Ok(keys) = serde_json::from_str::<Vec<ApiKeyRow>>(raw) {
+            return Ok(Self {
+                orgs: Vec::new(),
+                keys,
+            });
+        }
+        serde_json::from_str(raw).map_err(|err| KeyStoreError::Parse(err.to_string()))
+    }
+
+    async fn read(path: &Path) -> Result<Self, KeyStoreError> {
+        let raw = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|err| KeyStoreError::File {
+                path: path.display().to_string(),
+                message: err.to_string(),
+            })?;
+        Self::parse(&raw)
+    }
+
+    /// Replace the file atomically so a concurrent reader never sees half of it.
+    async fn write(&self, path: &Path) -> Result<(), KeyStoreError> {
+        let io_err = |err: std::io::Error| KeyStoreError::File {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        };
+        let json =
+            serde_json::to_vec_pretty(self).map_err(|err| KeyStoreError::Parse(err.to_string()))?;
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, json).await.map_err(io_err)?;
+        tokio::fs::rename(&tmp, path).await.map_err(io_err)
+    }
+}
+
+/// Where keys are loaded from, picked by `API_KEYS_SOURCE`.
+enum KeySource {
+    /// `DEMO_API_KEYS` plaintext pairs, hashed at startup. Read-only.
+    Env,
+    /// A JSON [`KeyFile`] at `API_KEYS_FILE`.
+    File(PathBuf),
+    /// The Supabase `orgs` and `api_keys` tables.
+    Supabase(SupabaseClient),
+}
+
+impl KeySource {
+    fn from_env() -> Self {
+        let source = std::env::var("API_KEYS_SOURCE")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        match source.as_str() {
+            "file" => match std::env::var("API_KEYS_FILE") {
+                Ok(path) if !path.trim().is_empty() => Self::File(PathBuf::from(path.trim())),
+                _ => {
+                    warn!(
+                        target = "hermes.api",
+                        "API_KEYS_SOURCE=file without API_KEYS_FILE; using DEMO_API_KEYS"
+                    );
+                    Self::Env
+                }
+            },
+            "supabase" => match SupabaseClient::from_env() {
+                Some(client) => Self::Supabase(client),
+                None => {
+                    warn!(
+                        target = "hermes.api",
+                        "API_KEYS_SOURCE=supabase without SUPABASE_URL; using DEMO_API_KEYS"
+                    );
+                    Self::Env
+                }
+            },
+            _ => Self::Env,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Env => "env",
+            Self::File(_) => "file",
+            Self::Supabase(_) => "supabase",
+        }
+    }
+
+    async fn load(&self, pepper: &Pepper) -> Result<KeySet, KeyStoreError> {
+        let (orgs, rows) = match self {
+            Self::Env => {
+                return Ok(KeySet {
+                    orgs: Vec::new(),
+                    keys: load_keys_from_env(pepper),
+                });
+            }
+            Self::File(path) => {
+                let file = KeyFile::read(path).await?;
+                (file.orgs, file.keys)
+            }
+            Self::Supabase(client) => (client.fetch_orgs().await?, client.fetch_api_keys().await?),
+        };
+        let keys = rows
+            .into_iter()
+            .map(ApiKey::from_row)
+            .collect::<Result<_, _>>()?;
+        Ok(KeySet { orgs, keys })
+    }
+
+    /// Apply `change` to the key file, starting from an empty one if it does
+    /// not exist yet.
+    async fn edit_file(
+        path: &Path,
+        change: impl FnOnce(&mut KeyFile) -> Result<(), AdminError>,
+    ) -> Result<(), AdminError> {
+        let mut file = match KeyFile::read(path).await {
+            Ok(file) => file,
+            Err(KeyStoreError::File { .. }) if !path.exists() => KeyFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+        change(&mut file)?;
+        file.write(path).await?;
+        Ok(())
+    }
+
+    async fn insert_org(&self, org: OrgRow) -> Result<(), AdminError> {
+        match self {
+            Self::Env => Err(AdminError::ReadOnly),
+            Self::File(path) => {
+                Self::edit_file(path, |file| {
+                    file.orgs.push(org);
+                    Ok(())
+                })
+                .await
+            }
+            Self::Supabase(client) => Ok(client
+                .insert_row("orgs", &org)
+                .await
+                .map_err(KeyStoreError::from)?),
+        }
+    }
+
+    async fn insert_key(&self, row: ApiKeyRow) -> Result<(), AdminError> {
+        match self {
+            Self::Env => Err(AdminError::ReadOnly),
+            Self::File(path) => {
+                Self::edit_file(path, |file| {
+                    file.keys.push(row);
+                    Ok(())
+                })
+                .await
+            }
+            Self::Supabase(client) => Ok(client
+                .insert_row("api_keys", &row)
+                .await
+                .map_err(KeyStoreError::from)?),
+        }
+    }
+
+    async fn disable_key(&self, org_id: &str, key_id: &str) -> Result<(), AdminError> {
+        match self {
+            Self::Env => Err(AdminError::ReadOnly),
+            Self::File(path) => {
+                Self::edit_file(path, |file| {
+                    let row = file
+                        .keys
+                        .iter_mut()
+                        .find(|row| row.org_id == org_id && row.key_id == key_id)
+                        .ok_or(AdminError::KeyNotFound)?;
+                    row.disabled = true;
+                    Ok(())
+                })
+                .await
+            }
+            Self::Supabase(client) => {
+                let filter = format!(
+                    "org_id=eq.{}&key_id=eq.{}",
+                    urlencoding::encode(org_id),
+                    urlencoding::encode(key_id)
+                );
+                Ok(client
+                    .update_rows(
+                        "api_keys",
+                        &filter,
+                        &serde_json::json!({ "disabled": true }),
+                    )
+                    .await
+                    .map_err(KeyStoreError::from)?)
+            }
+        }
+    }
+
+    async fn record_last_used(
+        &self,
+        used: &HashMap<String, DateTime<Utc>>,
+    ) -> Result<(), KeyStoreError> {
+        match self {
+            Self::Env => Ok(()),
+            Self::File(path) => {
+                let mut file = KeyFile::read(path).await?;
+                for row in &mut file.keys {
+                    if let Some(at) = used.get(&row.key_id) {
+                        row.last_used_at = row.last_used_at.max(Some(*at));
+                    }
+                }
+                file.write(path).await
+            }
+            Self::Supabase(client) => {
+                for (key_id, at) in used {
+                    let filter = format!("key_id=eq.{}", urlencoding::encode(key_id));
+                    client
+                        .update_rows(
+                            "api_keys",
+                            &filter,
+                            &serde_json::json!({ "last_used_at": at }),
+                        )
+                        .await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Accepted API keys, held only as salted hashes. Several keys may map to
+/// the same org, so a new key can be rolled out before the old one expires.
+#[derive(Clone)]
+pub struct KeyStore {
+    set: Arc<RwLock<Arc<KeySet>>>,
+    source: Arc<KeySource>,
+    pepper: Arc<Pepper>,
+    /// Last successful use per key id since it was last written back.
+    last_used: Arc<Mutex<HashMap<String, DateTime<Utc>>>>,
+    /// Serializes writes and reloads so a reload never swaps in a key set
+    /// read before an admin change landed.
+    writes: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl KeyStore {
+    fn new(source: KeySource, pepper: Pepper, set: KeySet) -> Self {
+        Self {
+            set: Arc::new(RwLock::new(Arc::new(set))),
+            source: Arc::new(source),
+            pepper: Arc::new(pepper),
+            last_used: Arc::default(),
+            writes: Arc::default(),
+        }
+    }
+
+    /// Load keys from `API_KEYS_SOURCE` (`env`, `file` or `supabase`) and,
+    /// for file and Supabase sources, re-read them every
+    /// `API_KEYS_RELOAD_SECS` (default `30`) so changes apply without a
+    /// restart.
+    pub async fn from_env() -> Self {
+        let source = KeySource::from_env();
+        if matches!(source, KeySource::Env) && std::env::var("API_KEY_PEPPER").is_err() {
+            warn!(
+                target = "hermes.api",
+                "API_KEY_PEPPER is not set; DEMO_API_KEYS ids will change on restart"
+            );
+        }
+        let store = Self::new(source, Pepper::from_env(), KeySet::default());
+        if let Err(err) = store.reload().await {
+            warn!(target = "hermes.api", source = store.source.name(), error = %err, "api_keys_load_failed");
+        }
+        if !matches!(*store.source, KeySource::Env) {
+            let interval = std::env::var("API_KEYS_RELOAD_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(30);
+            tokio::spawn(store.clone().reload_loop(Duration::from_secs(interval)));
+        }
+        store
+    }
+
+    /// Replace the key set with a fresh read of the source. On error the
+    /// previous keys stay in place.
+    pub async fn reload(&self) -> Result<usize, KeyStoreError> {
+        let _writing = self.writes.lock().await;
+        self.reload_locked().await
+    }
+
+    async fn reload_locked(&self) -> Result<usize, KeyStoreError> {
+        let set = self.source.load(&self.pepper).await?;
+        let count = set.keys.len();
+        *self.set.write().expect("api keys lock") = Arc::new(set);
+        Ok(count)
+    }
+
+    fn snapshot(&self) -> Arc<KeySet> {
+        self.set.read().expect("api keys lock").clone()
+    }
+
+    /// A fixed store of plaintext `(org_id, key_id, secret, scopes)` keys.
+    #[cfg(test)]
+    pub fn for_tests(keys: &[(&str, &str, &str, &[Scope])]) -> Self {
+        let keys = keys
+            .iter()
+            .map(|(org_id, key_id, secret, scopes)| ApiKey {
+                scopes: scopes.to_vec(),
+                ..ApiKey::from_secret(key_id.to_string(), org_id.to_string(), secret)
+            })
+            .collect();
+        Self::new(
+            KeySource::Env,
+            Pepper {
+                configured: true,
+                ..Pepper::random()
+            },
+            KeySet {
+                orgs: Vec::new(),
+                keys,
+            },
+        )
+    }
+
+    async fn reload_loop(self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(err) = self.flush_last_used().await {
+                warn!(target = "hermes.api", error = %err, "api_keys_last_used_write_failed");
+            }
+            match self.reload().await {
+                Ok(count) => info!(
+                    target = "hermes.api",
+                    key_count = count,
+                    "api_keys_reloaded"
+                ),
+                Err(err) => {
+                    warn!(target = "hermes.api", error = %err, "api_keys_reload_failed")
+                }
+            }
+        }
+    }
+
+    /// Write pending last-used times back to the source.
+    async fn flush_last_used(&self) -> Result<(), KeyStoreError> {
+        let _writing = self.writes.lock().await;
+        let used = std::mem::take(&mut *self.last_used.lock().expect("last used lock"));
+        if used.is_empty() {
+            return Ok(());
+        }
+        let result = self.source.record_last_used(&used).await;
+        if result.is_err() {
+            // Keep them for the next attempt, without losing newer uses.
+            let mut pending = self.last_used.lock().expect("last used lock");
+            for (key_id, at) in used {
+                let entry = pending.entry(key_id).or_insert(at);
+                *entry = (*entry).max(at);
+            }
+        }
+        result
+    }
+
+    /// Keys name their id (`hk_<key_id>_<secret>` when minted here, or the
+    /// id derived for `DEMO_API_KEYS`), so this is one lookup and one
+    /// constant-time compare. Other hand-made keys are checked against every
+    /// stored key, all of them each time so timing does not reveal where
+    /// the match was.
+    pub fn verify(&self, presented: &str) -> Result<VerifiedKey, KeyRejection> {
+        let set = self.snapshot();
+        let minted = minted_key_id(presented);
+        let key_id = minted.map_or_else(|| env_key_id(&self.pepper, presented), str::to_string);
+        let key = match set.keys.iter().find(|key| key.row.key_id == key_id) {
+            Some(key) => Some(key).filter(|key| key.matches(presented)),
+            None if minted.is_some() => None,
+            None => set
+                .keys
+                .iter()
+                .filter(|key| key.matches(presented))
+                .fold(None, |found, key| found.or(Some(key))),
+        }
+        .ok_or(KeyRejection::Unknown)?;
+        self.accept(&set, key)
+    }
+
+    /// Check a signed request: `signature` must be HMAC-SHA256 of `message`
+    /// keyed by the signing secret of `key_id`.
+    pub fn verify_signed(
+        &self,
+        key_id: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<VerifiedKey, KeyRejection> {
+        let set = self.snapshot();
+        let key = set
+            .keys
+            .iter()
+            .find(|key| key.row.key_id == key_id)
+            .ok_or(KeyRejection::Unknown)?;
+        let secret = self
+            .pepper
+            .signing_secret(key_id)
+            .ok_or(KeyRejection::SigningDisabled)?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&secret).expect("hmac accepts any key length");
+        mac.update(message);
+        if mac.verify_slice(signature).is_err() {
+            return Err(KeyRejection::BadSignature);
+        }
+        self.accept(&set, key)
+    }
+
+    /// The hex secret a key signs requests with, derived from the pepper and
+    /// the key id rather than anything stored.
+    pub fn signing_secret(&self, key_id: &str) -> Option<String> {
+        let known = self
+            .snapshot()
+            .keys
+            .iter()
+            .any(|key| key.row.key_id == key_id);
+        known
+            .then(|| self.pepper.signing_secret(key_id))
+            .flatten()
+            .map(hex::encode)
+    }
+
+    /// The signing secret of one of `org_id`'s keys, from any key source;
+    /// keys from `DEMO_API_KEYS` or a key file have no other way to get it.
+    pub fn signing_secret_for(&self, org_id: &str, key_id: &str) -> Result<String, AdminError> {
+        let exists = self
+            .snapshot()
+            .keys
+            .iter()
+            .any(|key| key.row.org_id == org_id && key.row.key_id == key_id);
+        if !exists {
+            return Err(AdminError::KeyNotFound);
+        }
+        self.signing_secret(key_id)
+            .ok_or(AdminError::SigningDisabled)
+    }
+
+    fn accept(&self, set: &KeySet, key: &ApiKey) -> Result<VerifiedKey, KeyRejection> {
+        if key.row.disabled {
+            return Err(KeyRejection::Disabled);
+        }
+        if key.row.expires_at.is_some_and(|at| at <= Utc::now()) {
+            return Err(KeyRejection::Expired);
+        }
+        self.last_used
+            .lock()
+            .expect("last used lock")
+            .insert(key.row.key_id.clone(), Utc::now());
+        Ok(VerifiedKey {
+            org_id: key.row.org_id.clone(),
+            key_id: key.row.key_id.clone(),
+            scopes: key.scopes.clone(),
+            rate_plan: set
+                .orgs
+                .iter()
+                .find(|org| org.org_id == key.row.org_id)
+                .and_then(|org| org.rate_plan.clone()),
+        })
+    }
+
+    /// Known orgs: those created through the admin API plus any that only
+    /// appear on keys.
+    pub fn orgs(&self) -> Vec<OrgRow> {
+        let set = self.snapshot();
+        let mut orgs = set.orgs.clone();
+        for key in &set.keys {
+            if !orgs.iter().any(|org| org.org_id == key.row.org_id) {
+                orgs.push(OrgRow {
+                    org_id: key.row.org_id.clone(),
+                    name: None,
+                    rate_plan: None,
+                    created_at: None,
+                });
+            }
+        }
+        orgs
+    }
+
+    pub async fn create_org(
+        &self,
+        org_id: &str,
+        name: Option<String>,
+        rate_plan: Option<String>,
+    ) -> Result<OrgRow, AdminError> {
+        let valid = !org_id.is_empty()
+            && org_id.len() <= 64
+            && org_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(AdminError::InvalidOrgId);
+        }
+        let _writing = self.writes.lock().await;
+        if self.snapshot().has_org(org_id) {
+            return Err(AdminError::OrgExists);
+        }
+        let org = OrgRow {
+            org_id: org_id.to_string(),
+            name,
+            rate_plan,
+            created_at: Some(Utc::now()),
+        };
+        self.source.insert_org(org.clone()).await?;
+        self.reload_locked().await?;
+        Ok(org)
+    }
+
+    /// Mint a key for an existing org. The plaintext key is returned here
+    /// and never again.
+    pub async fn mint_key(
+        &self,
+        org_id: &str,
+        scopes: Vec<Scope>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(ApiKeyInfo, String), AdminError> {
+        let _writing = self.writes.lock().await;
+        if !self.snapshot().has_org(org_id) {
+            return Err(AdminError::OrgNotFound);
+        }
+        // Stored with the row, so the id is random rather than derived.
+        let mut id = [0u8; 8];
+        rand::rng().fill_bytes(&mut id);
+        let key_id = format!("key-{}", hex::encode(id));
+        let mut bytes = [0u8; 24];
+        rand::rng().fill_bytes(&mut bytes);
+        let secret = format!("hk_{key_id}_{}", hex::encode(bytes));
+        let mut key = ApiKey::from_secret(key_id, org_id.to_string(), &secret);
+        key.row.scopes = Some(scopes.iter().map(|s| s.as_str().to_string()).collect());
+        key.row.expires_at = expires_at;
+        key.row.created_at = Some(Utc::now());
+        key.scopes = scopes;
+        self.source.insert_key(key.row.clone()).await?;
+        self.reload_locked().await?;
+        Ok((key.info(None), secret))
+    }
+
+    pub fn keys_for(&self, org_id: &str) -> Result<Vec<ApiKeyInfo>, AdminError> {
+        let set = self.snapshot();
+        if !set.has_org(org_id) {
+            return Err(AdminError::OrgNotFound);
+        }
+        let used = self.last_used.lock().expect("last used lock").clone();
+        Ok(set
+            .keys
+            .iter()
+            .filter(|key| key.row.org_id == org_id)
+            .map(|key| key.info(used.get(&key.row.key_id).copied()))
+            .collect())
+    }
+
+    /// Disable a key; requests using it fail from now on.
+    pub async fn revoke(&self, org_id: &str, key_id: &str) -> Result<ApiKeyInfo, AdminError> {
+        let _writing = self.writes.lock().await;
+        let exists = self
+            .snapshot()
+            .keys
+            .iter()
+            .any(|key| key.row.org_id == org_id && key.row.key_id == key_id);
+        if !exists {
+            return Err(AdminError::KeyNotFound);
+        }
+        self.source.disable_key(org_id, key_id).await?;
+        self.reload_locked().await?;
+        let used = self
+            .last_used
+            .lock()
+            .expect("last used lock")
+            .get(key_id)
+            .copied();
+        self.snapshot()
+            .keys
+            .iter()
+            .find(|key| key.row.org_id == org_id && key.row.key_id == key_id)
+            .map(|key| key.info(used))
+            .ok_or(AdminError::KeyNotFound)
+    }
+}
+
+/// Parse `DEMO_API_KEYS` (`org_id:key,...`, default `demo-org:demo-key`).
+/// Key ids are derived from the key and the pepper, so reordering the list
+/// keeps them stable.
+fn load_keys_from_env(pepper: &Pepper) -> Vec<ApiKey> {
+    let raw = std::env::var("DEMO_API_KEYS").unwrap_or_else(|_| "demo-org:demo-key".to_string());
+    let mut keys = parse_env_keys(&raw, pepper);
+    if keys.is_empty() {
+        warn!(
+            target = "hermes.api",
+            "DEMO_API_KEYS produced no keys; falling back to demo credentials"
+        );
+        keys = parse_env_keys("demo-org:demo-key", pepper);
+    } else {
+        info!(
+            target = "hermes.api",
+            key_count = keys.len(),
+            "loaded API keys from env"
+        );
+    }
+    keys
+}
+
+fn parse_env_keys(raw: &str, pepper: &Pepper) -> Vec<ApiKey> {
+    let mut keys = Vec::new();
+    for token in raw.split(',') {
+        let trimmed = token.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let mut parts = trimmed.splitn(2, ':');
+        let org_id = parts.next().map(str::trim).filter(|s| !s.is_empty());
+        let secret = parts.next().map(str::trim).filter(|s| !s.is_empty());
+        match (org_id, secret) {
+            (Some(org), Some(secret)) => {
+                keys.push(ApiKey::from_secret(
+                    env_key_id(pepper, secret),
+                    org.to_string(),
+                    secret,
+                ));
+            }
+            _ => warn!(
+                target = "hermes.api",
+                "ignored malformed DEMO_API_KEYS entry"
+            ),
+        }
+    }
+    keys
+}
+
+/// The key id in a key minted as `hk_<key_id>_<secret>`.
+fn minted_key_id(presented: &str) -> Option<&str> {
+    let (key_id, secret) = presented.strip_prefix("hk_")?.rsplit_once('_')?;
+    (!key_id.is_empty() && !secret.is_empty()).then_some(key_id)
+}
+
+/// `key-` plus the first 8 hex digits of the key's peppered HMAC.
+fn env_key_id(pepper: &Pepper, secret: &str) -> String {
+    let digest = hex::encode(pepper.mac("key-id", secret));
+    format!("key-{}", &digest[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Digest;
+
+    fn pepper(value: &str) -> Pepper {
+        Pepper {
+            bytes: value.as_bytes().to_vec(),
+            configured: true,
+        }
+    }
+
+    fn store(keys: Vec<ApiKey>, source: KeySource) -> KeyStore {
+        KeyStore::new(
+            source,
+            pepper("test-pepper"),
+            KeySet {
+                orgs: Vec::new(),
+                keys,
+            },
+        )
+    }
+
+    fn row(key_id: &str, secret: &str) -> serde_json::Value {
+        let salt = [7u8; 16];
+        serde_json::json!({
+            "key_id": key_id,
+            "org_id": "acme",
+            "salt": hex::encode(salt),
+            "hash": hex::encode(mac(&salt, secret).finalize().into_bytes()),
+        })
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("hermes-keys-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn env_key_ids_do_not_depend_on_order() {
+        let a = parse_env_keys("acme:sk_one,venture:sk_two", &pepper("p1"));
+        let b = parse_env_keys("venture:sk_two,acme:sk_one", &pepper("p1"));
+        let store_a = store(a, KeySource::Env);
+        let store_b = store(b, KeySource::Env);
+        assert_eq!(store_a.verify("sk_one"), store_b.verify("sk_one"));
+        assert_eq!(store_a.verify("sk_one").unwrap().org_id, "acme");
+        assert_eq!(store_a.verify("sk_nope"), Err(KeyRejection::Unknown));
+
+        // The id is keyed by the pepper, not a bare digest of the key.
+        let id = store_a.verify("sk_one").unwrap().key_id;
+        let unsalted = hex::encode(Sha256::digest(b"sk_one"));
+        assert_ne!(id, format!("key-{}", &unsalted[..8]));
+        let other = parse_env_keys("acme:sk_one", &pepper("p2"));
+        assert_ne!(
+            store(other, KeySource::Env)
+                .verify("sk_one")
+                .unwrap()
+                .key_id,
+            id
+        );
+    }
+
+    #[test]
+    fn stored_rows_cannot_sign_requests() {
+        let raw = row("k1", "sk_one");
+        let stored: ApiKeyRow = serde_json::from_value(raw).unwrap();
+        let keys = vec![ApiKey::from_row(stored.clone()).unwrap()];
+        let store = store(keys.clone(), KeySource::File(temp_path()));
+        let message = b"v1\nPOST\n/listings\n0\nn-1\n";
+        let sign = |secret: &[u8]| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        };
+
+        // Nothing in the row, salt or hash, is the signing secret.
+        for secret in [&stored.hash, &stored.salt] {
+            let forged = sign(&hex::decode(secret).unwrap());
+            assert_eq!(
+                store.verify_signed("k1", message, &forged),
+                Err(KeyRejection::BadSignature)
+            );
+        }
+        let secret = hex::decode(store.signing_secret("k1").unwrap()).unwrap();
+        assert!(store.verify_signed("k1", message, &sign(&secret)).is_ok());
+
+        // The same row under another pepper signs with another secret.
+        let other = KeyStore::new(
+            KeySource::File(temp_path()),
+            pepper("other-pepper"),
+            KeySet {
+                orgs: Vec::new(),
+                keys: keys.clone(),
+            },
+        );
+        assert_eq!(
+            other.verify_signed("k1", message, &sign(&secret)),
+            Err(KeyRejection::BadSignature)
+        );
+
+        // Without a configured pepper there is no signing secret at all.
+        let unpeppered = KeyStore::new(
+            KeySource::File(temp_path()),
+            Pepper::random(),
+            KeySet {
+                orgs: Vec::new(),
+                keys,
+            },
+        );
+        assert_eq!(unpeppered.signing_secret("k1"), None);
+        assert!(matches!(
+            unpeppered.signing_secret_for("acme", "k1"),
+            Err(AdminError::SigningDisabled)
+        ));
+        assert_eq!(
+            unpeppered.verify_signed("k1", message, &sign(&secret)),
+            Err(KeyRejection::SigningDisabled)
+        );
+    }
+
+    #[test]
+    fn disabled_and_expired_keys_are_rejected() {
+        let mut disabled = ApiKey::from_secret("k1".into(), "acme".into(), "sk_old");
+        disabled.row.disabled = true;
+        let mut expired = ApiKey::from_secret("k2".into(), "acme".into(), "sk_older");
+        expired.row.expires_at = Some(Utc::now() - chrono::Duration::seconds(1));
+        let mut rotating = ApiKey::from_secret("k3".into(), "acme".into(), "sk_current");
+        rotating.row.expires_at = Some(Utc::now() + chrono::Duration::hours(1));
+        let replacement = ApiKey::from_secret("k4".into(), "acme".into(), "sk_next");
+        let store = store(
+            vec![disabled, expired, rotating, replacement],
+            KeySource::Env,
+        );
+
+        assert_eq!(store.verify("sk_old"), Err(KeyRejection::Disabled));
+        assert_eq!(store.verify("sk_older"), Err(KeyRejection::Expired));
+        assert_eq!(store.verify("sk_current").unwrap().key_id, "k3");
+        assert_eq!(store.verify("sk_next").unwrap().key_id, "k4");
+    }
+
+    #[tokio::test]
+    async fn file_keys_reload_without_restart() {
+        let path = temp_path();
+        std::fs::write(&path, serde_json::json!([row("k1", "sk_one")]).to_string()).unwrap();
+        let store = store(Vec::new(), KeySource::File(path.clone()));
+        assert_eq!(store.reload().await.unwrap(), 1);
+        assert_eq!(store.verify("sk_one").unwrap().key_id, "k1");
+
+        std::fs::write(&path, serde_json::json!([row("k2", "sk_two")]).to_string()).unwrap();
+        store.reload().await.unwrap();
+        assert_eq!(store.verify("sk_one"), Err(KeyRejection::Unknown));
+        assert_eq!(store.verify("sk_two").unwrap().key_id, "k2");
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(store.reload().await.is_err());
+        assert!(
+            store.verify("sk_two").is_ok(),
+            "a bad reload keeps the old keys"
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn admin_changes_persist_and_apply_immediately() {
+        let path = temp_path();
+        let keys = store(Vec::new(), KeySource::File(path.clone()));
+        keys.create_org("acme", Some("Acme".into()), Some("pro".into()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            keys.create_org("acme", None, None).await,
+            Err(AdminError::OrgExists)
+        ));
+        assert!(matches!(
+            keys.mint_key("nobody", Scope::ALL.to_vec(), None).await,
+            Err(AdminError::OrgNotFound)
+        ));
+
+        let (info, secret) = keys
+            .mint_key("acme", vec![Scope::JobsRead], None)
+            .await
+            .unwrap();
+        let verified = keys.verify(&secret).unwrap();
+        assert_eq!(verified.key_id, info.key_id);
+        assert_eq!(verified.scopes, vec![Scope::JobsRead]);
+        assert_eq!(verified.rate_plan.as_deref(), Some("pro"));
+        let listed = keys.keys_for("acme").unwrap();
+        assert!(listed[0].last_used_at.is_some());
+
+        keys.flush_last_used().await.unwrap();
+        let on_disk = std::fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains(&secret), "only the hash is persisted");
+        assert!(on_disk.contains("last_used_at"));
+
+        let revoked = keys.revoke("acme", &info.key_id).await.unwrap();
+        assert!(revoked.disabled);
+        assert_eq!(keys.verify(&secret), Err(KeyRejection::Disabled));
+
+        let restarted = store(Vec::new(), KeySource::File(path.clone()));
+        restarted.reload().await.unwrap();
+        assert_eq!(restarted.verify(&secret), Err(KeyRejection::Disabled));
+        assert_eq!(restarted.orgs()[0].name.as_deref(), Some("Acme"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn minted_keys_name_their_id_and_default_scopes_exclude_admin() {
+        let path = temp_path();
+        let keys = store(Vec::new(), KeySource::File(path.clone()));
+        keys.create_org("acme", None, None).await.unwrap();
+        let (info, secret) = keys
+            .mint_key("acme", Scope::DEFAULT.to_vec(), None)
+            .await
+            .unwrap();
+        assert_eq!(minted_key_id(&secret), Some(info.key_id.as_str()));
+        assert!(keys.verify(&secret).is_ok());
+        let forged = format!("hk_{}_{}", info.key_id, "0".repeat(48));
+        assert_eq!(keys.verify(&forged), Err(KeyRejection::Unknown));
+
+        let legacy: ApiKeyRow = serde_json::from_value(row("k1", "sk_one")).unwrap();
+        let legacy = ApiKey::from_row(legacy).unwrap();
+        assert!(!legacy.scopes.contains(&Scope::AccountsAdmin));
+        let env = parse_env_keys("acme:sk_one", &pepper("p"));
+        assert!(!env[0].scopes.contains(&Scope::AccountsAdmin));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn env_keys_are_read_only() {
+        let store = store(parse_env_keys("acme:sk_one", &pepper("p")), KeySource::Env);
+        let key_id = store.verify("sk_one").unwrap().key_id;
+        let secret = store.signing_secret_for("acme", &key_id).unwrap();
+        assert_eq!(store.signing_secret(&key_id), Some(secret));
+        assert!(matches!(
+            store.signing_secret_for("venture", &key_id),
+            Err(AdminError::KeyNotFound)
+        ));
+        assert!(matches!(
+            store.create_org("venture", None, None).await,
+            Err(AdminError::ReadOnly)
+        ));
+        assert!(matches!(
+            store.mint_key("acme", Scope::ALL.to_vec(), None).await,
+            Err(AdminError::ReadOnly)
+        ));
+    }
+}
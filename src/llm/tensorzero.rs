@@ -95,6 +95,7 @@ impl LlmClient {
             request = request.header("X-API-Key", key);
         }
 
+        crate::usage::record_llm_call();
         let response = request
             .send()
             .await
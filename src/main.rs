@@ -1,29 +1,47 @@
+mod admin;
+mod audit;
 mod ebay;
 mod hsuf;
 mod http;
 mod idempotency;
 mod jobs;
+mod keys;
 mod llm;
 mod metrics;
 mod models;
 mod pipeline;
+mod ratelimit;
+#[cfg(test)]
+mod redis_stand_in;
+mod retention;
 mod security;
+mod seller_accounts;
+mod signing;
 mod supabase;
+mod usage;
 
+use audit::{AuditAction, AuditEvent};
 use axum::{
     Json, Router,
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
     http::StatusCode,
     middleware,
-    response::{IntoResponse, Response},
+    response::{
+        IntoResponse, Redirect, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
     routing::{get, post},
 };
 use models::{ApiError, ListingRequest, ListingResponse};
-use pipeline::{Pipeline, PipelineError, PipelineErrorKind};
-use security::{AuthContext, AuthState, require_api_auth};
+use pipeline::{BoxFuture, Pipeline, PipelineError, PipelineErrorKind, RunHooks};
+use security::{AuthContext, AuthState, Scope, require_api_auth};
 use serde_json::json;
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
-use tokio::sync::Mutex;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::mpsc;
+use tokio_stream::{
+    Stream, StreamExt,
+    wrappers::{ReceiverStream, UnboundedReceiverStream},
+};
 // metrics macros disabled in demo build
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use tower_http::{
@@ -43,25 +61,45 @@ async fn main() {
 async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     init_tracing();
 
-    let auth_state = AuthState::from_env();
-    let pipeline = Pipeline::demo();
-    let (queue, _worker) = jobs::JobQueue::spawn(pipeline.clone());
+    let redis = std::env::var("REDIS_URL")
+        .ok()
+        .and_then(|u| redis::Client::open(u).ok());
+    let auth_state = AuthState::from_env(redis.clone()).await;
+    let admin = admin::router(auth_state.keys().clone());
+    let usage = usage::UsageMeter::from_env(redis.clone());
+    let audit = audit::AuditLog::from_env();
+    let seller_accounts = seller_accounts::SellerAccounts::from_env(redis.clone());
+    let mut pipeline = Pipeline::demo()
+        .with_usage(usage.clone())
+        .with_audit(audit.clone())
+        .with_token_cache(ebay::token_cache::TokenCache::from_env(redis.clone()));
+    if let Some(accounts) = &seller_accounts {
+        pipeline = pipeline.with_seller_accounts(accounts.clone());
+    }
+    let job_store = jobs::JobStore::from_env(redis.clone());
+    info!(
+        target = "hermes.api",
+        backend = job_store.backend_name(),
+        "job store configured"
+    );
+    let (queue, _worker) = jobs::JobQueue::spawn(pipeline.clone(), job_store);
     let openapi_raw = include_str!("../docs/openapi.yaml");
     let openapi: serde_json::Value =
         serde_yaml::from_str(openapi_raw).unwrap_or(serde_json::json!({"openapi":"3.0.3"}));
     let prometheus_handle = PrometheusBuilder::new()
         .install_recorder()
         .expect("prom recorder");
-    let redis = std::env::var("REDIS_URL")
-        .ok()
-        .and_then(|u| redis::Client::open(u).ok());
+    let idempotency_store = idempotency::IdempotencyStore::from_env(redis.clone());
+    tokio::spawn(sweep_idempotency(idempotency_store.clone()));
+    let idempotent = middleware::from_fn_with_state(idempotency_store, idempotency::idempotent);
     let state = AppState {
         pipeline,
         queue,
+        usage,
+        audit,
+        seller_accounts,
         openapi: Arc::new(openapi),
-        idempotency: Arc::new(Mutex::new(HashMap::new())),
         prometheus_handle: prometheus_handle.clone(),
-        redis,
     };
 
     let cors = CorsLayer::new()
@@ -70,8 +108,15 @@ async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .allow_origin(Any);
 
     let protected = Router::new()
-        .route("/listings", post(create_listing))
-        .route("/listings/continue", post(create_listing_continue))
+        .route("/listings", post(create_listing).layer(idempotent.clone()))
+        .route(
+            "/listings/continue",
+            post(create_listing_continue).layer(idempotent.clone()),
+        )
+        .route(
+            "/listings/stream",
+            post(create_listing_stream).layer(middleware::from_fn(idempotency::reject_key)),
+        )
         .nest(
             "/stages",
             Router::new()
@@ -83,10 +128,24 @@ async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .nest(
             "/jobs",
             Router::new()
-                .route("/listings", post(enqueue_listing_job))
-                .route("/listings/continue", post(enqueue_continue_job))
-                .route("/{id}", get(get_job_status)),
+                .route("/", get(list_jobs))
+                .route(
+                    "/listings",
+                    post(enqueue_listing_job).layer(idempotent.clone()),
+                )
+                .route(
+                    "/listings/continue",
+                    post(enqueue_continue_job).layer(idempotent),
+                )
+                .route("/{id}", get(get_job_status).delete(cancel_job))
+                .route("/{id}/cancel", post(cancel_job))
+                .route("/{id}/retry", post(retry_job))
+                .route("/{id}/deliveries", get(get_job_deliveries))
+                .route("/{id}/events", get(job_events)),
         )
+        .route("/usage", get(get_usage))
+        .route("/audit", get(get_audit))
+        .route("/ebay/connect", get(ebay_connect))
         .route_layer(middleware::from_fn_with_state(auth_state, require_api_auth));
 
     let app = Router::new()
@@ -94,13 +153,16 @@ async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/metrics", get(metrics_endpoint))
         .route("/openapi.json", get(openapi_json))
         .route("/docs", get(swagger_ui))
+        // eBay redirects the seller's browser here, so it carries no API key;
+        // the signed `state` names the org instead.
+        .route("/ebay/callback", get(ebay_callback))
         .merge(protected)
+        .nest("/admin", admin)
         .with_state(AppState {
             openapi: Arc::new(
                 serde_yaml::from_str(include_str!("../docs/openapi.yaml"))
                     .unwrap_or(serde_json::json!({"openapi":"3.0.3"})),
             ),
-            idempotency: Arc::new(Mutex::new(HashMap::new())),
             prometheus_handle,
             ..state
         })
@@ -124,10 +186,12 @@ async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 struct AppState {
     pipeline: Pipeline,
     queue: jobs::JobQueue,
+    usage: usage::UsageMeter,
+    audit: audit::AuditLog,
+    /// Per-org eBay accounts; `None` without `EBAY_TOKEN_ENCRYPTION_KEY`.
+    seller_accounts: Option<seller_accounts::SellerAccounts>,
     openapi: Arc<serde_json::Value>,
-    idempotency: Arc<Mutex<HashMap<String, ListingResponse>>>,
     prometheus_handle: PrometheusHandle,
-    redis: Option<redis::Client>,
 }
 
 /// Health and readiness check.
@@ -187,6 +251,15 @@ async fn swagger_ui() -> axum::http::Response<String> {
         .unwrap()
 }
 
+/// Background sweeper that keeps the in-memory idempotency cache bounded.
+async fn sweep_idempotency(cache: idempotency::IdempotencyStore) {
+    let interval = retention::sweep_interval_from_env();
+    loop {
+        tokio::time::sleep(interval).await;
+        cache.sweep().await.record("idempotency");
+    }
+}
+
 fn body_limit_from_env() -> usize {
     std::env::var("REQUEST_MAX_BYTES")
         .ok()
@@ -228,10 +301,16 @@ async fn metrics_endpoint(
 async fn create_listing(
     State(state): State<AppState>,
     Extension(context): Extension<AuthContext>,
-    headers: axum::http::HeaderMap,
     Json(payload): Json<ListingRequest>,
 ) -> Result<Json<ListingResponse>, AppError> {
     crate::metrics::inc_requests("/listings");
+    if !payload.dry_run {
+        context.require(Scope::ListingsWrite)?;
+    }
+    state
+        .usage
+        .check(&context, &listing_metrics(payload.dry_run))
+        .await?;
     let _handler_start = std::time::Instant::now();
     info!(
         target = "hermes.api",
@@ -240,35 +319,81 @@ async fn create_listing(
         "listing pipeline invoked",
     );
 
-    if let Some(key) = headers
-        .get("Idempotency-Key")
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-    {
-        if let Some(client) = &state.redis {
-            if let Some(existing) = idempotency::redis_get(client, &key).await {
-                return Ok(Json(existing));
-            }
-            let response = state.pipeline.run(payload, Some(context)).await?;
-            let ttl = std::env::var("IDEMPOTENCY_TTL_SECS")
-                .ok()
-                .and_then(|v| v.parse::<usize>().ok())
-                .unwrap_or(3600);
-            idempotency::redis_set(client, &key, &response, ttl).await;
-            return Ok(Json(response));
-        }
-        if let Some(existing) = state.idempotency.lock().await.get(&key).cloned() {
-            return Ok(Json(existing));
-        }
-        let response = state.pipeline.run(payload, Some(context)).await?;
-        state.idempotency.lock().await.insert(key, response.clone());
-        return Ok(Json(response));
+    let event = AuditEvent::new(
+        &context,
+        AuditAction::ListingCreate,
+        "POST /listings",
+        &payload,
+    );
+    let result = state.pipeline.run(payload, Some(context)).await;
+    state.audit.record(event.finish(&result)).await;
+
+    Ok(Json(result?))
+}
+
+/// Run the listing pipeline, streaming progress as Server-Sent Events.
+///
+/// - Method: `POST`
+/// - Path: `/listings/stream`
+/// - Body: `ListingRequest`
+/// - Response: `text/event-stream` with one `stage` event per `StageReport`,
+///   then `completed` (`ListingResponse`) or `failed` (`ApiError`)
+async fn create_listing_stream(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Json(payload): Json<ListingRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    crate::metrics::inc_requests("/listings/stream");
+    if !payload.dry_run {
+        context.require(Scope::ListingsWrite)?;
     }
+    state
+        .usage
+        .check(&context, &listing_metrics(payload.dry_run))
+        .await?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    // The run is not tied to the connection: a client that disconnects
+    // mid-publish must not leave a half-created listing behind.
+    tokio::spawn(async move {
+        let hooks = StageStream(tx.clone());
+        let audit_event = AuditEvent::new(
+            &context,
+            AuditAction::ListingCreate,
+            "POST /listings/stream",
+            &payload,
+        );
+        let result = state
+            .pipeline
+            .run_with_hooks(payload, Some(context), &hooks)
+            .await;
+        state.audit.record(audit_event.finish(&result)).await;
+        let event = match result {
+            Ok(response) => Event::default().event("completed").json_data(&response),
+            Err(err) => Event::default()
+                .event("failed")
+                .json_data(PipelineFailure::from(&err)),
+        };
+        let _ = tx.send(event);
+    });
+    Ok(Sse::new(UnboundedReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
 
-    let response = state.pipeline.run(payload, Some(context)).await?;
+/// Forwards each stage report of a streamed run to the SSE response.
+struct StageStream(mpsc::UnboundedSender<Result<Event, axum::Error>>);
 
-    Ok(Json(response))
+impl RunHooks for StageStream {
+    fn before_stage<'a>(
+        &'a self,
+        _stage: &'static str,
+    ) -> BoxFuture<'a, Result<(), PipelineError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn after_stage(&self, report: &models::StageReport) {
+        let _ = self
+            .0
+            .send(Event::default().event("stage").json_data(report));
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -298,6 +423,8 @@ async fn create_listing_continue(
     Json(payload): Json<ContinueRequest>,
 ) -> Result<Json<ListingResponse>, AppError> {
     crate::metrics::inc_requests("/listings/continue");
+    context.require(Scope::ListingsWrite)?;
+    state.usage.check(&context, &listing_metrics(false)).await?;
     let images_source = payload
         .images_source
         .unwrap_or(models::ImagesSource::Single(String::new()));
@@ -316,13 +443,25 @@ async fn create_listing_continue(
         overrides: payload.overrides,
         dry_run: false,
     };
-    let response = state.pipeline.run(req, Some(context)).await?;
-    Ok(Json(response))
+    let event = AuditEvent::new(
+        &context,
+        AuditAction::ListingContinue,
+        "POST /listings/continue",
+        &req,
+    );
+    let result = state.pipeline.run(req, Some(context)).await;
+    state.audit.record(event.finish(&result)).await;
+    Ok(Json(result?))
 }
 
 #[derive(Debug)]
 enum AppError {
     Pipeline(PipelineError),
+    Job(jobs::JobError),
+    Scope(security::InsufficientScope),
+    Usage(usage::UsageError),
+    Audit(audit::AuditError),
+    SellerAccount(seller_accounts::SellerAccountError),
 }
 
 impl From<PipelineError> for AppError {
@@ -331,22 +470,166 @@ impl From<PipelineError> for AppError {
     }
 }
 
+impl From<jobs::JobError> for AppError {
+    fn from(value: jobs::JobError) -> Self {
+        Self::Job(value)
+    }
+}
+
+impl From<security::InsufficientScope> for AppError {
+    fn from(value: security::InsufficientScope) -> Self {
+        Self::Scope(value)
+    }
+}
+
+impl From<audit::AuditError> for AppError {
+    fn from(value: audit::AuditError) -> Self {
+        Self::Audit(value)
+    }
+}
+
+impl From<seller_accounts::SellerAccountError> for AppError {
+    fn from(value: seller_accounts::SellerAccountError) -> Self {
+        Self::SellerAccount(value)
+    }
+}
+
+impl From<usage::UsageError> for AppError {
+    fn from(value: usage::UsageError) -> Self {
+        Self::Usage(value)
+    }
+}
+
+/// Send the caller's seller to eBay to connect their account to this org.
+///
+/// - Method: `GET`
+/// - Path: `/ebay/connect`
+/// - Response: `303` redirect to eBay's consent page; after consent eBay
+///   returns the seller to `/ebay/callback`
+async fn ebay_connect(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+) -> Result<Redirect, AppError> {
+    crate::metrics::inc_requests("/ebay/connect");
+    let accounts = state
+        .seller_accounts
+        .as_ref()
+        .ok_or(seller_accounts::SellerAccountError::Disabled)?;
+    Ok(Redirect::to(&accounts.connect_url(&context.org_id).await?))
+}
+
+#[derive(Debug, Deserialize)]
+struct EbayCallbackQuery {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+}
+
+/// Finish the consent flow: store the org's eBay refresh token.
+///
+/// - Method: `GET`
+/// - Path: `/ebay/callback?code=&state=`
+/// - Auth: none; `state` from `/ebay/connect` identifies the org
+/// - Response: `{ org_id, connected_at, refresh_token_expires_at? }`
+async fn ebay_callback(
+    State(state): State<AppState>,
+    Query(query): Query<EbayCallbackQuery>,
+) -> Result<Json<seller_accounts::ConnectedAccount>, AppError> {
+    crate::metrics::inc_requests("/ebay/callback");
+    let accounts = state
+        .seller_accounts
+        .as_ref()
+        .ok_or(seller_accounts::SellerAccountError::Disabled)?;
+    let oauth_state = query
+        .state
+        .ok_or(seller_accounts::SellerAccountError::InvalidState)?;
+    // eBay sends the seller back without a code when they decline.
+    let code = query
+        .code
+        .filter(|code| !code.is_empty())
+        .ok_or(seller_accounts::SellerAccountError::Declined)?;
+    Ok(Json(accounts.complete(&code, &oauth_state).await?))
+}
+
+/// Quotas a listing run must be under before it starts.
+fn listing_metrics(dry_run: bool) -> [usage::Metric; 3] {
+    [
+        usage::Metric::for_listing(dry_run),
+        usage::Metric::LlmCalls,
+        usage::Metric::EbayCalls,
+    ]
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageQuery {
+    /// `YYYY-MM`; defaults to the current month.
+    #[serde(default)]
+    period: Option<String>,
+}
+
+/// The caller's metered usage for a month, against its plan's quotas.
+///
+/// - Method: `GET`
+/// - Path: `/usage?period=YYYY-MM`
+/// - Response: `{ org_id, period, plan, usage, quotas, resets_at }`
+async fn get_usage(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<usage::UsageReport>, AppError> {
+    crate::metrics::inc_requests("/usage");
+    let period = query.period.as_deref().map(str::trim);
+    if let Some(period) = period
+        && chrono::NaiveDate::parse_from_str(&format!("{period}-01"), "%Y-%m-%d").is_err()
+    {
+        return Err(AppError::Pipeline(PipelineError::invalid_input(
+            "usage",
+            "invalid_period",
+        )));
+    }
+    Ok(Json(state.usage.report(&context, period).await?))
+}
+
 #[derive(Debug, Serialize)]
 struct EnqueueResponse {
     job_id: String,
 }
 
+/// A job submission: the pipeline request plus job-only fields such as
+/// `callback_url`, side by side in the same JSON object.
+#[derive(Debug, Deserialize)]
+struct JobRequest<T> {
+    #[serde(flatten)]
+    body: T,
+    #[serde(flatten)]
+    options: jobs::JobOptions,
+}
+
 async fn enqueue_listing_job(
     State(state): State<AppState>,
     Extension(context): Extension<AuthContext>,
-    Json(payload): Json<ListingRequest>,
+    Json(payload): Json<JobRequest<ListingRequest>>,
 ) -> Result<Json<EnqueueResponse>, AppError> {
     crate::metrics::inc_requests("/jobs/listings");
-    let id = state
+    if !payload.body.dry_run {
+        context.require(Scope::ListingsWrite)?;
+    }
+    state
+        .usage
+        .check(&context, &listing_metrics(payload.body.dry_run))
+        .await?;
+    let event = AuditEvent::new(
+        &context,
+        AuditAction::JobEnqueue,
+        "POST /jobs/listings",
+        &payload.body,
+    );
+    let enqueued = state
         .queue
-        .enqueue_listing(payload, context)
-        .await
-        .map_err(|err| AppError::Pipeline(PipelineError::internal("enqueue", err.error)))?;
+        .enqueue_listing(payload.body, context, payload.options)
+        .await;
+    let id = record_enqueue(&state.audit, event, enqueued).await?;
     Ok(Json(EnqueueResponse {
         job_id: id.to_string(),
     }))
@@ -355,9 +638,14 @@ async fn enqueue_listing_job(
 async fn enqueue_continue_job(
     State(state): State<AppState>,
     Extension(context): Extension<AuthContext>,
-    Json(payload): Json<ContinueRequest>,
+    Json(JobRequest {
+        body: payload,
+        options,
+    }): Json<JobRequest<ContinueRequest>>,
 ) -> Result<Json<EnqueueResponse>, AppError> {
     crate::metrics::inc_requests("/jobs/listings/continue");
+    context.require(Scope::ListingsWrite)?;
+    state.usage.check(&context, &listing_metrics(false)).await?;
     let images_source = payload
         .images_source
         .unwrap_or(models::ImagesSource::Single(String::new()));
@@ -376,18 +664,91 @@ async fn enqueue_continue_job(
         overrides: payload.overrides,
         dry_run: false,
     };
-    let id = state
-        .queue
-        .enqueue_listing(req, context)
-        .await
-        .map_err(|err| AppError::Pipeline(PipelineError::internal("enqueue", err.error)))?;
+    let event = AuditEvent::new(
+        &context,
+        AuditAction::JobEnqueue,
+        "POST /jobs/listings/continue",
+        &req,
+    );
+    let enqueued = state.queue.enqueue_listing(req, context, options).await;
+    let id = record_enqueue(&state.audit, event, enqueued).await?;
     Ok(Json(EnqueueResponse {
         job_id: id.to_string(),
     }))
 }
 
+/// Audit a job submission, passing its outcome through.
+async fn record_enqueue<T: std::fmt::Display>(
+    audit: &audit::AuditLog,
+    event: AuditEvent,
+    enqueued: Result<T, jobs::JobError>,
+) -> Result<T, jobs::JobError> {
+    let event = match &enqueued {
+        Ok(id) => event.accepted(id),
+        Err(err) => event.failed(err),
+    };
+    audit.record(event).await;
+    enqueued
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQueryParams {
+    #[serde(default)]
+    since: Option<String>,
+    #[serde(default)]
+    until: Option<String>,
+    #[serde(default)]
+    action: Option<String>,
+    #[serde(default)]
+    sku: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct AuditPage {
+    events: Vec<audit::AuditEvent>,
+}
+
+/// The caller's audit trail, newest first.
+///
+/// - Method: `GET`
+/// - Path: `/audit?since=&until=&action=&sku=&limit=`
+/// - Response: `{ events: AuditEvent[] }`; pass the last event's `at` as
+///   `until` for the next page
+async fn get_audit(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Query(params): Query<AuditQueryParams>,
+) -> Result<Json<AuditPage>, AppError> {
+    crate::metrics::inc_requests("/audit");
+    let time = |raw: Option<&str>, code: &'static str| match raw {
+        Some(raw) => chrono::DateTime::parse_from_rfc3339(raw)
+            .map(|at| Some(at.with_timezone(&chrono::Utc)))
+            .map_err(|_| PipelineError::invalid_input("audit", code)),
+        None => Ok(None),
+    };
+    let action = match params.action.as_deref() {
+        Some(raw) => Some(
+            AuditAction::parse(raw)
+                .ok_or_else(|| PipelineError::invalid_input("audit", "invalid_action"))?,
+        ),
+        None => None,
+    };
+    let query = audit::AuditQuery {
+        since: time(params.since.as_deref(), "invalid_since")?,
+        until: time(params.until.as_deref(), "invalid_until")?,
+        action,
+        sku: params.sku.filter(|sku| !sku.is_empty()),
+        limit: params.limit.unwrap_or(audit::DEFAULT_LIMIT),
+    };
+    let events = state.audit.query(&context.org_id, &query).await?;
+    Ok(Json(AuditPage { events }))
+}
+
 async fn get_job_status(
     State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
     Path(id): Path<String>,
 ) -> Result<Json<jobs::JobInfo>, AppError> {
     let Ok(uuid) = uuid::Uuid::parse_str(&id) else {
@@ -396,29 +757,235 @@ async fn get_job_status(
             "invalid_job_id",
         )));
     };
-    if let Some(info) = state.queue.get(uuid).await {
-        Ok(Json(info))
-    } else {
-        Err(AppError::Pipeline(PipelineError::invalid_input(
+    let info = state.queue.get(uuid, &context.org_id).await?;
+    Ok(Json(info))
+}
+
+/// Callback delivery log for a job.
+///
+/// - Method: `GET`
+/// - Path: `/jobs/{id}/deliveries`
+/// - Response: `WebhookDelivery[]`, one entry per POST to the job's `callback_url`
+async fn get_job_deliveries(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<jobs::WebhookDelivery>>, AppError> {
+    crate::metrics::inc_requests("/jobs/{id}/deliveries");
+    let Ok(uuid) = uuid::Uuid::parse_str(&id) else {
+        return Err(AppError::Pipeline(PipelineError::invalid_input(
+            "jobs",
+            "invalid_job_id",
+        )));
+    };
+    let deliveries = state.queue.deliveries(uuid, &context.org_id).await?;
+    Ok(Json(deliveries))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListJobsQuery {
+    /// Comma-separated state names, e.g. `failed,dead_lettered`.
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    sku: Option<String>,
+    /// RFC 3339 timestamp; only jobs created at or after it.
+    #[serde(default)]
+    since: Option<String>,
+    #[serde(default)]
+    cursor: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+/// List the caller's jobs, newest first.
+///
+/// - Method: `GET`
+/// - Path: `/jobs?state=&sku=&since=&cursor=&limit=`
+/// - Response: `{ jobs: JobSummary[], next_cursor? }`; pass `next_cursor`
+///   back as `cursor` for the next page
+async fn list_jobs(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Query(query): Query<ListJobsQuery>,
+) -> Result<Json<jobs::JobPage>, AppError> {
+    crate::metrics::inc_requests("/jobs");
+    let states: Vec<String> = query
+        .state
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+    if states
+        .iter()
+        .any(|s| !jobs::JobState::NAMES.contains(&s.as_str()))
+    {
+        return Err(AppError::Pipeline(PipelineError::invalid_input(
+            "jobs",
+            "invalid_state",
+        )));
+    }
+    let since = match query.since.as_deref() {
+        Some(raw) => Some(
+            chrono::DateTime::parse_from_rfc3339(raw)
+                .map_err(|_| PipelineError::invalid_input("jobs", "invalid_since"))?
+                .with_timezone(&chrono::Utc),
+        ),
+        None => None,
+    };
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => Some(
+            jobs::JobCursor::decode(raw)
+                .ok_or_else(|| PipelineError::invalid_input("jobs", "invalid_cursor"))?,
+        ),
+        None => None,
+    };
+    let filter = jobs::JobFilter {
+        org_id: context.org_id,
+        states,
+        sku: query.sku.filter(|s| !s.is_empty()),
+        since,
+    };
+    let limit = query.limit.unwrap_or(jobs::DEFAULT_PAGE_SIZE);
+    let page = state.queue.list(&filter, cursor, limit).await?;
+    Ok(Json(page))
+}
+
+/// Live progress for a job as Server-Sent Events.
+///
+/// - Method: `GET`
+/// - Path: `/jobs/{id}/events`
+/// - Response: `text/event-stream`; the first event carries the current
+///   `JobInfo` (named after its state), followed by `stage` events
+///   (`StageReport`) and state changes. The stream ends after a terminal
+///   state (`completed`, `failed`, `dead_lettered`, `cancelled`).
+async fn job_events(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    crate::metrics::inc_requests("/jobs/{id}/events");
+    let Ok(uuid) = uuid::Uuid::parse_str(&id) else {
+        return Err(AppError::Pipeline(PipelineError::invalid_input(
+            "jobs",
+            "invalid_job_id",
+        )));
+    };
+    let events = state.queue.watch(uuid, &context.org_id).await?;
+    let stream = ReceiverStream::new(events)
+        .map(|event| Event::default().event(event.name()).json_data(&event));
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Requeue a dead-lettered job with a fresh retry budget.
+///
+/// - Method: `POST`
+/// - Path: `/jobs/{id}/retry`
+/// - Response: `JobInfo` (state `queued`); 409 unless the job is `dead_lettered`
+async fn retry_job(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<jobs::JobInfo>, AppError> {
+    crate::metrics::inc_requests("/jobs/{id}/retry");
+    let Ok(uuid) = uuid::Uuid::parse_str(&id) else {
+        return Err(AppError::Pipeline(PipelineError::invalid_input(
+            "jobs",
+            "invalid_job_id",
+        )));
+    };
+    let info = state.queue.requeue(uuid, &context.org_id).await?;
+    Ok(Json(info))
+}
+
+/// Cancel a queued or running job.
+///
+/// - Method: `DELETE /jobs/{id}` or `POST /jobs/{id}/cancel`
+/// - Response: `200` with `JobInfo` (state `cancelled`) for waiting jobs;
+///   `202` with `cancel_requested_at` set while a running job winds down;
+///   `409` once the job is publishing or finished
+async fn cancel_job(
+    State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<jobs::JobInfo>), AppError> {
+    crate::metrics::inc_requests("/jobs/{id}/cancel");
+    let Ok(uuid) = uuid::Uuid::parse_str(&id) else {
+        return Err(AppError::Pipeline(PipelineError::invalid_input(
             "jobs",
-            "not_found",
-        )))
+            "invalid_job_id",
+        )));
+    };
+    let info = state.queue.cancel(uuid, &context.org_id).await?;
+    let status = if matches!(info.state, jobs::JobState::Cancelled { .. }) {
+        StatusCode::OK
+    } else {
+        StatusCode::ACCEPTED
+    };
+    Ok((status, Json(info)))
+}
+
+/// The `ApiError` shape for a failed run, plus eBay's parsed error when an
+/// eBay call failed it.
+#[derive(Debug, Serialize)]
+struct PipelineFailure<'a> {
+    error: &'static str,
+    detail: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ebay: Option<&'a ebay::EbayApiError>,
+}
+
+impl<'a> From<&'a PipelineError> for PipelineFailure<'a> {
+    fn from(err: &'a PipelineError) -> Self {
+        Self {
+            error: err.stage(),
+            detail: err.detail(),
+            ebay: err.ebay_error(),
+        }
     }
 }
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         match self {
             AppError::Pipeline(err) => {
+                if let Some(usage) = err.usage_error() {
+                    return usage.clone().into_response();
+                }
                 let status = match err.kind() {
                     PipelineErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+                    // eBay auth failures, rate limits and outages.
+                    PipelineErrorKind::Internal if err.ebay_error().is_some() => {
+                        StatusCode::BAD_GATEWAY
+                    }
                     PipelineErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+                    PipelineErrorKind::Cancelled => StatusCode::CONFLICT,
+                    PipelineErrorKind::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+                };
+                (status, Json(PipelineFailure::from(&err))).into_response()
+            }
+            AppError::Job(err) => {
+                let (status, detail) = match &err {
+                    jobs::JobError::NotFound => (StatusCode::NOT_FOUND, "not_found".to_string()),
+                    jobs::JobError::Invalid(code) => (StatusCode::BAD_REQUEST, code.to_string()),
+                    jobs::JobError::Conflict(code) => (StatusCode::CONFLICT, code.to_string()),
+                    jobs::JobError::Store(inner) => {
+                        (StatusCode::INTERNAL_SERVER_ERROR, inner.to_string())
+                    }
                 };
                 let payload = ApiError {
-                    error: err.stage().to_string(),
-                    detail: Some(err.detail().to_string()),
+                    error: "jobs".to_string(),
+                    detail: Some(detail),
                 };
                 (status, Json(payload)).into_response()
             }
+            AppError::Scope(err) => err.into_response(),
+            AppError::Usage(err) => err.into_response(),
+            AppError::Audit(err) => err.into_response(),
+            AppError::SellerAccount(err) => err.into_response(),
         }
     }
 }
@@ -542,9 +1109,14 @@ struct ExtractProductResponse {
 
 async fn stage_extract_product(
     State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
     Json(req): Json<ExtractProductRequest>,
 ) -> Result<Json<ExtractProductResponse>, AppError> {
     crate::metrics::inc_requests("/stages/extract_product");
+    state
+        .usage
+        .check(&context, &[usage::Metric::LlmCalls])
+        .await?;
     let listing = ListingRequest {
         images_source: models::ImagesSource::Multiple(vec![]),
         sku: req.sku.clone(),
@@ -561,7 +1133,13 @@ async fn stage_extract_product(
         dry_run: false,
     };
     let llm = &state.pipeline.llm;
-    let out = pipeline::stages::extract_product(&listing, &req.images, 0, llm)
+    let out = state
+        .usage
+        .track(
+            &context.org_id,
+            pipeline::stages::extract_product(&listing, &req.images, 0, llm),
+            |_| None,
+        )
         .await
         .map_err(AppError::from)?;
     Ok(Json(ExtractProductResponse { product: out.value }))
@@ -581,20 +1159,27 @@ struct DescriptionResponse {
 
 async fn stage_description(
     State(state): State<AppState>,
+    Extension(context): Extension<AuthContext>,
     Json(req): Json<DescriptionRequest>,
 ) -> Result<Json<DescriptionResponse>, AppError> {
     crate::metrics::inc_requests("/stages/description");
+    state
+        .usage
+        .check(&context, &[usage::Metric::LlmCalls])
+        .await?;
     let prompt = format!(
         "Generate a compelling, policy-compliant eBay listing description. Title: {title}. Bullet points: {bullets:?}.",
         title = req.title,
         bullets = req.bullets,
     );
     let llm = &state.pipeline.llm;
-    match llm
-        .chat(&[llm::LlmMessage {
-            role: "user".into(),
-            content: prompt,
-        }])
+    let messages = [llm::LlmMessage {
+        role: "user".into(),
+        content: prompt,
+    }];
+    match state
+        .usage
+        .track(&context.org_id, llm.chat(&messages), |_| None)
         .await
     {
         Ok(resp) => Ok(Json(DescriptionResponse {
@@ -19,3 +19,13 @@ pub fn stage_elapsed(stage: &'static str, elapsed_ms: u128) {
         "stage_elapsed"
     );
 }
+
+pub fn evicted(store: &'static str, reason: &'static str, count: usize) {
+    trace!(
+        target = "hermes.metrics",
+        store = store,
+        reason = reason,
+        count = count as u64,
+        "evictions_total_inc"
+    );
+}
@@ -2,7 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct ListingRequest {
     pub images_source: ImagesSource,
@@ -62,7 +62,7 @@ pub struct ApiError {
     pub detail: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CategorySelectionInput {
     pub id: String,
     pub tree_id: String,
@@ -71,7 +71,7 @@ pub struct CategorySelectionInput {
     pub rationale: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineOverrides {
     #[serde(default)]
     pub resolved_images: Option<Vec<String>>,
@@ -110,7 +110,7 @@ impl MarketplaceId {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ImagesSource {
     Single(String),
@@ -1,4 +1,8 @@
-use crate::ebay::auth::get_user_access_token_from_refresh;
+use crate::audit::{AuditAction, AuditEvent, AuditLog};
+use crate::ebay::auth::EbayAuthError;
+use crate::ebay::config::USER_SCOPES;
+use crate::ebay::error::{EbayApiError, EbayErrorKind};
+use crate::ebay::executor::{self, StageCalls};
 use crate::ebay::inventory::{
     InventoryAvailability, InventoryItemRequest, InventoryLocationRequest, InventoryProduct,
     LocationAddress, LocationDetails, LocationGeo, ShipToLocationAvailability,
@@ -10,6 +14,7 @@ use crate::ebay::taxonomy::{
     Aspect as EbayAspect, AspectConstraint as EbayAspectConstraint, AspectValue as EbayAspectValue,
     TaxonomyResponse as EbayTaxonomyResponse,
 };
+use crate::ebay::token_cache::TokenCache;
 use crate::hsuf::ingest;
 use crate::hsuf::{
     HsufListingContext, Product as HsufProduct, build_listing_draft, estimate_package,
@@ -17,14 +22,17 @@ use crate::hsuf::{
 use crate::llm::{LlmClient, LlmConfig, LlmMessage};
 use crate::models::{ImagesSource, ListingRequest, ListingResponse, MarketplaceId, StageReport};
 use crate::security::AuthContext;
+use crate::seller_accounts::SellerAccounts;
 use crate::supabase::{EbayOrgConfig, SupabaseClient};
-use serde::Serialize;
+use crate::usage::{Metric, UsageError, UsageMeter};
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::{
     collections::{BTreeMap, HashSet, hash_map::DefaultHasher},
     env,
     future::Future,
     hash::{Hash, Hasher},
+    pin::Pin,
     sync::Arc,
     time::Instant,
 };
@@ -40,7 +48,11 @@ pub struct Pipeline {
     pub llm: Arc<LlmClient>,
     ebay_refresh_token: Option<String>,
     ebay_network_enabled: bool,
+    ebay_tokens: TokenCache,
+    seller_accounts: Option<SellerAccounts>,
     supabase: Option<SupabaseClient>,
+    usage: Option<UsageMeter>,
+    audit: Option<AuditLog>,
 }
 
 impl Pipeline {
@@ -55,7 +67,11 @@ impl Pipeline {
             llm: Arc::new(llm),
             ebay_refresh_token,
             ebay_network_enabled,
+            ebay_tokens: TokenCache::from_env(None),
+            seller_accounts: None,
             supabase,
+            usage: None,
+            audit: None,
         }
     }
 
@@ -63,6 +79,57 @@ impl Pipeline {
         Self::new(PipelineConfig::default())
     }
 
+    /// Meter every run made on behalf of an org with `usage`.
+    pub fn with_usage(self, usage: UsageMeter) -> Self {
+        Self {
+            usage: Some(usage),
+            ..self
+        }
+    }
+
+    /// Reuse eBay access tokens through `tokens`, e.g. one shared via Redis.
+    pub fn with_token_cache(self, tokens: TokenCache) -> Self {
+        Self {
+            ebay_tokens: tokens,
+            ..self
+        }
+    }
+
+    /// Publish with each org's own connected eBay account.
+    pub fn with_seller_accounts(self, accounts: SellerAccounts) -> Self {
+        Self {
+            seller_accounts: Some(accounts),
+            ..self
+        }
+    }
+
+    /// Record every live eBay publish in `audit`.
+    pub fn with_audit(self, audit: AuditLog) -> Self {
+        Self {
+            audit: Some(audit),
+            ..self
+        }
+    }
+
+    /// Run `fut`, charging the calling org for it when metering is on.
+    /// `charge` is reserved from the org's quota before the run starts and
+    /// kept only if it succeeds.
+    async fn metered<T>(
+        &self,
+        auth: Option<&AuthContext>,
+        charge: Option<Metric>,
+        fut: impl Future<Output = Result<T, PipelineError>>,
+    ) -> Result<T, PipelineError> {
+        match (&self.usage, auth, charge) {
+            (Some(usage), Some(ctx), Some(charge)) => usage
+                .track_reserved(ctx, charge, fut, Result::is_ok)
+                .await
+                .map_err(PipelineError::usage)?,
+            (Some(usage), Some(ctx), None) => usage.track(&ctx.org_id, fut, |_| None).await,
+            _ => fut.await,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn llm_client(&self) -> &LlmClient {
         &self.llm
@@ -105,14 +172,53 @@ impl Pipeline {
         Ok(out.value)
     }
 
-    async fn fetch_ebay_token(&self) -> Result<String, PipelineError> {
-        let refresh = self
-            .ebay_refresh_token
-            .as_ref()
-            .ok_or_else(|| PipelineError::internal("ebay_auth", "EBAY_REFRESH_TOKEN is not set"))?;
-        get_user_access_token_from_refresh(refresh, EBAY_USER_SCOPES)
+    /// The refresh token to publish with: the caller's own eBay account
+    /// when per-org accounts are on, else `EBAY_REFRESH_TOKEN`.
+    fn ebay_refresh_token_for(
+        &self,
+        auth: Option<&AuthContext>,
+        org_config: Option<&EbayOrgConfig>,
+    ) -> Result<String, PipelineError> {
+        if let (Some(accounts), Some(ctx)) = (&self.seller_accounts, auth) {
+            return match accounts.refresh_token(&ctx.org_id, org_config) {
+                Ok(Some(token)) => Ok(token),
+                Ok(None) => Err(PipelineError::invalid_input(
+                    "ebay_auth",
+                    "ebay_account_not_connected: connect one with GET /ebay/connect",
+                )),
+                Err(err) => Err(PipelineError::internal("ebay_auth", err.to_string())),
+            };
+        }
+        self.ebay_refresh_token
+            .clone()
+            .ok_or_else(|| PipelineError::internal("ebay_auth", "EBAY_REFRESH_TOKEN is not set"))
+    }
+
+    async fn fetch_ebay_token(&self, refresh: &str) -> Result<String, PipelineError> {
+        self.ebay_tokens
+            .user_token(refresh, USER_SCOPES)
             .await
-            .map_err(|err| PipelineError::internal("ebay_auth", err.to_string()))
+            .map_err(|err| match err {
+                EbayAuthError::Api(err) => PipelineError::ebay("ebay_auth", err),
+                other => PipelineError::internal("ebay_auth", other.to_string()),
+            })
+    }
+
+    /// Drop the cached access token when eBay refused it, so the next run
+    /// mints a new one instead of failing until it expires.
+    async fn forget_rejected_token<T>(
+        &self,
+        refresh: Option<&str>,
+        result: &Result<T, PipelineError>,
+    ) {
+        let rejected = result
+            .as_ref()
+            .err()
+            .and_then(PipelineError::ebay_error)
+            .is_some_and(|err| err.kind == EbayErrorKind::Auth);
+        if let (true, Some(refresh)) = (rejected, refresh) {
+            self.ebay_tokens.invalidate(refresh, USER_SCOPES).await;
+        }
     }
 
     pub async fn run(
@@ -120,23 +226,109 @@ impl Pipeline {
         request: ListingRequest,
         auth: Option<AuthContext>,
     ) -> Result<ListingResponse, PipelineError> {
-        let request = Arc::new(request);
-        let mut stages = Vec::new();
-        let org_config = match (auth.as_ref(), self.supabase.as_ref()) {
-            (Some(ctx), Some(client)) => {
-                let org_id = Uuid::parse_str(&ctx.org_id)
-                    .map_err(|err| PipelineError::internal("supabase", err.to_string()))?;
-                match client.fetch_ebay_org_config(org_id).await {
-                    Ok(config) => config,
-                    Err(err) => {
-                        warn!(target = "hermes.supabase", org_id = %ctx.org_id, error = %err, "ebay_org_config_lookup_failed");
-                        None
-                    }
-                }
+        self.run_with_hooks(request, auth, &NoHooks).await
+    }
+
+    /// Like [`Pipeline::run`], consulting `hooks` around every stage.
+    pub async fn run_with_hooks(
+        &self,
+        request: ListingRequest,
+        auth: Option<AuthContext>,
+        hooks: &dyn RunHooks,
+    ) -> Result<ListingResponse, PipelineError> {
+        let charge = Metric::for_listing(request.dry_run);
+        let run = async {
+            let request = Arc::new(request);
+            let org_config = self.org_config(auth.as_ref()).await?;
+            let prepared = self
+                .prepare_with(&request, org_config.as_ref(), hooks)
+                .await?;
+            if request.dry_run {
+                return Ok(ListingResponse {
+                    listing_id: format!("PREVIEW-{}", Uuid::new_v4().simple()),
+                    stages: prepared.stages,
+                });
             }
-            _ => None,
+            self.publish_with(
+                &request,
+                auth.as_ref(),
+                org_config.as_ref(),
+                prepared,
+                hooks,
+            )
+            .await
+        };
+        self.metered(auth.as_ref(), Some(charge), run).await
+    }
+
+    /// Run every stage up to and including `build_listing`, so the listing
+    /// can be published later with [`Pipeline::publish_prepared`].
+    pub async fn prepare(
+        &self,
+        request: ListingRequest,
+        auth: Option<AuthContext>,
+        hooks: &dyn RunHooks,
+    ) -> Result<PreparedListing, PipelineError> {
+        let run = async {
+            let request = Arc::new(request);
+            let org_config = self.org_config(auth.as_ref()).await?;
+            self.prepare_with(&request, org_config.as_ref(), hooks)
+                .await
+        };
+        self.metered(auth.as_ref(), None, run).await
+    }
+
+    /// Finish a listing from [`Pipeline::prepare`] with `push_inventory` and
+    /// `publish_offer`. The org's eBay settings are looked up again, so edits
+    /// made since preparation apply.
+    pub async fn publish_prepared(
+        &self,
+        request: ListingRequest,
+        auth: Option<AuthContext>,
+        prepared: PreparedListing,
+        hooks: &dyn RunHooks,
+    ) -> Result<ListingResponse, PipelineError> {
+        let run = async {
+            let request = Arc::new(request);
+            let org_config = self.org_config(auth.as_ref()).await?;
+            self.publish_with(
+                &request,
+                auth.as_ref(),
+                org_config.as_ref(),
+                prepared,
+                hooks,
+            )
+            .await
         };
+        self.metered(auth.as_ref(), Some(Metric::ListingsPublished), run)
+            .await
+    }
+
+    async fn org_config(
+        &self,
+        auth: Option<&AuthContext>,
+    ) -> Result<Option<EbayOrgConfig>, PipelineError> {
+        let (Some(ctx), Some(client)) = (auth, self.supabase.as_ref()) else {
+            return Ok(None);
+        };
+        let org_id = Uuid::parse_str(&ctx.org_id)
+            .map_err(|err| PipelineError::internal("supabase", err.to_string()))?;
+        match client.fetch_ebay_org_config(org_id).await {
+            Ok(config) => Ok(config),
+            Err(err) => {
+                warn!(target = "hermes.supabase", org_id = %ctx.org_id, error = %err, "ebay_org_config_lookup_failed");
+                Ok(None)
+            }
+        }
+    }
 
+    async fn prepare_with(
+        &self,
+        request: &Arc<ListingRequest>,
+        org_config: Option<&EbayOrgConfig>,
+        hooks: &dyn RunHooks,
+    ) -> Result<PreparedListing, PipelineError> {
+        let mut stages = Vec::new();
         let images = if let Some(ov) = &request.overrides {
             if let Some(imgs) = ov.resolved_images.clone() {
                 if imgs.is_empty() {
@@ -151,6 +343,7 @@ impl Pipeline {
                         "too_many_images",
                     ));
                 }
+                hooks.before_stage("resolve_images").await?;
                 let started = Instant::now();
                 let imgs2 = imgs.clone();
                 let output = json!({
@@ -159,32 +352,31 @@ impl Pipeline {
                     "use_signed_urls": request.use_signed_urls,
                     "source": "override",
                 });
-                stages.push(StageReport::new(
-                    "resolve_images",
-                    started.elapsed().as_millis(),
-                    output,
-                ));
+                let report =
+                    StageReport::new("resolve_images", started.elapsed().as_millis(), output);
+                hooks.after_stage(&report);
+                stages.push(report);
                 imgs
             } else {
-                self.capture_stage("resolve_images", &mut stages, {
+                self.capture_stage("resolve_images", hooks, &mut stages, {
                     let req = request.clone();
                     async move { stages::resolve_images(&req).await }
                 })
                 .await?
             }
         } else {
-            self.capture_stage("resolve_images", &mut stages, {
+            self.capture_stage("resolve_images", hooks, &mut stages, {
                 let req = request.clone();
                 async move { stages::resolve_images(&req).await }
             })
             .await?
         };
 
-        let seed = compute_seed(&request, &images);
+        let seed = compute_seed(request, &images);
 
         let selection = if let Some(ov) = &request.overrides {
             if let Some(sel) = ov.category.clone() {
-                self.capture_stage("select_category", &mut stages, {
+                self.capture_stage("select_category", hooks, &mut stages, {
                     let images = images.clone();
                     let selection = CategorySelection {
                         id: sel.id,
@@ -220,7 +412,7 @@ impl Pipeline {
                 })
                 .await?
             } else {
-                self.capture_stage("select_category", &mut stages, {
+                self.capture_stage("select_category", hooks, &mut stages, {
                     let req = request.clone();
                     let images = images.clone();
                     let categories = self.config.categories;
@@ -229,7 +421,7 @@ impl Pipeline {
                 .await?
             }
         } else {
-            self.capture_stage("select_category", &mut stages, {
+            self.capture_stage("select_category", hooks, &mut stages, {
                 let req = request.clone();
                 let images = images.clone();
                 let categories = self.config.categories;
@@ -239,20 +431,20 @@ impl Pipeline {
         };
 
         let taxonomy = self
-            .capture_stage("fetch_taxonomy", &mut stages, {
+            .capture_stage("fetch_taxonomy", hooks, &mut stages, {
                 let selection = selection.clone();
                 async move { stages::fetch_taxonomy(&selection).await }
             })
             .await?;
 
         let token = self
-            .capture_stage("acquire_user_token", &mut stages, async move {
+            .capture_stage("acquire_user_token", hooks, &mut stages, async move {
                 stages::acquire_user_token().await
             })
             .await?;
 
         let conditions = self
-            .capture_stage("prepare_conditions", &mut stages, {
+            .capture_stage("prepare_conditions", hooks, &mut stages, {
                 let selection = selection.clone();
                 async move { stages::prepare_conditions(&selection).await }
             })
@@ -262,7 +454,7 @@ impl Pipeline {
         let llm_for_extract = llm.clone();
         let product = if let Some(ov) = &request.overrides {
             if let Some(value) = ov.product.clone() {
-                self.capture_stage("extract_product", &mut stages, {
+                self.capture_stage("extract_product", hooks, &mut stages, {
                     let images = images.clone();
                     async move {
                         match serde_json::from_value::<HsufProduct>(value) {
@@ -286,7 +478,7 @@ impl Pipeline {
                 .await?
             } else {
                 self
-                    .capture_stage("extract_product", &mut stages, {
+                    .capture_stage("extract_product", hooks, &mut stages, {
                         let req = request.clone();
                         let images = images.clone();
                         async move { stages::extract_product(&req, &images, seed, &llm_for_extract).await }
@@ -294,17 +486,17 @@ impl Pipeline {
                     .await?
             }
         } else {
-            self.capture_stage("extract_product", &mut stages, {
+            self.capture_stage("extract_product", hooks, &mut stages, {
                 let req = request.clone();
                 let images = images.clone();
                 async move { stages::extract_product(&req, &images, seed, &llm_for_extract).await }
             })
             .await?
         };
-        let ebay_runtime = resolve_ebay_config(&request, org_config.as_ref())?;
+        let ebay_runtime = resolve_ebay_config(request, org_config)?;
         let llm_for_build = llm.clone();
         let listing = self
-            .capture_stage("build_listing", &mut stages, {
+            .capture_stage("build_listing", hooks, &mut stages, {
                 let req = request.clone();
                 let product = product.clone();
                 let taxonomy = taxonomy.clone();
@@ -324,38 +516,62 @@ impl Pipeline {
             })
             .await?;
 
-        if request.dry_run {
-            return Ok(ListingResponse {
-                listing_id: format!("PREVIEW-{}", Uuid::new_v4().simple()),
-                stages,
-            });
-        }
+        Ok(PreparedListing {
+            listing,
+            selection,
+            token,
+            stages,
+        })
+    }
 
-        let ebay_token = if self.ebay_network_enabled {
-            Some(self.fetch_ebay_token().await?)
+    async fn publish_with(
+        &self,
+        request: &Arc<ListingRequest>,
+        auth: Option<&AuthContext>,
+        org_config: Option<&EbayOrgConfig>,
+        prepared: PreparedListing,
+        hooks: &dyn RunHooks,
+    ) -> Result<ListingResponse, PipelineError> {
+        let PreparedListing {
+            listing,
+            selection,
+            token,
+            mut stages,
+        } = prepared;
+        let ebay_runtime = resolve_ebay_config(request, org_config)?;
+        let ebay_refresh = if self.ebay_network_enabled {
+            Some(self.ebay_refresh_token_for(auth, org_config)?)
         } else {
             None
         };
+        let ebay_token = match &ebay_refresh {
+            Some(refresh) => Some(self.fetch_ebay_token(refresh).await?),
+            None => None,
+        };
 
         let inventory_token = ebay_token.clone();
         let location_cfg = ebay_runtime.location.clone();
-        self.capture_stage("push_inventory", &mut stages, {
-            let req = request.clone();
-            let listing = listing.clone();
-            async move {
-                stages::push_inventory(
-                    &req,
-                    &listing,
-                    inventory_token.as_deref(),
-                    location_cfg.clone(),
-                )
-                .await
-            }
-        })
-        .await?;
+        let pushed = self
+            .capture_stage("push_inventory", hooks, &mut stages, {
+                let req = request.clone();
+                let listing = listing.clone();
+                async move {
+                    stages::push_inventory(
+                        &req,
+                        &listing,
+                        inventory_token.as_deref(),
+                        location_cfg.clone(),
+                    )
+                    .await
+                }
+            })
+            .await;
+        self.forget_rejected_token(ebay_refresh.as_deref(), &pushed)
+            .await;
+        pushed?;
 
-        let offer = self
-            .capture_stage("publish_offer", &mut stages, {
+        let published = self
+            .capture_stage("publish_offer", hooks, &mut stages, {
                 let req = request.clone();
                 let listing = listing.clone();
                 let token = token.clone();
@@ -372,7 +588,14 @@ impl Pipeline {
                     .await
                 }
             })
-            .await?;
+            .await;
+        self.forget_rejected_token(ebay_refresh.as_deref(), &published)
+            .await;
+        if ebay_token.is_some() {
+            self.audit_publish(request, auth, &published, stages.last())
+                .await;
+        }
+        let offer = published?;
 
         Ok(ListingResponse {
             listing_id: offer.listing_id.clone(),
@@ -380,25 +603,92 @@ impl Pipeline {
         })
     }
 
+    /// Record a live `publish_offer` call, made directly or by a job.
+    async fn audit_publish(
+        &self,
+        request: &ListingRequest,
+        auth: Option<&AuthContext>,
+        published: &Result<OfferResult, PipelineError>,
+        report: Option<&StageReport>,
+    ) {
+        let (Some(audit), Some(ctx)) = (&self.audit, auth) else {
+            return;
+        };
+        let output = report
+            .filter(|report| report.name == "publish_offer")
+            .map(|report| &report.output);
+        let reconciled =
+            published.is_ok() && output.is_some_and(|output| output["reconciled"] == json!(true));
+        let action = if reconciled {
+            AuditAction::ReconcileOffer
+        } else {
+            AuditAction::PublishOffer
+        };
+        let event = AuditEvent::new(ctx, action, "publish_offer", request);
+        let event = match published {
+            Ok(offer) => event.with_ids(
+                output
+                    .and_then(|output| output["offer_id"].as_str())
+                    .map(str::to_string),
+                Some(offer.listing_id.clone()),
+            ),
+            Err(err) => event.failed(err),
+        };
+        audit.record(event).await;
+    }
+
     async fn capture_stage<T, Fut>(
         &self,
         name: &'static str,
+        hooks: &dyn RunHooks,
         stages: &mut Vec<StageReport>,
         fut: Fut,
     ) -> Result<T, PipelineError>
     where
         Fut: Future<Output = Result<StageOutcome<T>, PipelineError>>,
     {
+        hooks.before_stage(name).await?;
         let started = Instant::now();
-        let outcome = fut.await?;
+        let (result, calls) = executor::stage_scope(fut).await;
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                // Put what eBay said in the transcript; the run ends here.
+                if let Some(ebay) = err.ebay_error() {
+                    let mut output = json!({ "failed": true, "error": err.detail(), "ebay": ebay });
+                    with_ebay_calls(&mut output, calls);
+                    hooks.after_stage(&StageReport::new(
+                        name,
+                        started.elapsed().as_millis(),
+                        output,
+                    ));
+                }
+                return Err(err);
+            }
+        };
         let elapsed_ms = started.elapsed().as_millis();
         // Lightweight metrics: stage elapsed (trace-based)
         crate::metrics::stage_elapsed(name, elapsed_ms);
-        stages.push(StageReport::new(name, elapsed_ms, outcome.output));
+        let mut output = outcome.output;
+        with_ebay_calls(&mut output, calls);
+        let report = StageReport::new(name, elapsed_ms, output);
+        hooks.after_stage(&report);
+        stages.push(report);
         Ok(outcome.value)
     }
 }
 
+/// Add `ebay_calls` (calls made, requests sent including retries) to a
+/// stage's output when the stage talked to eBay.
+fn with_ebay_calls(output: &mut Value, calls: StageCalls) {
+    if calls.calls == 0 {
+        return;
+    }
+    if let Value::Object(map) = output {
+        map.insert("ebay_calls".into(), json!(calls));
+    }
+}
+
 #[derive(Clone)]
 pub struct PipelineConfig {
     pub categories: &'static [CategoryDefinition],
@@ -459,23 +749,24 @@ const CATEGORY_POOL: [CategoryDefinition; 5] = [
     },
 ];
 
-const EBAY_USER_SCOPES: &[&str] = &[
-    "https://api.ebay.com/oauth/api_scope/sell.inventory",
-    "https://api.ebay.com/oauth/api_scope/sell.account",
-];
-
 #[derive(Debug, Error)]
 #[error("stage `{stage}` failed: {message}")]
 pub struct PipelineError {
     stage: &'static str,
     message: String,
     kind: PipelineErrorKind,
+    ebay: Option<Box<EbayApiError>>,
+    usage: Option<Box<UsageError>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PipelineErrorKind {
     InvalidInput,
     Internal,
+    /// A [`RunHooks`] implementation stopped the run before `stage`.
+    Cancelled,
+    /// The org's quota had no room left for the run.
+    QuotaExceeded,
 }
 
 impl PipelineError {
@@ -484,6 +775,8 @@ impl PipelineError {
             stage,
             message: message.into(),
             kind: PipelineErrorKind::InvalidInput,
+            ebay: None,
+            usage: None,
         }
     }
 
@@ -492,6 +785,50 @@ impl PipelineError {
             stage,
             message: message.into(),
             kind: PipelineErrorKind::Internal,
+            ebay: None,
+            usage: None,
+        }
+    }
+
+    pub fn cancelled(stage: &'static str) -> Self {
+        Self {
+            stage,
+            message: "cancelled".into(),
+            kind: PipelineErrorKind::Cancelled,
+            ebay: None,
+            usage: None,
+        }
+    }
+
+    /// A failed eBay call. eBay rejecting the request as sent is invalid
+    /// input; anything else (auth, rate limits, outages) is internal.
+    pub fn ebay(stage: &'static str, err: EbayApiError) -> Self {
+        let kind = match err.kind {
+            EbayErrorKind::Validation => PipelineErrorKind::InvalidInput,
+            _ => PipelineErrorKind::Internal,
+        };
+        Self {
+            stage,
+            message: err.to_string(),
+            kind,
+            ebay: Some(Box::new(err)),
+            usage: None,
+        }
+    }
+
+    /// Metering refused the run: over quota, or the counters were
+    /// unavailable, which is worth retrying.
+    pub fn usage(err: UsageError) -> Self {
+        let kind = match err {
+            UsageError::QuotaExceeded { .. } => PipelineErrorKind::QuotaExceeded,
+            UsageError::Unavailable(_) => PipelineErrorKind::Internal,
+        };
+        Self {
+            stage: "usage",
+            message: err.to_string(),
+            kind,
+            ebay: None,
+            usage: Some(Box::new(err)),
         }
     }
 
@@ -506,6 +843,41 @@ impl PipelineError {
     pub fn detail(&self) -> &str {
         &self.message
     }
+
+    /// The eBay error behind this failure, if an eBay call caused it.
+    pub fn ebay_error(&self) -> Option<&EbayApiError> {
+        self.ebay.as_deref()
+    }
+
+    /// Why metering refused the run, if it did.
+    pub fn usage_error(&self) -> Option<&UsageError> {
+        self.usage.as_deref()
+    }
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Callbacks a caller can attach to [`Pipeline::run_with_hooks`].
+pub trait RunHooks: Send + Sync {
+    /// Runs before `stage` starts; returning an error aborts the pipeline
+    /// there, so nothing from that stage onwards executes.
+    fn before_stage<'a>(&'a self, stage: &'static str) -> BoxFuture<'a, Result<(), PipelineError>>;
+
+    /// Runs as soon as a stage's report is recorded, before the next stage.
+    /// A stage failed by an eBay call also reports, with `failed: true` and
+    /// the eBay error as its output.
+    fn after_stage(&self, _report: &StageReport) {}
+}
+
+pub(crate) struct NoHooks;
+
+impl RunHooks for NoHooks {
+    fn before_stage<'a>(
+        &'a self,
+        _stage: &'static str,
+    ) -> BoxFuture<'a, Result<(), PipelineError>> {
+        Box::pin(async { Ok(()) })
+    }
 }
 
 #[derive(Debug)]
@@ -700,9 +1072,46 @@ mod tests {
         );
         assert!(resp.listing_id.starts_with("PREVIEW-"));
     }
+
+    #[derive(Default)]
+    struct RecordingHooks(std::sync::Mutex<Vec<String>>);
+
+    impl RunHooks for RecordingHooks {
+        fn before_stage<'a>(
+            &'a self,
+            _stage: &'static str,
+        ) -> BoxFuture<'a, Result<(), PipelineError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn after_stage(&self, report: &StageReport) {
+            self.0.lock().unwrap().push(report.name.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn hooks_see_every_recorded_stage() {
+        let hooks = RecordingHooks::default();
+        let resp = Pipeline::demo()
+            .run_with_hooks(sample_request(), None, &hooks)
+            .await
+            .expect("pipeline run");
+        let names: Vec<String> = resp.stages.iter().map(|s| s.name.clone()).collect();
+        assert_eq!(*hooks.0.lock().unwrap(), names);
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// A listing built but not yet pushed to eBay, with the stage reports that
+/// produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreparedListing {
+    pub listing: ListingPlan,
+    pub selection: CategorySelection,
+    pub token: DemoCredentials,
+    pub stages: Vec<StageReport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CategorySelection {
     pub id: String,
     pub tree_id: String,
@@ -727,7 +1136,7 @@ pub struct TaxonomyAspect {
     pub samples: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DemoCredentials {
     pub token: String,
     pub expires_in: u64,
@@ -739,7 +1148,7 @@ pub struct ConditionBundle {
     pub default_condition: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListingPlan {
     pub sku: String,
     pub title: String,
@@ -1160,6 +1569,7 @@ pub mod stages {
     ) -> Result<StageOutcome<InventoryReceipt>, PipelineError> {
         short_pause(15).await;
         let inventory_request = inventory_request_from_listing(listing);
+        let mut location_error = None;
         if let Some(token) = access_token {
             if let Some(location) = location_cfg.clone() {
                 let location_payload = InventoryLocationRequest {
@@ -1182,24 +1592,25 @@ pub mod stages {
                     },
                 };
                 if !location_payload.location.address.address_line1.is_empty()
-                    && upsert_inventory_location(
+                    && let Err(err) = upsert_inventory_location(
                         &listing.merchant_location_key,
                         &location_payload,
                         token,
                     )
                     .await
-                    .is_err()
                 {
                     warn!(
                         target = "hermes.ebay",
                         location = %listing.merchant_location_key,
+                        error = %err,
                         "inventory_location_upsert_failed"
                     );
+                    location_error = Some(err);
                 }
             }
             upsert_inventory_item(&request.sku, &inventory_request, token)
                 .await
-                .map_err(|err| PipelineError::internal("push_inventory", err.to_string()))?;
+                .map_err(|err| PipelineError::ebay("push_inventory", err))?;
         }
         let receipt = InventoryReceipt {
             sku: listing.sku.clone(),
@@ -1216,6 +1627,7 @@ pub mod stages {
                 "status": receipt.status,
                 "media_attached": listing.media.len(),
                 "inventory_request": inventory_request,
+                "location_error": location_error,
             }),
         ))
     }
@@ -1233,12 +1645,13 @@ pub mod stages {
         let (create_offer, update_offer) = build_offer_requests(listing);
         let create_offer_json = json!(&create_offer);
         let update_offer_json = json!(&update_offer);
+        let mut reconciled = false;
         let (listing_id, offer_id) = if let Some(user_token) = access_token {
             match offers::create_offer(&create_offer, user_token).await {
                 Ok(new_offer_id) => {
                     let published = offers::publish_offer(&new_offer_id, user_token)
                         .await
-                        .map_err(|err| PipelineError::internal("publish_offer", err.to_string()))?;
+                        .map_err(offer_error)?;
                     let final_listing_id = if published.is_empty() {
                         fallback_listing_id()
                     } else {
@@ -1247,9 +1660,10 @@ pub mod stages {
                     (final_listing_id, Some(new_offer_id))
                 }
                 Err(offers::EbayOfferError::EntityExists) => {
+                    reconciled = true;
                     reconcile_existing_offer(&create_offer, &update_offer, user_token).await?
                 }
-                Err(err) => return Err(PipelineError::internal("publish_offer", err.to_string())),
+                Err(err) => return Err(offer_error(err)),
             }
         } else {
             (fallback_listing_id(), None)
@@ -1273,6 +1687,7 @@ pub mod stages {
                 "create_offer": create_offer_json,
                 "update_offer": update_offer_json,
                 "offer_id": offer_id,
+                "reconciled": reconciled,
             }),
         ))
     }
@@ -1452,7 +1867,7 @@ async fn reconcile_existing_offer(
 ) -> Result<(String, Option<String>), PipelineError> {
     let offers = offers::get_offers_by_sku(&create_req.sku, access_token)
         .await
-        .map_err(|err| PipelineError::internal("publish_offer", err.to_string()))?;
+        .map_err(offer_error)?;
     let candidate = offers
         .iter()
         .find(|offer| offer.marketplaceId.as_deref() == Some(&create_req.marketplace_id))
@@ -1469,18 +1884,14 @@ async fn reconcile_existing_offer(
         warn!(target = "hermes.ebay", offer_id = %candidate, error = %err, "offer_update_failed_withdraw_retry");
         offers::withdraw_offer(&candidate, access_token)
             .await
-            .map_err(|withdraw_err| {
-                PipelineError::internal("publish_offer", withdraw_err.to_string())
-            })?;
+            .map_err(offer_error)?;
         offers::update_offer(&candidate, update_req, access_token)
             .await
-            .map_err(|update_err| {
-                PipelineError::internal("publish_offer", update_err.to_string())
-            })?;
+            .map_err(offer_error)?;
     }
     let listing_id = offers::publish_offer(&candidate, access_token)
         .await
-        .map_err(|err| PipelineError::internal("publish_offer", err.to_string()))?;
+        .map_err(offer_error)?;
     let final_listing_id = if listing_id.is_empty() {
         fallback_listing_id()
     } else {
@@ -1489,6 +1900,13 @@ async fn reconcile_existing_offer(
     Ok((final_listing_id, Some(candidate)))
 }
 
+fn offer_error(err: offers::EbayOfferError) -> PipelineError {
+    match err {
+        offers::EbayOfferError::Api(err) => PipelineError::ebay("publish_offer", err),
+        other => PipelineError::internal("publish_offer", other.to_string()),
+    }
+}
+
 fn parse_env_bool(key: &str) -> bool {
     match env::var(key) {
         Ok(value) => matches!(
@@ -0,0 +1,645 @@
+use axum::http::{self, Method, header::HeaderValue};
+use once_cell::sync::Lazy;
+use redis::aio::{ConnectionLike, MultiplexedConnection};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// How long to stay on the local buckets after Redis fails before trying it
+/// again, so an outage costs one timeout rather than one per request.
+const REDIS_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Which bucket a route draws from. Each org has one bucket per class, so
+/// polling job status never eats into the budget for running pipelines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateClass {
+    /// Routes that run LLM calls or the eBay pipeline.
+    Expensive,
+    Cheap,
+}
+
+impl RateClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateClass::Expensive => "expensive",
+            RateClass::Cheap => "cheap",
+        }
+    }
+}
+
+/// The bucket a route draws from and how many tokens one call costs.
+/// Unlisted routes cost one cheap token.
+pub fn route_cost(method: &Method, route: &str) -> (RateClass, f64) {
+    match (method.as_str(), route) {
+        ("POST", "/listings" | "/listings/stream" | "/jobs/listings" | "/jobs/{id}/retry") => {
+            (RateClass::Expensive, 1.0)
+        }
+        // Resumes after review, so only the remaining stages run.
+        ("POST", "/listings/continue" | "/jobs/listings/continue") => (RateClass::Expensive, 0.5),
+        ("POST", "/stages/extract_product" | "/stages/select_category" | "/stages/description") => {
+            (RateClass::Expensive, 0.5)
+        }
+        _ => (RateClass::Cheap, 1.0),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+struct BucketLimit {
+    rate_per_sec: f64,
+    capacity: f64,
+}
+
+impl BucketLimit {
+    fn is_valid(&self) -> bool {
+        self.rate_per_sec > 0.0 && self.capacity >= 1.0
+    }
+}
+
+/// Limits for each bucket class, assigned to orgs by name.
+#[derive(Clone, Copy, Debug, Deserialize)]
+struct RatePlan {
+    expensive: BucketLimit,
+    cheap: BucketLimit,
+}
+
+impl RatePlan {
+    fn limit(&self, class: RateClass) -> BucketLimit {
+        match class {
+            RateClass::Expensive => self.expensive,
+            RateClass::Cheap => self.cheap,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TokenBuckets {
+    default_plan: RatePlan,
+    plans: HashMap<String, RatePlan>,
+    store: BucketStore,
+}
+
+impl TokenBuckets {
+    /// The default plan comes from `RATE_LIMIT_PER_SEC`/`RATE_LIMIT_CAPACITY`
+    /// (expensive bucket) and `RATE_LIMIT_CHEAP_PER_SEC`/
+    /// `RATE_LIMIT_CHEAP_CAPACITY` (cheap bucket). `RATE_LIMIT_PLANS` adds
+    /// named plans as JSON, e.g.
+    /// `{"pro":{"expensive":{"rate_per_sec":2,"capacity":20},"cheap":{"rate_per_sec":50,"capacity":100}}}`;
+    /// a plan named `default` replaces the built-in one.
+    ///
+    /// Buckets live in Redis when `REDIS_URL` is set, so every replica draws
+    /// from the same budget; `RATE_LIMIT_BACKEND=memory` keeps them local.
+    pub fn from_env(redis: Option<redis::Client>) -> Self {
+        let limit = |rate_var: &str, capacity_var: &str, rate: f64, capacity: f64| BucketLimit {
+            rate_per_sec: env::var(rate_var)
+                .ok()
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|value| *value > 0.0)
+                .unwrap_or(rate),
+            capacity: env::var(capacity_var)
+                .ok()
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|value| *value >= 1.0)
+                .unwrap_or(capacity),
+        };
+        let default_plan = RatePlan {
+            expensive: limit("RATE_LIMIT_PER_SEC", "RATE_LIMIT_CAPACITY", 5.0, 10.0),
+            cheap: limit(
+                "RATE_LIMIT_CHEAP_PER_SEC",
+                "RATE_LIMIT_CHEAP_CAPACITY",
+                20.0,
+                40.0,
+            ),
+        };
+        let plans = match env::var("RATE_LIMIT_PLANS") {
+            Ok(raw) if !raw.trim().is_empty() => {
+                serde_json::from_str::<HashMap<String, RatePlan>>(&raw).unwrap_or_else(|err| {
+                    warn!(target = "hermes.api", error = %err, "rate_limit_plans_invalid");
+                    HashMap::new()
+                })
+            }
+            _ => HashMap::new(),
+        };
+        let backend = env::var("RATE_LIMIT_BACKEND")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let limiter = Self::new(default_plan, plans);
+        let limiter = match redis {
+            Some(client) if backend != "memory" => {
+                let timeout = env::var("RATE_LIMIT_REDIS_TIMEOUT_MS")
+                    .ok()
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .filter(|value| *value > 0)
+                    .unwrap_or(100);
+                limiter.with_redis(client, Duration::from_millis(timeout))
+            }
+            _ => limiter,
+        };
+        info!(
+            target = "hermes.api",
+            backend = limiter.store.backend_name(),
+            "rate limiter configured"
+        );
+        limiter
+    }
+
+    fn new(default_plan: RatePlan, mut plans: HashMap<String, RatePlan>) -> Self {
+        plans.retain(|name, plan| {
+            let valid = plan.expensive.is_valid() && plan.cheap.is_valid();
+            if !valid {
+                warn!(target = "hermes.api", plan = %name, "rate_limit_plan_invalid");
+            }
+            valid
+        });
+        let default_plan = plans.remove("default").unwrap_or(default_plan);
+        Self {
+            default_plan,
+            plans,
+            store: BucketStore::Memory(MemoryBuckets::default()),
+        }
+    }
+
+    fn with_redis(self, client: redis::Client, timeout: Duration) -> Self {
+        Self {
+            store: BucketStore::Redis(Arc::new(RedisBuckets {
+                client,
+                timeout,
+                connection: Mutex::new(None),
+                retry_at: std::sync::Mutex::new(None),
+                fallback: MemoryBuckets::default(),
+            })),
+            ..self
+        }
+    }
+
+    /// A local limiter with the same capacity for both classes.
+    #[cfg(test)]
+    pub fn for_tests(capacity: f64) -> Self {
+        let limit = BucketLimit {
+            rate_per_sec: capacity,
+            capacity,
+        };
+        let plan = RatePlan {
+            expensive: limit,
+            cheap: limit,
+        };
+        Self::new(plan, HashMap::new())
+    }
+
+    fn plan(&self, name: Option<&str>) -> RatePlan {
+        match name {
+            None => self.default_plan,
+            Some(name) => self.plans.get(name).copied().unwrap_or_else(|| {
+                warn!(target = "hermes.api", plan = %name, "rate_limit_plan_unknown");
+                self.default_plan
+            }),
+        }
+    }
+
+    pub async fn consume(
+        &self,
+        org_id: &str,
+        plan: Option<&str>,
+        class: RateClass,
+        cost: f64,
+    ) -> Result<RatePermit, RateExceeded> {
+        let limit = self.plan(plan).limit(class);
+        // A call can never cost more than a full bucket.
+        let cost = cost.min(limit.capacity);
+        let key = format!("{org_id}:{}", class.as_str());
+        let Taken { allowed, tokens } = self.store.take(&key, limit, cost).await;
+        if allowed {
+            Ok(RatePermit {
+                class,
+                capacity: limit.capacity,
+                tokens,
+                rate: limit.rate_per_sec,
+            })
+        } else {
+            let deficit = cost - tokens;
+            Err(RateExceeded {
+                class,
+                retry_after: (deficit / limit.rate_per_sec).max(0.0),
+                capacity: limit.capacity,
+                tokens,
+                rate: limit.rate_per_sec,
+            })
+        }
+    }
+}
+
+/// Outcome of drawing from a bucket: whether the call fits, and the tokens
+/// left afterwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Taken {
+    allowed: bool,
+    tokens: f64,
+}
+
+#[derive(Clone)]
+enum BucketStore {
+    Memory(MemoryBuckets),
+    Redis(Arc<RedisBuckets>),
+}
+
+impl BucketStore {
+    fn backend_name(&self) -> &'static str {
+        match self {
+            Self::Memory(_) => "memory",
+            Self::Redis(_) => "redis",
+        }
+    }
+
+    async fn take(&self, key: &str, limit: BucketLimit, cost: f64) -> Taken {
+        match self {
+            Self::Memory(buckets) => buckets.take(key, limit, cost).await,
+            Self::Redis(redis) => match redis.take(key, limit, cost).await {
+                Some(taken) => taken,
+                None => redis.fallback.take(key, limit, cost).await,
+            },
+        }
+    }
+}
+
+/// Per-process buckets: the only store without Redis, and the fallback
+/// while Redis is unreachable.
+#[derive(Clone, Default)]
+struct MemoryBuckets {
+    buckets: Arc<Mutex<HashMap<String, BucketState>>>,
+}
+
+impl MemoryBuckets {
+    async fn take(&self, key: &str, limit: BucketLimit, cost: f64) -> Taken {
+        let mut guard = self.buckets.lock().await;
+        let now = Instant::now();
+        let state = guard.entry(key.to_string()).or_insert_with(|| BucketState {
+            tokens: limit.capacity,
+            last_refill: now,
+        });
+
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        // `min` also clamps buckets left over from a larger plan.
+        state.tokens = (state.tokens + elapsed * limit.rate_per_sec).min(limit.capacity);
+        state.last_refill = now;
+
+        let allowed = state.tokens >= cost;
+        if allowed {
+            state.tokens -= cost;
+        }
+        Taken {
+            allowed,
+            tokens: state.tokens,
+        }
+    }
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Buckets shared by every replica, kept as Redis hashes under
+/// `hermes:ratelimit:{org_id}:{class}`.
+struct RedisBuckets {
+    client: redis::Client,
+    timeout: Duration,
+    connection: Mutex<Option<MultiplexedConnection>>,
+    /// While set, skip Redis until this instant and use `fallback`.
+    retry_at: std::sync::Mutex<Option<Instant>>,
+    fallback: MemoryBuckets,
+}
+
+impl RedisBuckets {
+    /// `None` when Redis is unavailable or too slow.
+    async fn take(&self, key: &str, limit: BucketLimit, cost: f64) -> Option<Taken> {
+        if let Some(retry_at) = *self.retry_at.lock().expect("rate limit lock")
+            && Instant::now() < retry_at
+        {
+            return None;
+        }
+        let attempt = async {
+            let mut conn = self.connection().await?;
+            redis_take(&mut conn, &format!("hermes:ratelimit:{key}"), limit, cost).await
+        };
+        let error = match tokio::time::timeout(self.timeout, attempt).await {
+            Ok(Ok(taken)) => {
+                *self.retry_at.lock().expect("rate limit lock") = None;
+                return Some(taken);
+            }
+            Ok(Err(err)) => err.to_string(),
+            Err(_) => "timed out".to_string(),
+        };
+        warn!(target = "hermes.api", error = %error, "rate_limit_store_unavailable");
+        *self.connection.lock().await = None;
+        *self.retry_at.lock().expect("rate limit lock") = Some(Instant::now() + REDIS_RETRY_AFTER);
+        None
+    }
+
+    async fn connection(&self) -> redis::RedisResult<MultiplexedConnection> {
+        let mut cached = self.connection.lock().await;
+        if let Some(conn) = cached.as_ref() {
+            return Ok(conn.clone());
+        }
+        let conn = self.client.get_multiplexed_async_connection().await?;
+        *cached = Some(conn.clone());
+        Ok(conn)
+    }
+}
+
+/// Refill and draw from the bucket in one step, on Redis' clock so replicas
+/// with skewed clocks agree. Returns `{allowed, tokens}`; tokens go back as a
+/// string because Redis truncates Lua numbers to integers.
+static TAKE_SCRIPT: Lazy<redis::Script> = Lazy::new(|| {
+    redis::Script::new(
+        r#"
+local rate = tonumber(ARGV[1])
+local capacity = tonumber(ARGV[2])
+local cost = tonumber(ARGV[3])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) / 1000 * rate)
+local allowed = 0
+if tokens >= cost then
+  tokens = tokens - cost
+  allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', tostring(now))
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate * 1000) + 1000)
+return {allowed, tostring(tokens)}
+"#,
+    )
+});
+
+async fn redis_take(
+    conn: &mut impl ConnectionLike,
+    key: &str,
+    limit: BucketLimit,
+    cost: f64,
+) -> redis::RedisResult<Taken> {
+    let (allowed, tokens): (i64, String) = TAKE_SCRIPT
+        .key(key)
+        .arg(limit.rate_per_sec)
+        .arg(limit.capacity)
+        .arg(cost)
+        .invoke_async(conn)
+        .await?;
+    Ok(Taken {
+        allowed: allowed == 1,
+        tokens: tokens.parse().unwrap_or(0.0),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct RatePermit {
+    class: RateClass,
+    capacity: f64,
+    tokens: f64,
+    rate: f64,
+}
+
+impl RatePermit {
+    pub fn apply_headers(&self, headers: &mut http::HeaderMap) {
+        let remaining = self.tokens.max(0.0).floor() as u64;
+        let reset = ((self.capacity - self.tokens) / self.rate).ceil().max(0.0) as u64;
+        headers.insert(
+            "X-RateLimit-Bucket",
+            HeaderValue::from_static(self.class.as_str()),
+        );
+        headers.insert(
+            "X-RateLimit-Limit",
+            HeaderValue::from_str(&(self.capacity as u64).to_string())
+                .unwrap_or_else(|_| HeaderValue::from_static("0")),
+        );
+        headers.insert(
+            "X-RateLimit-Remaining",
+            HeaderValue::from_str(&remaining.to_string())
+                .unwrap_or_else(|_| HeaderValue::from_static("0")),
+        );
+        headers.insert(
+            "X-RateLimit-Reset",
+            HeaderValue::from_str(&reset.to_string())
+                .unwrap_or_else(|_| HeaderValue::from_static("0")),
+        );
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateExceeded {
+    class: RateClass,
+    retry_after: f64,
+    capacity: f64,
+    tokens: f64,
+    rate: f64,
+}
+
+impl RateExceeded {
+    pub fn apply_headers(&self, headers: &mut http::HeaderMap) {
+        let retry = self.retry_after.ceil().max(0.0) as u64;
+        headers.insert(
+            http::header::RETRY_AFTER,
+            HeaderValue::from_str(&retry.to_string())
+                .unwrap_or_else(|_| HeaderValue::from_static("1")),
+        );
+        headers.insert(
+            "X-RateLimit-Bucket",
+            HeaderValue::from_static(self.class.as_str()),
+        );
+        headers.insert(
+            "X-RateLimit-Limit",
+            HeaderValue::from_str(&(self.capacity as u64).to_string())
+                .unwrap_or_else(|_| HeaderValue::from_static("0")),
+        );
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from_static("0"));
+        let reset = ((self.capacity - self.tokens) / self.rate).ceil().max(0.0) as u64;
+        headers.insert(
+            "X-RateLimit-Reset",
+            HeaderValue::from_str(&reset.to_string())
+                .unwrap_or_else(|_| HeaderValue::from_static("0")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis_stand_in::RedisStandIn;
+
+    fn plan(expensive: f64, cheap: f64) -> RatePlan {
+        RatePlan {
+            expensive: BucketLimit {
+                rate_per_sec: 0.001,
+                capacity: expensive,
+            },
+            cheap: BucketLimit {
+                rate_per_sec: 0.001,
+                capacity: cheap,
+            },
+        }
+    }
+
+    /// Hashes and a `TIME` the test moves by hand, enough for
+    /// [`TAKE_SCRIPT`].
+    const COMMANDS: &str = r#"
+        clock_ms = 1700000000000
+        store = {}
+        redis = { call = function(cmd, key, ...)
+          local args = {...}
+          if cmd == 'TIME' then
+            local ms = clock_ms
+            return { tostring(math.floor(ms / 1000)), tostring((ms % 1000) * 1000) }
+          end
+          local hash = store[key] or {}
+          store[key] = hash
+          if cmd == 'HMGET' then
+            local out = {}
+            for i, field in ipairs(args) do out[i] = hash[field] or false end
+            return out
+          elseif cmd == 'HSET' then
+            for i = 1, #args, 2 do hash[args[i]] = tostring(args[i + 1]) end
+            return #args / 2
+          elseif cmd == 'PEXPIRE' then
+            return 1
+          end
+          error('unsupported command ' .. cmd)
+        end }
+    "#;
+
+    fn advance(redis: &RedisStandIn, ms: u64) {
+        let globals = redis.lua.globals();
+        let clock: f64 = globals.get("clock_ms").unwrap();
+        globals.set("clock_ms", clock + ms as f64).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cheap_routes_do_not_drain_the_expensive_bucket() {
+        let limiter = TokenBuckets::new(plan(2.0, 5.0), HashMap::new());
+        for _ in 0..5 {
+            let permit = limiter
+                .consume("acme", None, RateClass::Cheap, 1.0)
+                .await
+                .unwrap();
+            assert_eq!(permit.class, RateClass::Cheap);
+        }
+        assert!(
+            limiter
+                .consume("acme", None, RateClass::Cheap, 1.0)
+                .await
+                .is_err()
+        );
+
+        let (class, cost) = route_cost(&Method::POST, "/stages/description");
+        assert_eq!(class, RateClass::Expensive);
+        for _ in 0..4 {
+            assert!(limiter.consume("acme", None, class, cost).await.is_ok());
+        }
+        let exceeded = limiter
+            .consume("acme", None, RateClass::Expensive, 1.0)
+            .await
+            .unwrap_err();
+        let mut headers = http::HeaderMap::new();
+        exceeded.apply_headers(&mut headers);
+        assert_eq!(headers["X-RateLimit-Bucket"], "expensive");
+        assert_eq!(headers["X-RateLimit-Limit"], "2");
+        assert!(headers.contains_key(http::header::RETRY_AFTER));
+    }
+
+    #[tokio::test]
+    async fn orgs_get_their_plan() {
+        let plans = HashMap::from([
+            ("pro".to_string(), plan(3.0, 3.0)),
+            ("broken".to_string(), plan(0.0, 3.0)),
+        ]);
+        let limiter = TokenBuckets::new(plan(1.0, 1.0), plans);
+        assert!(!limiter.plans.contains_key("broken"));
+
+        let permit = limiter
+            .consume("acme", Some("pro"), RateClass::Expensive, 1.0)
+            .await
+            .unwrap();
+        assert_eq!(permit.capacity, 3.0);
+        // Unknown plans fall back to the default, and buckets are per org.
+        let permit = limiter
+            .consume("globex", Some("gold"), RateClass::Expensive, 1.0)
+            .await
+            .unwrap();
+        assert_eq!(permit.capacity, 1.0);
+        assert!(
+            limiter
+                .consume("globex", None, RateClass::Expensive, 1.0)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn redis_buckets_refill_on_the_server_clock() {
+        let mut redis = RedisStandIn::new(COMMANDS);
+        let limit = BucketLimit {
+            rate_per_sec: 2.0,
+            capacity: 3.0,
+        };
+        let key = "hermes:ratelimit:acme:expensive";
+
+        // Every replica runs the same script against the same hash.
+        for expected in [2.0, 1.0, 0.0] {
+            let taken = redis_take(&mut redis, key, limit, 1.0).await.unwrap();
+            assert_eq!(
+                taken,
+                Taken {
+                    allowed: true,
+                    tokens: expected
+                }
+            );
+        }
+        let denied = redis_take(&mut redis, key, limit, 1.0).await.unwrap();
+        assert!(!denied.allowed);
+
+        advance(&redis, 250);
+        let taken = redis_take(&mut redis, key, limit, 0.5).await.unwrap();
+        assert_eq!(
+            taken,
+            Taken {
+                allowed: true,
+                tokens: 0.0
+            }
+        );
+
+        advance(&redis, 10_000);
+        let taken = redis_take(&mut redis, key, limit, 1.0).await.unwrap();
+        assert_eq!(taken.tokens, 2.0, "refill stops at capacity");
+    }
+
+    #[tokio::test]
+    async fn unreachable_redis_falls_back_to_local_buckets() {
+        let client = redis::Client::open("redis://127.0.0.1:1").unwrap();
+        let limiter = TokenBuckets::new(plan(2.0, 2.0), HashMap::new())
+            .with_redis(client, Duration::from_millis(200));
+        for _ in 0..2 {
+            assert!(
+                limiter
+                    .consume("acme", None, RateClass::Cheap, 1.0)
+                    .await
+                    .is_ok()
+            );
+        }
+        assert!(
+            limiter
+                .consume("acme", None, RateClass::Cheap, 1.0)
+                .await
+                .is_err()
+        );
+        let BucketStore::Redis(redis) = &limiter.store else {
+            panic!("expected the redis store");
+        };
+        assert!(redis.retry_at.lock().unwrap().is_some());
+    }
+}
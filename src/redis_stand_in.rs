@@ -0,0 +1,117 @@
+use redis::{Cmd, ErrorKind, RedisError, RedisFuture, Value, aio::ConnectionLike};
+use std::collections::HashMap;
+
+/// Runs against the interpreter after the next plain command.
+type Hook = Box<dyn FnOnce(&mlua::Lua)>;
+
+/// Enough of Redis to run our Lua scripts in tests: `SCRIPT LOAD`/`EVALSHA`
+/// on a Lua 5.1 interpreter (Redis' own dialect). Each test passes the Lua
+/// that defines `redis.call` for the commands its scripts use; plain
+/// commands sent outside a script go through that same table.
+pub struct RedisStandIn {
+    pub lua: mlua::Lua,
+    scripts: HashMap<String, String>,
+    on_next_command: Option<Hook>,
+}
+
+impl RedisStandIn {
+    pub fn new(commands: &str) -> Self {
+        let lua = mlua::Lua::new();
+        lua.load(commands).exec().unwrap();
+        Self {
+            lua,
+            scripts: HashMap::new(),
+            on_next_command: None,
+        }
+    }
+
+    /// Run `f` right after the next plain command, as if another replica
+    /// wrote between our read and our write.
+    pub fn on_next_command(&mut self, f: impl FnOnce(&mlua::Lua) + 'static) {
+        self.on_next_command = Some(Box::new(f));
+    }
+
+    fn run(&mut self, cmd: &Cmd) -> redis::RedisResult<Value> {
+        let args: Vec<String> = cmd
+            .args_iter()
+            .map(|arg| match arg {
+                redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                redis::Arg::Cursor => String::new(),
+            })
+            .collect();
+        match args[0].to_uppercase().as_str() {
+            "SCRIPT" => {
+                let hash = redis::Script::new(&args[2]).get_hash().to_string();
+                self.scripts.insert(hash.clone(), args[2].clone());
+                Ok(Value::BulkString(hash.into_bytes()))
+            }
+            "EVALSHA" => {
+                let Some(source) = self.scripts.get(&args[1]) else {
+                    return Err(RedisError::from((ErrorKind::NoScriptError, "NOSCRIPT")));
+                };
+                let key_count: usize = args[2].parse().unwrap();
+                let globals = self.lua.globals();
+                globals
+                    .set("KEYS", args[3..3 + key_count].to_vec())
+                    .unwrap();
+                globals.set("ARGV", args[3 + key_count..].to_vec()).unwrap();
+                let result: mlua::Value = self.lua.load(source.as_str()).eval().unwrap();
+                Ok(to_redis(result))
+            }
+            command => {
+                let redis: mlua::Table = self.lua.globals().get("redis").unwrap();
+                let call: mlua::Function = redis.get("call").unwrap();
+                let mut call_args = vec![command.to_string()];
+                call_args.extend(args[1..].iter().cloned());
+                let result: mlua::Value = call.call(mlua::Variadic::from_iter(call_args)).unwrap();
+                let result = to_redis(result);
+                if let Some(f) = self.on_next_command.take() {
+                    f(&self.lua);
+                }
+                Ok(result)
+            }
+        }
+    }
+}
+
+/// Lua to RESP the way Redis converts script results.
+fn to_redis(value: mlua::Value) -> Value {
+    match value {
+        mlua::Value::Integer(n) => Value::Int(n),
+        mlua::Value::Number(n) => Value::Int(n as i64),
+        mlua::Value::String(s) => Value::BulkString(s.as_bytes().to_vec()),
+        mlua::Value::Boolean(true) => Value::Int(1),
+        mlua::Value::Table(table) => Value::Array(
+            table
+                .sequence_values::<mlua::Value>()
+                .map(|item| to_redis(item.unwrap()))
+                .collect(),
+        ),
+        _ => Value::Nil,
+    }
+}
+
+impl ConnectionLike for RedisStandIn {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let result = self.run(cmd);
+        Box::pin(async move { result })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        _: &'a redis::Pipeline,
+        _: usize,
+        _: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async {
+            Err(RedisError::from((
+                ErrorKind::ClientError,
+                "pipelining not supported by stand-in",
+            )))
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
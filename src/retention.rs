@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+/// How long finished entries are kept, and how many may be held in memory.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub ttl: Duration,
+    pub max_entries: usize,
+}
+
+impl RetentionPolicy {
+    /// `JOB_RETENTION_SECS` (default one day) and `JOB_MAX_FINISHED`
+    /// (default `10000`) for completed, failed and cancelled jobs.
+    pub fn jobs_from_env() -> Self {
+        Self::from_env("JOB_RETENTION_SECS", 86_400, "JOB_MAX_FINISHED", 10_000)
+    }
+
+    /// `IDEMPOTENCY_TTL_SECS` (default `3600`) and `IDEMPOTENCY_MAX_ENTRIES`
+    /// (default `10000`) for cached listing responses.
+    pub fn idempotency_from_env() -> Self {
+        Self::from_env(
+            "IDEMPOTENCY_TTL_SECS",
+            3_600,
+            "IDEMPOTENCY_MAX_ENTRIES",
+            10_000,
+        )
+    }
+
+    fn from_env(ttl_var: &str, default_ttl: u64, max_var: &str, default_max: usize) -> Self {
+        let ttl = std::env::var(ttl_var)
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(default_ttl);
+        let max_entries = std::env::var(max_var)
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(default_max);
+        Self {
+            ttl: Duration::from_secs(ttl),
+            max_entries,
+        }
+    }
+}
+
+/// How often background sweepers enforce retention
+/// (`RETENTION_SWEEP_INTERVAL_SECS`, default `60`).
+pub fn sweep_interval_from_env() -> Duration {
+    let secs = std::env::var("RETENTION_SWEEP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(60);
+    Duration::from_secs(secs)
+}
+
+/// Entries removed by one sweep, by reason.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Evicted {
+    pub expired: usize,
+    pub over_capacity: usize,
+}
+
+impl Evicted {
+    /// Report non-empty sweeps for `store` to metrics.
+    pub fn record(&self, store: &'static str) {
+        if self.expired > 0 {
+            crate::metrics::evicted(store, "expired", self.expired);
+        }
+        if self.over_capacity > 0 {
+            crate::metrics::evicted(store, "over_capacity", self.over_capacity);
+        }
+    }
+}
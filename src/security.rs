@@ -1,75 +1,305 @@
-use crate::models::ApiError;
+use crate::{
+    keys::{KeyRejection, KeyStore, VerifiedKey},
+    models::ApiError,
+    ratelimit::{RateClass, RateExceeded, RatePermit, TokenBuckets, route_cost},
+    signing::{RequestSigning, SignedHeaders, string_to_sign},
+};
 use axum::{
-    Json,
-    body::Body,
-    extract::State,
-    http::{self, Request, StatusCode, header::HeaderValue},
+    Json, RequestExt,
+    body::{Body, to_bytes},
+    extract::{MatchedPath, OriginalUri, State},
+    http::{self, HeaderValue, Method, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
-use serde::Serialize;
-use std::{collections::HashMap, convert::Infallible, env, sync::Arc, time::Instant};
-use tokio::sync::Mutex;
-use tracing::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, sync::Arc};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
 
 #[derive(Clone)]
 pub struct AuthState {
-    records: Arc<HashMap<String, OrgRecord>>,
+    keys: KeyStore,
     limiter: Arc<TokenBuckets>,
+    signing: Arc<RequestSigning>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AuthContext {
     pub org_id: String,
     pub api_key_id: String,
+    /// What the key may do; see [`required_scopes`] for the route mapping.
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    /// The org's plan, which picks its rate limits and usage quotas.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+    /// The `X-Request-Id` of the request that authenticated, kept with jobs
+    /// so their audit events point back at the submission.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
-#[derive(Clone)]
-struct OrgRecord {
-    org_id: String,
-    api_key_id: String,
+impl From<VerifiedKey> for AuthContext {
+    fn from(key: VerifiedKey) -> Self {
+        Self {
+            org_id: key.org_id,
+            api_key_id: key.key_id,
+            scopes: key.scopes,
+            plan: key.rate_plan,
+            request_id: None,
+        }
+    }
+}
+
+impl AuthContext {
+    pub fn require(&self, scope: Scope) -> Result<(), InsufficientScope> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(InsufficientScope(scope.as_str()))
+        }
+    }
+}
+
+/// A permission granted to an API key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    /// Run the pipeline through `publish_offer`, inline or as a job.
+    #[serde(rename = "listings:write")]
+    ListingsWrite,
+    /// Run the pipeline with `dry_run: true` only.
+    #[serde(rename = "listings:dry_run")]
+    ListingsDryRun,
+    #[serde(rename = "jobs:read")]
+    JobsRead,
+    /// Cancel or retry jobs.
+    #[serde(rename = "jobs:write")]
+    JobsWrite,
+    /// Call the raw `/stages/*` endpoints.
+    #[serde(rename = "stages:invoke")]
+    StagesInvoke,
+    /// Connect the org's eBay seller account, which decides where every
+    /// listing is published.
+    #[serde(rename = "accounts:admin")]
+    AccountsAdmin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 6] = [
+        Scope::ListingsWrite,
+        Scope::ListingsDryRun,
+        Scope::JobsRead,
+        Scope::JobsWrite,
+        Scope::StagesInvoke,
+        Scope::AccountsAdmin,
+    ];
+
+    /// What keys get when no scopes are given: everything but the admin
+    /// scopes, which have to be granted explicitly.
+    pub const DEFAULT: [Scope; 5] = [
+        Scope::ListingsWrite,
+        Scope::ListingsDryRun,
+        Scope::JobsRead,
+        Scope::JobsWrite,
+        Scope::StagesInvoke,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ListingsWrite => "listings:write",
+            Scope::ListingsDryRun => "listings:dry_run",
+            Scope::JobsRead => "jobs:read",
+            Scope::JobsWrite => "jobs:write",
+            Scope::StagesInvoke => "stages:invoke",
+            Scope::AccountsAdmin => "accounts:admin",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == raw.trim())
+    }
+}
+
+/// Scopes that admit a request to a route; holding any one of them is enough.
+/// Listing routes also take `listings:dry_run`, and the handler then demands
+/// `listings:write` unless the request is a dry run. Routes missing here are
+/// refused.
+pub fn required_scopes(method: &Method, route: &str) -> &'static [Scope] {
+    const LISTINGS: &[Scope] = &[Scope::ListingsWrite, Scope::ListingsDryRun];
+    match (method.as_str(), route) {
+        (
+            "POST",
+            "/listings"
+            | "/listings/continue"
+            | "/listings/stream"
+            | "/jobs/listings"
+            | "/jobs/listings/continue",
+        ) => LISTINGS,
+        (
+            "GET",
+            "/jobs" | "/jobs/" | "/jobs/{id}" | "/jobs/{id}/deliveries" | "/jobs/{id}/events",
+        ) => &[Scope::JobsRead],
+        ("GET", "/ebay/connect") => &[Scope::AccountsAdmin],
+        // Any valid key may see its org's consumption and audit trail.
+        ("GET", "/usage" | "/audit") => &Scope::ALL,
+        ("DELETE", "/jobs/{id}") | ("POST", "/jobs/{id}/cancel" | "/jobs/{id}/retry") => {
+            &[Scope::JobsWrite]
+        }
+        ("POST", route) if route.starts_with("/stages/") => &[Scope::StagesInvoke],
+        _ => &[],
+    }
+}
+
+/// The key lacks the scope named in the error detail.
+#[derive(Debug)]
+pub struct InsufficientScope(pub &'static str);
+
+impl IntoResponse for InsufficientScope {
+    fn into_response(self) -> Response {
+        let payload = ApiError {
+            error: "insufficient_scope".to_string(),
+            detail: Some(self.0.to_string()),
+        };
+        (StatusCode::FORBIDDEN, Json(payload)).into_response()
+    }
 }
 
 impl AuthState {
-    pub fn from_env() -> Self {
-        let records = Arc::new(load_keys_from_env());
-        let limiter = Arc::new(TokenBuckets::from_env());
-        Self { records, limiter }
+    pub async fn from_env(redis: Option<redis::Client>) -> Self {
+        let keys = KeyStore::from_env().await;
+        let limiter = Arc::new(TokenBuckets::from_env(redis.clone()));
+        let signing = Arc::new(RequestSigning::from_env(redis));
+        Self {
+            keys,
+            limiter,
+            signing,
+        }
     }
 
-    fn authenticate(&self, presented: &str) -> Option<AuthContext> {
-        self.records.get(presented).map(|record| AuthContext {
-            org_id: record.org_id.clone(),
-            api_key_id: record.api_key_id.clone(),
-        })
+    pub fn keys(&self) -> &KeyStore {
+        &self.keys
+    }
+
+    fn authenticate(&self, presented: &str) -> Result<AuthContext, KeyRejection> {
+        self.keys.verify(presented).map(VerifiedKey::into)
+    }
+
+    /// Verify a signed request, returning it with its body restored. The
+    /// nonce is only claimed once the signature checks out, so forged
+    /// requests cannot burn a client's nonces.
+    async fn authenticate_signed(
+        &self,
+        signed: SignedHeaders,
+        request: Request<Body>,
+    ) -> Result<(AuthContext, Request<Body>), Response> {
+        self.signing
+            .check_timestamp(signed.timestamp)
+            .map_err(|rejection| unauthorized_response(rejection.code(), rejection.message()))?;
+
+        // Buffer the body (within `DefaultBodyLimit`) so it can be hashed
+        // and still handed to the handler.
+        let (parts, body) = request.with_limited_body().into_parts();
+        let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+            let payload = ApiError {
+                error: "request".to_string(),
+                detail: Some("body_too_large".to_string()),
+            };
+            return Err((StatusCode::PAYLOAD_TOO_LARGE, Json(payload)).into_response());
+        };
+        // Nested routers strip their prefix; sign what the client sent.
+        let uri = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map_or(&parts.uri, |uri| &uri.0);
+        let path = uri
+            .path_and_query()
+            .map_or(uri.path(), |path| path.as_str());
+        let message = string_to_sign(&parts.method, path, signed.timestamp, &signed.nonce, &bytes);
+
+        let context: AuthContext = self
+            .keys
+            .verify_signed(&signed.key_id, message.as_bytes(), &signed.signature)
+            .map_err(key_rejected)?
+            .into();
+        self.signing
+            .claim_nonce(&signed.key_id, &signed.nonce)
+            .await
+            .map_err(|rejection| unauthorized_response(rejection.code(), rejection.message()))?;
+        Ok((context, Request::from_parts(parts, Body::from(bytes))))
     }
 
-    async fn consume(&self, org_id: &str) -> Result<RatePermit, RateExceeded> {
-        self.limiter.consume(org_id).await
+    async fn consume(
+        &self,
+        org_id: &str,
+        plan: Option<&str>,
+        class: RateClass,
+        cost: f64,
+    ) -> Result<RatePermit, RateExceeded> {
+        self.limiter.consume(org_id, plan, class, cost).await
     }
 }
 
 pub async fn require_api_auth(
     State(state): State<AuthState>,
-    mut request: Request<Body>,
+    request: Request<Body>,
     next: Next,
 ) -> Result<Response, Infallible> {
-    let Some(presented) = extract_api_key(request.headers()) else {
-        let response =
-            unauthorized_response("missing_api_key", "Provide X-Hermes-Key or Bearer token");
-        return Ok(response);
+    let (context, mut request) = match SignedHeaders::from_headers(request.headers()) {
+        Some(Ok(signed)) => match state.authenticate_signed(signed, request).await {
+            Ok(authenticated) => authenticated,
+            Err(response) => return Ok(response),
+        },
+        Some(Err(rejection)) => {
+            return Ok(unauthorized_response(rejection.code(), rejection.message()));
+        }
+        None => {
+            let Some(presented) = extract_api_key(request.headers()) else {
+                let response = unauthorized_response(
+                    "missing_api_key",
+                    "Provide X-Hermes-Key, a Bearer token or a signed request",
+                );
+                return Ok(response);
+            };
+            match state.authenticate(&presented) {
+                Ok(context) => (context, request),
+                Err(rejection) => return Ok(key_rejected(rejection)),
+            }
+        }
     };
 
-    let Some(context) = state.authenticate(&presented) else {
-        let response = unauthorized_response("invalid_api_key", "Key not recognized");
-        return Ok(response);
+    let request_id = request_id(request.headers());
+    let context = AuthContext {
+        request_id: Some(request_id.clone()),
+        ..context
     };
 
-    match state.consume(&context.org_id).await {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    let allowed = required_scopes(request.method(), route);
+    if !allowed.iter().any(|scope| context.scopes.contains(scope)) {
+        let required = allowed.first().map_or("route_not_scoped", Scope::as_str);
+        return Ok(InsufficientScope(required).into_response());
+    }
+
+    let (class, cost) = route_cost(request.method(), route);
+    match state
+        .consume(&context.org_id, context.plan.as_deref(), class, cost)
+        .await
+    {
         Ok(permit) => {
             request.extensions_mut().insert(context.clone());
             let mut response = next.run(request).await;
             permit.apply_headers(response.headers_mut());
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
             Ok(response)
         }
         Err(exceeded) => {
@@ -80,6 +310,22 @@ pub async fn require_api_auth(
     }
 }
 
+/// The caller's `X-Request-Id` when it is a sensible token, else a new id.
+fn request_id(headers: &http::HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= 128
+                && id
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || "-_.:".contains(ch))
+        })
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_string)
+}
+
 fn extract_api_key(headers: &http::HeaderMap) -> Option<String> {
     if let Some(value) = headers.get(http::header::AUTHORIZATION)
         && let Ok(raw) = value.to_str()
@@ -95,6 +341,17 @@ fn extract_api_key(headers: &http::HeaderMap) -> Option<String> {
         .filter(|value| !value.is_empty())
 }
 
+fn key_rejected(rejection: KeyRejection) -> Response {
+    let message = match rejection {
+        KeyRejection::Unknown => "Key not recognized",
+        KeyRejection::Disabled => "Key has been revoked",
+        KeyRejection::Expired => "Key has expired",
+        KeyRejection::BadSignature => "Signature does not match the request",
+        KeyRejection::SigningDisabled => "Signed requests are not enabled on this server",
+    };
+    unauthorized_response(rejection.code(), message)
+}
+
 fn unauthorized_response(code: &str, message: &str) -> Response {
     let payload = ApiError {
         error: code.to_string(),
@@ -111,176 +368,205 @@ fn too_many_requests(code: &str, message: &str) -> Response {
     (StatusCode::TOO_MANY_REQUESTS, Json(payload)).into_response()
 }
 
-fn load_keys_from_env() -> HashMap<String, OrgRecord> {
-    let raw = env::var("DEMO_API_KEYS").unwrap_or_else(|_| "demo-org:demo-key".to_string());
-    let mut entries = HashMap::new();
-    for (idx, token) in raw.split(',').enumerate() {
-        let trimmed = token.trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-        let mut parts = trimmed.splitn(2, ':');
-        let org_id = parts.next().map(str::trim).filter(|s| !s.is_empty());
-        let key = parts.next().map(str::trim).filter(|s| !s.is_empty());
-        match (org_id, key) {
-            (Some(org), Some(secret)) => {
-                let record = OrgRecord {
-                    org_id: org.to_string(),
-                    api_key_id: format!("key-{:02}", idx + 1),
-                };
-                entries.insert(secret.to_string(), record);
-            }
-            _ => warn!(
-                target = "hermes.api",
-                "ignored malformed DEMO_API_KEYS entry: {trimmed}"
-            ),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router, middleware,
+        routing::{get, post},
+    };
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn keys() -> KeyStore {
+        KeyStore::for_tests(&[
+            ("acme", "full", "sk_full", &Scope::ALL),
+            ("acme", "reader", "sk_reader", &[Scope::JobsRead]),
+            ("acme", "preview", "sk_preview", &[Scope::ListingsDryRun]),
+            ("acme", "publisher", "sk_publisher", &[Scope::ListingsWrite]),
+        ])
     }
 
-    if entries.is_empty() {
-        warn!(
-            target = "hermes.api",
-            "DEMO_API_KEYS produced no keys; falling back to demo credentials"
-        );
-        entries.insert(
-            "demo-key".to_string(),
-            OrgRecord {
-                org_id: "demo-org".to_string(),
-                api_key_id: "key-01".to_string(),
-            },
+    fn app() -> Router {
+        app_with(keys())
+    }
+
+    fn app_with(keys: KeyStore) -> Router {
+        let state = AuthState {
+            keys,
+            limiter: Arc::new(TokenBuckets::for_tests(100.0)),
+            signing: Arc::new(RequestSigning::for_tests(Duration::from_secs(300))),
+        };
+        Router::new()
+            .route("/listings", post(|body: String| async move { body }))
+            .nest(
+                "/jobs",
+                Router::new().route("/{id}", get(|| async { "ok" }).delete(|| async { "ok" })),
+            )
+            .route("/stages/description", post(|| async { "ok" }))
+            .route("/ebay/connect", get(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(state, require_api_auth))
+    }
+
+    async fn call(method: Method, path: &str, key: &str) -> (StatusCode, String) {
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(path)
+                    .header("X-Hermes-Key", key)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn routes_require_their_scope() {
+        assert_eq!(
+            call(Method::GET, "/jobs/1", "sk_reader").await.0,
+            StatusCode::OK
         );
-    } else {
-        info!(
-            target = "hermes.api",
-            key_count = entries.len(),
-            "loaded API keys from env"
+        assert_eq!(
+            call(Method::DELETE, "/jobs/1", "sk_full").await.0,
+            StatusCode::OK
         );
-    }
 
-    entries
-}
+        let (status, body) = call(Method::DELETE, "/jobs/1", "sk_reader").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("insufficient_scope") && body.contains("jobs:write"));
 
-#[derive(Clone)]
-struct TokenBuckets {
-    rate_per_sec: f64,
-    capacity: f64,
-    buckets: Arc<Mutex<HashMap<String, BucketState>>>,
-}
+        let (status, body) = call(Method::POST, "/stages/description", "sk_preview").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("stages:invoke"));
 
-impl TokenBuckets {
-    fn from_env() -> Self {
-        let rate_per_sec = env::var("RATE_LIMIT_PER_SEC")
-            .ok()
-            .and_then(|value| value.parse::<f64>().ok())
-            .filter(|value| *value > 0.0)
-            .unwrap_or(5.0);
-        let capacity = env::var("RATE_LIMIT_CAPACITY")
-            .ok()
-            .and_then(|value| value.parse::<f64>().ok())
-            .filter(|value| *value >= 1.0)
-            .unwrap_or(10.0);
-        Self {
-            rate_per_sec,
-            capacity,
-            buckets: Arc::new(Mutex::new(HashMap::new())),
-        }
+        // Publishing keys cannot re-point the org's eBay account.
+        let (status, body) = call(Method::GET, "/ebay/connect", "sk_publisher").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("accounts:admin"));
+        assert_eq!(
+            call(Method::GET, "/ebay/connect", "sk_full").await.0,
+            StatusCode::OK
+        );
+
+        // Dry-run keys reach the listing handler, which checks `dry_run` itself.
+        assert_eq!(
+            call(Method::POST, "/listings", "sk_preview").await.0,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call(Method::POST, "/listings", "sk_reader").await.0,
+            StatusCode::FORBIDDEN
+        );
     }
 
-    async fn consume(&self, key: &str) -> Result<RatePermit, RateExceeded> {
-        let mut guard = self.buckets.lock().await;
-        let now = Instant::now();
-        let state = guard.entry(key.to_string()).or_insert_with(|| BucketState {
-            tokens: self.capacity,
-            last_refill: now,
-        });
-
-        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
-        if elapsed > 0.0 {
-            state.tokens = (state.tokens + elapsed * self.rate_per_sec).min(self.capacity);
-            state.last_refill = now;
-        }
+    /// A request signed the way a client would, with the key's signing secret.
+    fn signed(
+        keys: &KeyStore,
+        key_id: &str,
+        path: &str,
+        body: &str,
+        timestamp: i64,
+        nonce: &str,
+    ) -> Request<Body> {
+        let secret = hex::decode(keys.signing_secret(key_id).unwrap()).unwrap();
+        let message = string_to_sign(&Method::POST, path, timestamp, nonce, body.as_bytes());
+        let mut mac = Hmac::<Sha256>::new_from_slice(&secret).unwrap();
+        mac.update(message.as_bytes());
+        Request::builder()
+            .method(Method::POST)
+            .uri(path)
+            .header("X-Hermes-Key-Id", key_id)
+            .header("X-Hermes-Timestamp", timestamp.to_string())
+            .header("X-Hermes-Nonce", nonce)
+            .header(
+                "X-Hermes-Signature",
+                format!("v1={}", hex::encode(mac.finalize().into_bytes())),
+            )
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
 
-        if state.tokens >= 1.0 {
-            state.tokens -= 1.0;
-            Ok(RatePermit {
-                capacity: self.capacity,
-                tokens: state.tokens,
-                rate: self.rate_per_sec,
-            })
-        } else {
-            let deficit = 1.0 - state.tokens;
-            let retry_after = (deficit / self.rate_per_sec).max(0.0);
-            Err(RateExceeded {
-                retry_after,
-                capacity: self.capacity,
-                tokens: state.tokens,
-                rate: self.rate_per_sec,
-            })
-        }
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, String) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
     }
-}
 
-struct BucketState {
-    tokens: f64,
-    last_refill: Instant,
-}
+    #[tokio::test]
+    async fn signed_requests_authenticate_once() {
+        let keys = keys();
+        let app = app_with(keys.clone());
+        let now = chrono::Utc::now().timestamp();
 
-#[derive(Debug, Clone, Serialize)]
-pub struct RatePermit {
-    capacity: f64,
-    tokens: f64,
-    rate: f64,
-}
+        let request = signed(&keys, "full", "/listings", r#"{"sku":"A1"}"#, now, "n-1");
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"sku":"A1"}"#, "the handler still sees the body");
 
-impl RatePermit {
-    fn apply_headers(&self, headers: &mut http::HeaderMap) {
-        let remaining = self.tokens.max(0.0).floor() as u64;
-        let reset = ((self.capacity - self.tokens) / self.rate).ceil().max(0.0) as u64;
-        headers.insert(
-            "X-RateLimit-Limit",
-            HeaderValue::from_str(&(self.capacity as u64).to_string())
-                .unwrap_or_else(|_| HeaderValue::from_static("0")),
-        );
-        headers.insert(
-            "X-RateLimit-Remaining",
-            HeaderValue::from_str(&remaining.to_string())
-                .unwrap_or_else(|_| HeaderValue::from_static("0")),
-        );
-        headers.insert(
-            "X-RateLimit-Reset",
-            HeaderValue::from_str(&reset.to_string())
-                .unwrap_or_else(|_| HeaderValue::from_static("0")),
-        );
+        let replay = signed(&keys, "full", "/listings", r#"{"sku":"A1"}"#, now, "n-1");
+        let (status, body) = send(&app, replay).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("nonce_reused"));
+
+        let stale = signed(&keys, "full", "/listings", "{}", now - 600, "n-2");
+        let (status, body) = send(&app, stale).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("signature_expired"));
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct RateExceeded {
-    retry_after: f64,
-    capacity: f64,
-    tokens: f64,
-    rate: f64,
-}
+    #[tokio::test]
+    async fn tampered_signed_requests_are_rejected() {
+        let keys = keys();
+        let app = app_with(keys.clone());
+        let now = chrono::Utc::now().timestamp();
 
-impl RateExceeded {
-    fn apply_headers(&self, headers: &mut http::HeaderMap) {
-        let retry = self.retry_after.ceil().max(0.0) as u64;
-        headers.insert(
-            http::header::RETRY_AFTER,
-            HeaderValue::from_str(&retry.to_string())
-                .unwrap_or_else(|_| HeaderValue::from_static("1")),
-        );
-        headers.insert(
-            "X-RateLimit-Limit",
-            HeaderValue::from_str(&(self.capacity as u64).to_string())
-                .unwrap_or_else(|_| HeaderValue::from_static("0")),
-        );
-        headers.insert("X-RateLimit-Remaining", HeaderValue::from_static("0"));
-        let reset = ((self.capacity - self.tokens) / self.rate).ceil().max(0.0) as u64;
-        headers.insert(
-            "X-RateLimit-Reset",
-            HeaderValue::from_str(&reset.to_string())
-                .unwrap_or_else(|_| HeaderValue::from_static("0")),
+        let request = signed(&keys, "full", "/listings", r#"{"sku":"A1"}"#, now, "n-1");
+        let (parts, _) = request.into_parts();
+        let tampered = Request::from_parts(parts, Body::from(r#"{"sku":"B2"}"#));
+        let (status, body) = send(&app, tampered).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("invalid_signature"));
+
+        // The failed attempt did not burn the nonce.
+        let request = signed(&keys, "full", "/listings", "{}", now, "n-1");
+        assert_eq!(send(&app, request).await.0, StatusCode::OK);
+
+        let mut unsigned = signed(&keys, "full", "/listings", "{}", now, "n-2");
+        unsigned.headers_mut().remove("X-Hermes-Nonce");
+        let (status, body) = send(&app, unsigned).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("malformed_signature"));
+
+        // Signed requests are still held to the key's scopes.
+        let request = signed(&keys, "reader", "/listings", "{}", now, "n-3");
+        assert_eq!(send(&app, request).await.0, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn dry_run_keys_cannot_publish() {
+        let context = AuthContext {
+            org_id: "acme".into(),
+            api_key_id: "preview".into(),
+            scopes: vec![Scope::ListingsDryRun],
+            plan: None,
+            request_id: None,
+        };
+        assert!(context.require(Scope::ListingsDryRun).is_ok());
+        assert_eq!(
+            context.require(Scope::ListingsWrite).unwrap_err().0,
+            "listings:write"
         );
     }
 }
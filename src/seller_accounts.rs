@@ -0,0 +1,537 @@
+use crate::ebay::auth::{EbayAuthError, consent_url, exchange_authorization_code};
+use crate::ebay::config::USER_SCOPES;
+use crate::models::ApiError;
+use crate::supabase::{EbayOrgConfig, SupabaseClient, SupabaseError};
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD},
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// How long a consent link stays usable.
+const STATE_TTL_SECS: i64 = 600;
+
+/// Prefix of sealed tokens, so the format can change without guessing.
+const SEALED_PREFIX: &str = "v1.";
+
+#[derive(Debug, Error)]
+pub enum SellerAccountError {
+    #[error("ebay_connect_disabled")]
+    Disabled,
+    #[error("invalid_oauth_state")]
+    InvalidState,
+    #[error("ebay_consent_declined")]
+    Declined,
+    /// Supabase holds tokens next to the org's settings, so those come first.
+    #[error("ebay_org_config_missing")]
+    OrgNotConfigured,
+    #[error("refresh_token_unreadable")]
+    Unreadable,
+    #[error(transparent)]
+    Auth(#[from] EbayAuthError),
+    #[error("seller account store: {0}")]
+    Store(#[from] SupabaseError),
+}
+
+impl IntoResponse for SellerAccountError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            SellerAccountError::InvalidState | SellerAccountError::Declined => {
+                StatusCode::BAD_REQUEST
+            }
+            SellerAccountError::OrgNotConfigured => StatusCode::CONFLICT,
+            SellerAccountError::Auth(EbayAuthError::Api(_)) => StatusCode::BAD_GATEWAY,
+            SellerAccountError::Disabled
+            | SellerAccountError::Auth(_)
+            | SellerAccountError::Store(_) => StatusCode::SERVICE_UNAVAILABLE,
+            SellerAccountError::Unreadable => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let payload = ApiError {
+            error: "ebay_account".to_string(),
+            detail: Some(self.to_string()),
+        };
+        (status, Json(payload)).into_response()
+    }
+}
+
+/// AES-256-GCM for refresh tokens at rest, plus the HMAC key that signs
+/// OAuth `state`, derived from the same secret.
+pub struct TokenCipher {
+    key: Key<Aes256Gcm>,
+    state_key: [u8; 32],
+}
+
+impl TokenCipher {
+    /// `secret` is 32 bytes as 64 hex digits.
+    pub fn from_hex(secret: &str) -> Option<Self> {
+        let bytes = hex::decode(secret.trim()).ok().filter(|b| b.len() == 32)?;
+        let mut state_key = Sha256::new();
+        state_key.update(b"hermes-ebay-oauth-state\n");
+        state_key.update(&bytes);
+        Some(Self {
+            key: *Key::<Aes256Gcm>::from_slice(&bytes),
+            state_key: state_key.finalize().into(),
+        })
+    }
+
+    /// Seal `token` for `org_id`; the org is authenticated as associated
+    /// data, so a sealed token copied to another org's row will not open.
+    pub fn seal(&self, org_id: &str, token: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = Aes256Gcm::new(&self.key)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: token.as_bytes(),
+                    aad: org_id.as_bytes(),
+                },
+            )
+            .expect("AES-GCM encryption does not fail for in-memory buffers");
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&sealed);
+        format!("{SEALED_PREFIX}{}", BASE64.encode(out))
+    }
+
+    pub fn open(&self, org_id: &str, sealed: &str) -> Result<String, SellerAccountError> {
+        let raw = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .and_then(|body| BASE64.decode(body).ok())
+            .filter(|raw| raw.len() > 12)
+            .ok_or(SellerAccountError::Unreadable)?;
+        let (nonce, ciphertext) = raw.split_at(12);
+        let plain = Aes256Gcm::new(&self.key)
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: org_id.as_bytes(),
+                },
+            )
+            .map_err(|_| SellerAccountError::Unreadable)?;
+        String::from_utf8(plain).map_err(|_| SellerAccountError::Unreadable)
+    }
+
+    /// `state` for a consent link: the org, an expiry and `nonce`, signed so
+    /// the unauthenticated callback can trust which org it is for.
+    pub fn sign_state(&self, org_id: &str, nonce: &str, now: DateTime<Utc>) -> String {
+        let payload = format!(
+            "{}.{}.{nonce}",
+            URL_SAFE_NO_PAD.encode(org_id),
+            now.timestamp() + STATE_TTL_SECS,
+        );
+        format!("{payload}.{}", hex::encode(self.state_mac(&payload)))
+    }
+
+    /// The org and nonce a `state` was signed with, if it is genuine and
+    /// unexpired.
+    pub fn verify_state(
+        &self,
+        state: &str,
+        now: DateTime<Utc>,
+    ) -> Result<OAuthState, SellerAccountError> {
+        let (payload, signature) = state
+            .rsplit_once('.')
+            .ok_or(SellerAccountError::InvalidState)?;
+        let signature = hex::decode(signature).map_err(|_| SellerAccountError::InvalidState)?;
+        let mut mac = self.state_hmac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| SellerAccountError::InvalidState)?;
+        let mut parts = payload.split('.');
+        let org_id = parts
+            .next()
+            .and_then(|org| URL_SAFE_NO_PAD.decode(org).ok())
+            .and_then(|org| String::from_utf8(org).ok());
+        let expires = parts.next().and_then(|at| at.parse::<i64>().ok());
+        let nonce = parts.next().filter(|nonce| !nonce.is_empty());
+        match (org_id, expires, nonce) {
+            (Some(org_id), Some(expires), Some(nonce)) if now.timestamp() <= expires => {
+                Ok(OAuthState {
+                    org_id,
+                    nonce: nonce.to_string(),
+                })
+            }
+            _ => Err(SellerAccountError::InvalidState),
+        }
+    }
+
+    fn state_hmac(&self) -> Hmac<Sha256> {
+        <Hmac<Sha256> as Mac>::new_from_slice(&self.state_key).expect("HMAC accepts any key length")
+    }
+
+    fn state_mac(&self, payload: &str) -> Vec<u8> {
+        let mut mac = self.state_hmac();
+        mac.update(payload.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+/// What a genuine `state` carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OAuthState {
+    pub org_id: String,
+    pub nonce: String,
+}
+
+/// Nonces of consent links not used yet: in process, or in Redis under
+/// `hermes:ebay_state:{nonce}` so the callback may land on any replica. If
+/// Redis is unreachable the local set is used instead.
+enum StateNonces {
+    Memory(MemoryStates),
+    Redis {
+        client: redis::Client,
+        fallback: MemoryStates,
+    },
+}
+
+impl StateNonces {
+    async fn issue(&self, nonce: &str) {
+        match self {
+            Self::Memory(states) => states.issue(nonce),
+            Self::Redis { client, fallback } => {
+                let attempt = async {
+                    let mut conn = client.get_multiplexed_async_connection().await?;
+                    redis::cmd("SET")
+                        .arg(format!("hermes:ebay_state:{nonce}"))
+                        .arg(1)
+                        .arg("NX")
+                        .arg("EX")
+                        .arg(STATE_TTL_SECS)
+                        .query_async::<Option<String>>(&mut conn)
+                        .await
+                };
+                if let Err(err) = attempt.await {
+                    warn!(target = "hermes.api", error = %err, "ebay_state_redis_failed");
+                    fallback.issue(nonce);
+                }
+            }
+        }
+    }
+
+    /// `true` the first time an issued, unexpired nonce is used.
+    async fn consume(&self, nonce: &str) -> bool {
+        match self {
+            Self::Memory(states) => states.consume(nonce),
+            Self::Redis { client, fallback } => {
+                let attempt = async {
+                    let mut conn = client.get_multiplexed_async_connection().await?;
+                    redis::cmd("DEL")
+                        .arg(format!("hermes:ebay_state:{nonce}"))
+                        .query_async::<i64>(&mut conn)
+                        .await
+                };
+                match attempt.await {
+                    // It may have been issued while Redis was down.
+                    Ok(deleted) => deleted == 1 || fallback.consume(nonce),
+                    Err(err) => {
+                        warn!(target = "hermes.api", error = %err, "ebay_state_redis_failed");
+                        fallback.consume(nonce)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct MemoryStates {
+    issued: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl MemoryStates {
+    fn issue(&self, nonce: &str) {
+        let now = Utc::now();
+        let mut issued = self.issued.lock().expect("ebay state lock");
+        issued.retain(|_, expires| *expires > now);
+        issued.insert(
+            nonce.to_string(),
+            now + chrono::Duration::seconds(STATE_TTL_SECS),
+        );
+    }
+
+    fn consume(&self, nonce: &str) -> bool {
+        let now = Utc::now();
+        let mut issued = self.issued.lock().expect("ebay state lock");
+        issued.remove(nonce).is_some_and(|expires| expires > now)
+    }
+}
+
+/// A connected seller account, as reported by `/ebay/callback`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectedAccount {
+    pub org_id: String,
+    pub connected_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token_expires_at: Option<DateTime<Utc>>,
+}
+
+/// Where sealed refresh tokens live: the org's `ebay_org_config` row when
+/// Supabase is configured, else process memory.
+enum AccountStore {
+    /// Org id to sealed refresh token.
+    Memory(Mutex<HashMap<String, String>>),
+    Supabase(SupabaseClient),
+}
+
+/// Per-org eBay seller accounts, connected through eBay's consent flow and
+/// kept as refresh tokens sealed by [`TokenCipher`].
+#[derive(Clone)]
+pub struct SellerAccounts {
+    cipher: Arc<TokenCipher>,
+    store: Arc<AccountStore>,
+    states: Arc<StateNonces>,
+}
+
+impl SellerAccounts {
+    /// Enabled by `EBAY_TOKEN_ENCRYPTION_KEY` (64 hex digits); without it
+    /// every org publishes with `EBAY_REFRESH_TOKEN`. Consent link nonces
+    /// are shared through Redis when `REDIS_URL` is set
+    /// (`EBAY_STATE_BACKEND=memory` keeps them per replica).
+    pub fn from_env(redis: Option<redis::Client>) -> Option<Self> {
+        let secret = env::var("EBAY_TOKEN_ENCRYPTION_KEY").ok()?;
+        let Some(cipher) = TokenCipher::from_hex(&secret) else {
+            warn!(
+                target = "hermes.api",
+                "EBAY_TOKEN_ENCRYPTION_KEY must be 64 hex digits; per-org eBay accounts are disabled"
+            );
+            return None;
+        };
+        let store = match SupabaseClient::from_env() {
+            Some(client) => AccountStore::Supabase(client),
+            None => AccountStore::Memory(Mutex::default()),
+        };
+        let backend = env::var("EBAY_STATE_BACKEND")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let states = match redis {
+            Some(client) if backend != "memory" => StateNonces::Redis {
+                client,
+                fallback: MemoryStates::default(),
+            },
+            _ => StateNonces::Memory(MemoryStates::default()),
+        };
+        Some(Self {
+            cipher: Arc::new(cipher),
+            store: Arc::new(store),
+            states: Arc::new(states),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn for_tests(cipher: TokenCipher) -> Self {
+        Self {
+            cipher: Arc::new(cipher),
+            store: Arc::new(AccountStore::Memory(Mutex::default())),
+            states: Arc::new(StateNonces::Memory(MemoryStates::default())),
+        }
+    }
+
+    /// Where to send a seller of `org_id` to grant access.
+    pub async fn connect_url(&self, org_id: &str) -> Result<String, SellerAccountError> {
+        let state = self.issue_state(org_id).await;
+        Ok(consent_url(&state, USER_SCOPES)?)
+    }
+
+    async fn issue_state(&self, org_id: &str) -> String {
+        let nonce = Uuid::new_v4().simple().to_string();
+        self.states.issue(&nonce).await;
+        self.cipher.sign_state(org_id, &nonce, Utc::now())
+    }
+
+    /// The org a callback's `state` is for, using up its nonce so the same
+    /// consent link cannot be completed twice.
+    async fn claim_state(&self, state: &str) -> Result<String, SellerAccountError> {
+        let state = self.cipher.verify_state(state, Utc::now())?;
+        if !self.states.consume(&state.nonce).await {
+            return Err(SellerAccountError::InvalidState);
+        }
+        Ok(state.org_id)
+    }
+
+    /// Finish the consent redirect: check `state`, exchange `code` and keep
+    /// the org's refresh token.
+    pub async fn complete(
+        &self,
+        code: &str,
+        state: &str,
+    ) -> Result<ConnectedAccount, SellerAccountError> {
+        let org_id = self.claim_state(state).await?;
+        let grant = exchange_authorization_code(code).await?;
+        let now = Utc::now();
+        let account = ConnectedAccount {
+            org_id,
+            connected_at: now,
+            refresh_token_expires_at: grant
+                .refresh_token_expires_in
+                .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+                .map(|ttl| now + ttl),
+        };
+        self.save(&account, &grant.refresh_token).await?;
+        info!(target = "hermes.api", org_id = %account.org_id, "ebay_account_connected");
+        Ok(account)
+    }
+
+    async fn save(
+        &self,
+        account: &ConnectedAccount,
+        refresh_token: &str,
+    ) -> Result<(), SellerAccountError> {
+        let ciphertext = self.cipher.seal(&account.org_id, refresh_token);
+        match self.store.as_ref() {
+            AccountStore::Memory(accounts) => {
+                accounts
+                    .lock()
+                    .expect("seller accounts lock")
+                    .insert(account.org_id.clone(), ciphertext);
+                Ok(())
+            }
+            AccountStore::Supabase(client) => {
+                let org_id = Uuid::parse_str(&account.org_id)
+                    .map_err(|_| SellerAccountError::OrgNotConfigured)?;
+                if client.fetch_ebay_org_config(org_id).await?.is_none() {
+                    return Err(SellerAccountError::OrgNotConfigured);
+                }
+                let patch = json!({
+                    "refresh_token_ciphertext": ciphertext,
+                    "refresh_token_expires_at": account.refresh_token_expires_at,
+                    "ebay_connected_at": account.connected_at,
+                });
+                client
+                    .update_rows("ebay_org_config", &format!("org_id=eq.{org_id}"), &patch)
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// The refresh token `org_id` connected, if any. With Supabase it is read
+    /// from the org's already-fetched `org_config`.
+    pub fn refresh_token(
+        &self,
+        org_id: &str,
+        org_config: Option<&EbayOrgConfig>,
+    ) -> Result<Option<String>, SellerAccountError> {
+        let sealed = match self.store.as_ref() {
+            AccountStore::Memory(accounts) => accounts
+                .lock()
+                .expect("seller accounts lock")
+                .get(org_id)
+                .cloned(),
+            AccountStore::Supabase(_) => {
+                org_config.and_then(|config| config.refresh_token_ciphertext.clone())
+            }
+        };
+        sealed
+            .map(|sealed| self.cipher.open(org_id, &sealed))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> TokenCipher {
+        TokenCipher::from_hex(&"ab".repeat(32)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn tokens_are_sealed_per_org() {
+        let cipher = cipher();
+        let sealed = cipher.seal("acme", "v^1.1#refresh");
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("refresh"));
+        assert_eq!(cipher.open("acme", &sealed).unwrap(), "v^1.1#refresh");
+        assert!(cipher.open("venture", &sealed).is_err());
+        assert!(
+            TokenCipher::from_hex(&"cd".repeat(32))
+                .unwrap()
+                .open("acme", &sealed)
+                .is_err()
+        );
+        assert!(TokenCipher::from_hex("abcd").is_none());
+
+        let accounts = SellerAccounts::for_tests(cipher);
+        let account = ConnectedAccount {
+            org_id: "acme".into(),
+            connected_at: Utc::now(),
+            refresh_token_expires_at: None,
+        };
+        assert_eq!(accounts.refresh_token("acme", None).unwrap(), None);
+        accounts.save(&account, "acme-refresh").await.unwrap();
+        assert_eq!(
+            accounts.refresh_token("acme", None).unwrap().as_deref(),
+            Some("acme-refresh")
+        );
+        assert_eq!(accounts.refresh_token("venture", None).unwrap(), None);
+    }
+
+    #[test]
+    fn oauth_state_names_the_org_until_it_expires() {
+        let cipher = cipher();
+        let now = Utc::now();
+        let state = cipher.sign_state("org.with.dots", "n1", now);
+        assert_eq!(
+            cipher.verify_state(&state, now).unwrap(),
+            OAuthState {
+                org_id: "org.with.dots".into(),
+                nonce: "n1".into(),
+            }
+        );
+
+        let later = now + chrono::Duration::seconds(STATE_TTL_SECS + 1);
+        assert!(cipher.verify_state(&state, later).is_err());
+
+        let (payload, signature) = state.rsplit_once('.').unwrap();
+        let forged = payload.replacen(
+            &URL_SAFE_NO_PAD.encode("org.with.dots"),
+            &URL_SAFE_NO_PAD.encode("victim"),
+            1,
+        );
+        assert!(
+            cipher
+                .verify_state(&format!("{forged}.{signature}"), now)
+                .is_err()
+        );
+        assert!(cipher.verify_state("garbage", now).is_err());
+    }
+
+    #[tokio::test]
+    async fn oauth_state_works_once() {
+        let accounts = SellerAccounts::for_tests(cipher());
+        let state = accounts.issue_state("acme").await;
+        assert_eq!(accounts.claim_state(&state).await.unwrap(), "acme");
+        assert!(matches!(
+            accounts.claim_state(&state).await,
+            Err(SellerAccountError::InvalidState)
+        ));
+
+        // A genuine signature is not enough; the nonce must have been issued.
+        let unissued = cipher().sign_state("acme", "n-unissued", Utc::now());
+        assert!(matches!(
+            accounts.claim_state(&unissued).await,
+            Err(SellerAccountError::InvalidState)
+        ));
+    }
+}
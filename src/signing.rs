@@ -0,0 +1,231 @@
+use axum::http::{HeaderMap, Method};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+pub const KEY_ID_HEADER: &str = "X-Hermes-Key-Id";
+pub const TIMESTAMP_HEADER: &str = "X-Hermes-Timestamp";
+pub const NONCE_HEADER: &str = "X-Hermes-Nonce";
+pub const SIGNATURE_HEADER: &str = "X-Hermes-Signature";
+
+/// Nonces longer than this are refused rather than stored.
+const MAX_NONCE_LEN: usize = 128;
+
+/// The signing headers of one request, parsed but not yet verified; the
+/// signature is HMAC-SHA256 of [`string_to_sign`] under the key's secret.
+#[derive(Debug, Clone)]
+pub struct SignedHeaders {
+    pub key_id: String,
+    pub timestamp: i64,
+    pub nonce: String,
+    pub signature: Vec<u8>,
+}
+
+impl SignedHeaders {
+    /// `None` when the request is not signed at all (no key id or signature
+    /// header); an error when it is signed but the headers are incomplete or
+    /// malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Result<Self, SignatureRejection>> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        if header(KEY_ID_HEADER).is_none() && header(SIGNATURE_HEADER).is_none() {
+            return None;
+        }
+        let parsed = (|| {
+            let key_id = header(KEY_ID_HEADER)?.to_string();
+            let timestamp = header(TIMESTAMP_HEADER)?.parse::<i64>().ok()?;
+            let nonce = header(NONCE_HEADER)
+                .filter(|nonce| nonce.len() <= MAX_NONCE_LEN)?
+                .to_string();
+            let signature = hex::decode(header(SIGNATURE_HEADER)?.strip_prefix("v1=")?).ok()?;
+            Some(Self {
+                key_id,
+                timestamp,
+                nonce,
+                signature,
+            })
+        })();
+        Some(parsed.ok_or(SignatureRejection::Malformed))
+    }
+}
+
+/// The canonical request a client signs: version, method, path with query,
+/// timestamp, nonce and the hex SHA-256 of the body, one per line.
+pub fn string_to_sign(
+    method: &Method,
+    path_and_query: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    format!(
+        "v1\n{}\n{}\n{}\n{}\n{}",
+        method.as_str(),
+        path_and_query,
+        timestamp,
+        nonce,
+        hex::encode(Sha256::digest(body))
+    )
+}
+
+/// Why a signed request was refused before its key was checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureRejection {
+    Malformed,
+    Expired,
+    Replayed,
+}
+
+impl SignatureRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SignatureRejection::Malformed => "malformed_signature",
+            SignatureRejection::Expired => "signature_expired",
+            SignatureRejection::Replayed => "nonce_reused",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            SignatureRejection::Malformed => {
+                "Signed requests need X-Hermes-Key-Id, X-Hermes-Timestamp, X-Hermes-Nonce and X-Hermes-Signature: v1=<hex>"
+            }
+            SignatureRejection::Expired => "Timestamp is outside the allowed clock skew",
+            SignatureRejection::Replayed => "Nonce was already used",
+        }
+    }
+}
+
+/// Freshness checks for signed requests, so a captured one cannot be
+/// replayed: the clock-skew window and the nonces seen within it.
+pub struct RequestSigning {
+    max_skew: Duration,
+    nonces: NonceCache,
+}
+
+impl RequestSigning {
+    /// `SIGNED_REQUEST_MAX_SKEW_SECS` (default `300`) bounds how far a
+    /// timestamp may be from the server clock. Nonces are shared through
+    /// Redis when `REDIS_URL` is set (`SIGNED_REQUEST_NONCE_BACKEND=memory`
+    /// keeps them per replica).
+    pub fn from_env(redis: Option<redis::Client>) -> Self {
+        let max_skew = env::var("SIGNED_REQUEST_MAX_SKEW_SECS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(300);
+        let backend = env::var("SIGNED_REQUEST_NONCE_BACKEND")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let nonces = match redis {
+            Some(client) if backend != "memory" => NonceCache::Redis {
+                client,
+                fallback: MemoryNonces::default(),
+            },
+            _ => NonceCache::Memory(MemoryNonces::default()),
+        };
+        Self {
+            max_skew: Duration::from_secs(max_skew),
+            nonces,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn for_tests(max_skew: Duration) -> Self {
+        Self {
+            max_skew,
+            nonces: NonceCache::Memory(MemoryNonces::default()),
+        }
+    }
+
+    /// Whether `timestamp` is within the skew window of the server clock.
+    pub fn check_timestamp(&self, timestamp: i64) -> Result<(), SignatureRejection> {
+        let skew = Utc::now().timestamp().abs_diff(timestamp);
+        if skew > self.max_skew.as_secs() {
+            return Err(SignatureRejection::Expired);
+        }
+        Ok(())
+    }
+
+    /// Record the nonce of a verified request, refusing one already seen.
+    /// A nonce is remembered until its timestamp has left the skew window
+    /// on either side, after which the timestamp check refuses it anyway.
+    pub async fn claim_nonce(&self, key_id: &str, nonce: &str) -> Result<(), SignatureRejection> {
+        if self.nonces.insert(key_id, nonce, self.max_skew * 2).await {
+            Ok(())
+        } else {
+            Err(SignatureRejection::Replayed)
+        }
+    }
+}
+
+/// Seen nonces: in process, or in Redis under
+/// `hermes:nonce:{key_id}:{nonce}` so every replica refuses a replay. If
+/// Redis is unreachable the local cache is used instead.
+enum NonceCache {
+    Memory(MemoryNonces),
+    Redis {
+        client: redis::Client,
+        fallback: MemoryNonces,
+    },
+}
+
+impl NonceCache {
+    /// `true` when the nonce is new.
+    async fn insert(&self, key_id: &str, nonce: &str, ttl: Duration) -> bool {
+        match self {
+            Self::Memory(nonces) => nonces.insert(key_id, nonce, ttl),
+            Self::Redis { client, fallback } => {
+                let attempt = async {
+                    let mut conn = client.get_multiplexed_async_connection().await?;
+                    redis::cmd("SET")
+                        .arg(format!("hermes:nonce:{key_id}:{nonce}"))
+                        .arg(1)
+                        .arg("NX")
+                        .arg("EX")
+                        .arg(ttl.as_secs().max(1))
+                        .query_async::<Option<String>>(&mut conn)
+                        .await
+                };
+                match attempt.await {
+                    Ok(set) => set.is_some(),
+                    Err(err) => {
+                        warn!(target = "hermes.api", error = %err, "nonce_cache_redis_failed");
+                        fallback.insert(key_id, nonce, ttl)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct MemoryNonces {
+    seen: Mutex<HashMap<(String, String), Instant>>,
+}
+
+impl MemoryNonces {
+    fn insert(&self, key_id: &str, nonce: &str, ttl: Duration) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().expect("nonce cache lock");
+        seen.retain(|_, expires| *expires > now);
+        match seen.entry((key_id.to_string(), nonce.to_string())) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(now + ttl);
+                true
+            }
+        }
+    }
+}
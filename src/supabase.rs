@@ -1,6 +1,6 @@
 use crate::http::build_client;
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -37,6 +37,9 @@ pub struct EbayOrgConfig {
     pub country: Option<String>,
     pub latitude: Option<String>,
     pub longitude: Option<String>,
+    /// The org's eBay refresh token, sealed by `seller_accounts::TokenCipher`.
+    #[serde(default)]
+    pub refresh_token_ciphertext: Option<String>,
 }
 
 impl SupabaseClient {
@@ -83,4 +86,121 @@ impl SupabaseClient {
             .map_err(|err| SupabaseError::Deserialize(err.to_string()))?;
         Ok(payload.pop())
     }
+
+    /// Every row of the `api_keys` table (see `keys::ApiKeyRow`).
+    pub async fn fetch_api_keys(&self) -> Result<Vec<crate::keys::ApiKeyRow>, SupabaseError> {
+        let url = format!(
+            "{}/rest/v1/api_keys?select=key_id,org_id,salt,hash,expires_at,disabled,scopes,created_at,last_used_at",
+            self.base_url
+        );
+        let response = self
+            .http
+            .get(url)
+            .header("apikey", &self.service_key)
+            .header("Authorization", format!("Bearer {}", self.service_key))
+            .send()
+            .await
+            .map_err(|err| SupabaseError::Request(err.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(SupabaseError::Request(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|err| SupabaseError::Deserialize(err.to_string()))
+    }
+
+    /// Every row of the `orgs` table (see `keys::OrgRow`).
+    pub async fn fetch_orgs(&self) -> Result<Vec<crate::keys::OrgRow>, SupabaseError> {
+        let url = format!(
+            "{}/rest/v1/orgs?select=org_id,name,rate_plan,created_at",
+            self.base_url
+        );
+        let response = self
+            .authorized(self.http.get(url))
+            .send()
+            .await
+            .map_err(|err| SupabaseError::Request(err.to_string()))?;
+        Self::ensure_success(&response)?;
+        response
+            .json()
+            .await
+            .map_err(|err| SupabaseError::Deserialize(err.to_string()))
+    }
+
+    /// Rows of `table` matching PostgREST query `params` (filters, `order`, `limit`).
+    pub async fn select_rows<T: serde::de::DeserializeOwned>(
+        &self,
+        table: &str,
+        params: &[(&str, String)],
+    ) -> Result<Vec<T>, SupabaseError> {
+        let url = format!("{}/rest/v1/{table}", self.base_url);
+        let response = self
+            .authorized(self.http.get(url))
+            .query(params)
+            .send()
+            .await
+            .map_err(|err| SupabaseError::Request(err.to_string()))?;
+        Self::ensure_success(&response)?;
+        response
+            .json()
+            .await
+            .map_err(|err| SupabaseError::Deserialize(err.to_string()))
+    }
+
+    pub async fn insert_row<T: Serialize>(
+        &self,
+        table: &str,
+        row: &T,
+    ) -> Result<(), SupabaseError> {
+        let url = format!("{}/rest/v1/{table}", self.base_url);
+        let response = self
+            .authorized(self.http.post(url))
+            .header("Prefer", "return=minimal")
+            .json(row)
+            .send()
+            .await
+            .map_err(|err| SupabaseError::Request(err.to_string()))?;
+        Self::ensure_success(&response)
+    }
+
+    /// PATCH rows of `table` matching a PostgREST `filter` (e.g. `key_id=eq.k1`).
+    pub async fn update_rows(
+        &self,
+        table: &str,
+        filter: &str,
+        patch: &serde_json::Value,
+    ) -> Result<(), SupabaseError> {
+        let url = format!("{}/rest/v1/{table}?{filter}", self.base_url);
+        let response = self
+            .authorized(self.http.patch(url))
+            .header("Prefer", "return=minimal")
+            .json(patch)
+            .send()
+            .await
+            .map_err(|err| SupabaseError::Request(err.to_string()))?;
+        Self::ensure_success(&response)
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        request
+            .header("apikey", &self.service_key)
+            .header("Authorization", format!("Bearer {}", self.service_key))
+    }
+
+    fn ensure_success(response: &reqwest::Response) -> Result<(), SupabaseError> {
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(SupabaseError::Request(format!(
+                "HTTP {}",
+                response.status()
+            )))
+        }
+    }
 }
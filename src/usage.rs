@@ -0,0 +1,721 @@
+use crate::{models::ApiError, security::AuthContext};
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use thiserror::Error;
+use tracing::warn;
+
+/// Monthly counters live this long in Redis, so last month stays readable.
+const REDIS_RETENTION_SECS: i64 = 400 * 24 * 3600;
+
+/// Add one to field `ARGV[1]` of `KEYS[1]` unless that would pass the limit
+/// `ARGV[2]`; returns 1 when the unit was taken.
+const RESERVE_SCRIPT: &str = r#"
+local count = redis.call('HINCRBY', KEYS[1], ARGV[1], 1)
+if count > tonumber(ARGV[2]) then
+  redis.call('HINCRBY', KEYS[1], ARGV[1], -1)
+  return 0
+end
+redis.call('EXPIRE', KEYS[1], ARGV[3])
+return 1
+"#;
+
+/// What an org is billed for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    ListingsPublished,
+    DryRuns,
+    LlmCalls,
+    EbayCalls,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [
+        Metric::ListingsPublished,
+        Metric::DryRuns,
+        Metric::LlmCalls,
+        Metric::EbayCalls,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::ListingsPublished => "listings_published",
+            Metric::DryRuns => "dry_runs",
+            Metric::LlmCalls => "llm_calls",
+            Metric::EbayCalls => "ebay_calls",
+        }
+    }
+
+    /// What a listing run is charged against, besides the calls it makes.
+    pub fn for_listing(dry_run: bool) -> Self {
+        if dry_run {
+            Metric::DryRuns
+        } else {
+            Metric::ListingsPublished
+        }
+    }
+}
+
+/// One org's counts for a month.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub listings_published: u64,
+    #[serde(default)]
+    pub dry_runs: u64,
+    #[serde(default)]
+    pub llm_calls: u64,
+    #[serde(default)]
+    pub ebay_calls: u64,
+}
+
+impl Usage {
+    pub fn get(&self, metric: Metric) -> u64 {
+        match metric {
+            Metric::ListingsPublished => self.listings_published,
+            Metric::DryRuns => self.dry_runs,
+            Metric::LlmCalls => self.llm_calls,
+            Metric::EbayCalls => self.ebay_calls,
+        }
+    }
+
+    fn get_mut(&mut self, metric: Metric) -> &mut u64 {
+        match metric {
+            Metric::ListingsPublished => &mut self.listings_published,
+            Metric::DryRuns => &mut self.dry_runs,
+            Metric::LlmCalls => &mut self.llm_calls,
+            Metric::EbayCalls => &mut self.ebay_calls,
+        }
+    }
+
+    pub fn add(&mut self, metric: Metric, count: u64) {
+        *self.get_mut(metric) += count;
+    }
+
+    fn merge(&mut self, other: &Usage) {
+        for metric in Metric::ALL {
+            self.add(metric, other.get(metric));
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Usage::default()
+    }
+}
+
+#[derive(Default)]
+struct CallCounts {
+    llm: AtomicU64,
+    ebay: AtomicU64,
+}
+
+tokio::task_local! {
+    static CALLS: Arc<CallCounts>;
+}
+
+/// Count an LLM gateway request against the run being metered, if any.
+pub fn record_llm_call() {
+    let _ = CALLS.try_with(|calls| calls.llm.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Count an eBay API request against the run being metered, if any.
+pub fn record_ebay_call() {
+    let _ = CALLS.try_with(|calls| calls.ebay.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Run `fut`, returning its output and the LLM and eBay calls it made.
+pub async fn metered<F: Future>(fut: F) -> (F::Output, Usage) {
+    let calls = Arc::new(CallCounts::default());
+    let output = CALLS.scope(calls.clone(), fut).await;
+    let usage = Usage {
+        llm_calls: calls.llm.load(Ordering::Relaxed),
+        ebay_calls: calls.ebay.load(Ordering::Relaxed),
+        ..Usage::default()
+    };
+    (output, usage)
+}
+
+/// Monthly limits per metric; metrics left out are unlimited.
+pub type Quotas = BTreeMap<Metric, u64>;
+
+/// Billing period, e.g. `2026-10` (UTC calendar month).
+pub fn period_of(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m").to_string()
+}
+
+/// Start of the month after `at`, when counters start over.
+pub fn period_end(at: DateTime<Utc>) -> DateTime<Utc> {
+    let (year, month) = if at.month() == 12 {
+        (at.year() + 1, 1)
+    } else {
+        (at.year(), at.month() + 1)
+    };
+    let start = NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .expect("first of the month is a valid date");
+    Utc.from_utc_datetime(&start)
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum UsageError {
+    #[error("quota_exceeded")]
+    QuotaExceeded {
+        metric: Metric,
+        limit: u64,
+        resets_at: DateTime<Utc>,
+        status: StatusCode,
+    },
+    #[error("usage_unavailable")]
+    Unavailable(String),
+}
+
+impl IntoResponse for UsageError {
+    fn into_response(self) -> Response {
+        match self {
+            UsageError::QuotaExceeded {
+                metric,
+                limit,
+                resets_at,
+                status,
+            } => {
+                let payload = ApiError {
+                    error: "quota_exceeded".to_string(),
+                    detail: Some(format!(
+                        "{} quota of {limit} reached; resets at {}",
+                        metric.as_str(),
+                        resets_at.to_rfc3339()
+                    )),
+                };
+                let mut response = (status, Json(payload)).into_response();
+                let retry = (resets_at - Utc::now()).num_seconds().max(1);
+                if let Ok(value) = HeaderValue::from_str(&retry.to_string()) {
+                    response.headers_mut().insert(RETRY_AFTER, value);
+                }
+                response
+            }
+            UsageError::Unavailable(_) => {
+                let payload = ApiError {
+                    error: "usage_unavailable".to_string(),
+                    detail: None,
+                };
+                (StatusCode::SERVICE_UNAVAILABLE, Json(payload)).into_response()
+            }
+        }
+    }
+}
+
+/// Where monthly counters are kept: in process, or in Redis hashes under
+/// `hermes:usage:{org_id}:{period}` so every replica adds to the same count.
+#[derive(Clone)]
+enum UsageStore {
+    Memory(Arc<Mutex<HashMap<String, Usage>>>),
+    Redis(redis::Client),
+}
+
+impl UsageStore {
+    async fn add(&self, org_id: &str, period: &str, usage: &Usage) -> Result<(), String> {
+        match self {
+            Self::Memory(counts) => {
+                counts
+                    .lock()
+                    .expect("usage lock")
+                    .entry(format!("{org_id}:{period}"))
+                    .or_default()
+                    .merge(usage);
+                Ok(())
+            }
+            Self::Redis(client) => {
+                let key = format!("hermes:usage:{org_id}:{period}");
+                let mut pipe = redis::pipe();
+                for metric in Metric::ALL {
+                    let count = usage.get(metric);
+                    if count > 0 {
+                        pipe.hincr(&key, metric.as_str(), count).ignore();
+                    }
+                }
+                pipe.expire(&key, REDIS_RETENTION_SECS).ignore();
+                let mut conn = client
+                    .get_multiplexed_async_connection()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                pipe.query_async::<()>(&mut conn)
+                    .await
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    /// Atomically add one `metric` unless the count is already at `limit`.
+    async fn reserve(
+        &self,
+        org_id: &str,
+        period: &str,
+        metric: Metric,
+        limit: u64,
+    ) -> Result<bool, String> {
+        match self {
+            Self::Memory(counts) => {
+                let mut counts = counts.lock().expect("usage lock");
+                let usage = counts.entry(format!("{org_id}:{period}")).or_default();
+                if usage.get(metric) >= limit {
+                    return Ok(false);
+                }
+                usage.add(metric, 1);
+                Ok(true)
+            }
+            Self::Redis(client) => {
+                let mut conn = client
+                    .get_multiplexed_async_connection()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                redis::Script::new(RESERVE_SCRIPT)
+                    .key(format!("hermes:usage:{org_id}:{period}"))
+                    .arg(metric.as_str())
+                    .arg(limit)
+                    .arg(REDIS_RETENTION_SECS)
+                    .invoke_async::<i64>(&mut conn)
+                    .await
+                    .map(|taken| taken == 1)
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    /// Give back one `metric` taken by [`Self::reserve`].
+    async fn release(&self, org_id: &str, period: &str, metric: Metric) -> Result<(), String> {
+        match self {
+            Self::Memory(counts) => {
+                if let Some(usage) = counts
+                    .lock()
+                    .expect("usage lock")
+                    .get_mut(&format!("{org_id}:{period}"))
+                {
+                    let count = usage.get_mut(metric);
+                    *count = count.saturating_sub(1);
+                }
+                Ok(())
+            }
+            Self::Redis(client) => {
+                let mut conn = client
+                    .get_multiplexed_async_connection()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                redis::cmd("HINCRBY")
+                    .arg(format!("hermes:usage:{org_id}:{period}"))
+                    .arg(metric.as_str())
+                    .arg(-1)
+                    .query_async::<i64>(&mut conn)
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    async fn get(&self, org_id: &str, period: &str) -> Result<Usage, String> {
+        match self {
+            Self::Memory(counts) => Ok(counts
+                .lock()
+                .expect("usage lock")
+                .get(&format!("{org_id}:{period}"))
+                .copied()
+                .unwrap_or_default()),
+            Self::Redis(client) => {
+                let mut conn = client
+                    .get_multiplexed_async_connection()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                let fields: HashMap<String, u64> = redis::cmd("HGETALL")
+                    .arg(format!("hermes:usage:{org_id}:{period}"))
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                let mut usage = Usage::default();
+                for metric in Metric::ALL {
+                    usage.add(metric, fields.get(metric.as_str()).copied().unwrap_or(0));
+                }
+                Ok(usage)
+            }
+        }
+    }
+}
+
+/// A month of usage measured against the org's plan.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    pub org_id: String,
+    pub period: String,
+    pub plan: String,
+    pub usage: Usage,
+    /// Only metrics with a limit appear here.
+    pub quotas: Quotas,
+    pub resets_at: DateTime<Utc>,
+}
+
+/// Per-org monthly metering, with quotas looked up by the org's plan (the
+/// same name that picks its rate limits).
+#[derive(Clone)]
+pub struct UsageMeter {
+    store: UsageStore,
+    quotas: Arc<HashMap<String, Quotas>>,
+    exceeded_status: StatusCode,
+}
+
+impl UsageMeter {
+    /// Counters go to Redis when `REDIS_URL` is set (`USAGE_BACKEND=memory`
+    /// keeps them local). `USAGE_QUOTAS` maps plan names to monthly limits,
+    /// e.g. `{"default":{"listings_published":100},"pro":{"listings_published":5000}}`;
+    /// orgs on a plan without an entry get `default`, and without any entry
+    /// usage is only metered. Exceeding a quota answers
+    /// `USAGE_QUOTA_STATUS` (`429` default, or `402`).
+    pub fn from_env(redis: Option<redis::Client>) -> Self {
+        let backend = std::env::var("USAGE_BACKEND")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let store = match redis {
+            Some(client) if backend != "memory" => UsageStore::Redis(client),
+            _ => UsageStore::Memory(Arc::default()),
+        };
+        let quotas = match std::env::var("USAGE_QUOTAS") {
+            Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw).unwrap_or_else(|err| {
+                warn!(target = "hermes.api", error = %err, "usage_quotas_invalid");
+                HashMap::new()
+            }),
+            _ => HashMap::new(),
+        };
+        let exceeded_status = std::env::var("USAGE_QUOTA_STATUS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u16>().ok())
+            .filter(|v| matches!(v, 402 | 429))
+            .and_then(|v| StatusCode::from_u16(v).ok())
+            .unwrap_or(StatusCode::TOO_MANY_REQUESTS);
+        Self::new(store, quotas, exceeded_status)
+    }
+
+    fn new(
+        store: UsageStore,
+        quotas: HashMap<String, Quotas>,
+        exceeded_status: StatusCode,
+    ) -> Self {
+        Self {
+            store,
+            quotas: Arc::new(quotas),
+            exceeded_status,
+        }
+    }
+
+    /// An in-memory meter with the given per-plan quotas.
+    #[cfg(test)]
+    pub fn for_tests(quotas: &[(&str, Quotas)], exceeded_status: StatusCode) -> Self {
+        let quotas = quotas
+            .iter()
+            .map(|(plan, quotas)| (plan.to_string(), quotas.clone()))
+            .collect();
+        Self::new(UsageStore::Memory(Arc::default()), quotas, exceeded_status)
+    }
+
+    fn plan_name(plan: Option<&str>) -> &str {
+        plan.unwrap_or("default")
+    }
+
+    fn quotas_for(&self, plan: Option<&str>) -> Quotas {
+        let plan = Self::plan_name(plan);
+        self.quotas
+            .get(plan)
+            .or_else(|| self.quotas.get("default"))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Refuse the request if any of `metrics` is already at its monthly
+    /// limit. A limited org whose counters cannot be read is refused too,
+    /// since letting it through could bill past its quota.
+    pub async fn check(&self, context: &AuthContext, metrics: &[Metric]) -> Result<(), UsageError> {
+        let quotas = self.quotas_for(context.plan.as_deref());
+        if !metrics.iter().any(|metric| quotas.contains_key(metric)) {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let usage = self
+            .store
+            .get(&context.org_id, &period_of(now))
+            .await
+            .map_err(|err| self.unavailable(context, err))?;
+        for metric in metrics {
+            if let Some(&limit) = quotas.get(metric)
+                && usage.get(*metric) >= limit
+            {
+                return Err(UsageError::QuotaExceeded {
+                    metric: *metric,
+                    limit,
+                    resets_at: period_end(now),
+                    status: self.exceeded_status,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn unavailable(&self, context: &AuthContext, err: String) -> UsageError {
+        warn!(target = "hermes.api", org_id = %context.org_id, error = %err, "usage_store_unavailable");
+        UsageError::Unavailable(err)
+    }
+
+    /// Run `fut` with one `charge` taken from the org's quota up front, so
+    /// concurrent runs cannot all pass a check and overshoot it. The unit is
+    /// given back unless `succeeded`; the calls `fut` made are added after.
+    /// Metrics without a limit are simply charged on success.
+    pub async fn track_reserved<F: Future>(
+        &self,
+        context: &AuthContext,
+        charge: Metric,
+        fut: F,
+        succeeded: impl FnOnce(&F::Output) -> bool,
+    ) -> Result<F::Output, UsageError> {
+        let now = Utc::now();
+        let period = period_of(now);
+        let limit = self
+            .quotas_for(context.plan.as_deref())
+            .get(&charge)
+            .copied();
+        if let Some(limit) = limit {
+            let taken = self
+                .store
+                .reserve(&context.org_id, &period, charge, limit)
+                .await
+                .map_err(|err| self.unavailable(context, err))?;
+            if !taken {
+                return Err(UsageError::QuotaExceeded {
+                    metric: charge,
+                    limit,
+                    resets_at: period_end(now),
+                    status: self.exceeded_status,
+                });
+            }
+        }
+        let (output, mut usage) = metered(fut).await;
+        match (limit.is_some(), succeeded(&output)) {
+            (true, false) => {
+                if let Err(err) = self.store.release(&context.org_id, &period, charge).await {
+                    warn!(target = "hermes.api", org_id = %context.org_id, error = %err, "usage_release_failed");
+                }
+            }
+            (false, true) => usage.add(charge, 1),
+            _ => {}
+        }
+        self.record(&context.org_id, &usage).await;
+        Ok(output)
+    }
+
+    /// Add `usage` to the org's current month.
+    pub async fn record(&self, org_id: &str, usage: &Usage) {
+        if usage.is_empty() {
+            return;
+        }
+        if let Err(err) = self.store.add(org_id, &period_of(Utc::now()), usage).await {
+            warn!(target = "hermes.api", org_id = %org_id, error = %err, "usage_record_failed");
+        }
+    }
+
+    /// Run `fut` and charge `org_id` for the calls it made, plus whatever
+    /// `outcome` adds once it has finished.
+    pub async fn track<F: Future>(
+        &self,
+        org_id: &str,
+        fut: F,
+        outcome: impl FnOnce(&F::Output) -> Option<Metric>,
+    ) -> F::Output {
+        let (output, mut usage) = metered(fut).await;
+        if let Some(metric) = outcome(&output) {
+            usage.add(metric, 1);
+        }
+        self.record(org_id, &usage).await;
+        output
+    }
+
+    /// The org's usage for `period` (default: this month) against its plan.
+    pub async fn report(
+        &self,
+        context: &AuthContext,
+        period: Option<&str>,
+    ) -> Result<UsageReport, UsageError> {
+        let now = Utc::now();
+        let period = period.map_or_else(|| period_of(now), str::to_string);
+        let usage = self
+            .store
+            .get(&context.org_id, &period)
+            .await
+            .map_err(UsageError::Unavailable)?;
+        let resets_at = NaiveDate::parse_from_str(&format!("{period}-01"), "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|start| period_end(Utc.from_utc_datetime(&start)))
+            .unwrap_or_else(|| period_end(now));
+        Ok(UsageReport {
+            org_id: context.org_id.clone(),
+            period,
+            plan: Self::plan_name(context.plan.as_deref()).to_string(),
+            usage,
+            quotas: self.quotas_for(context.plan.as_deref()),
+            resets_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(plan: Option<&str>) -> AuthContext {
+        AuthContext {
+            org_id: "acme".into(),
+            api_key_id: "key".into(),
+            scopes: Vec::new(),
+            plan: plan.map(str::to_string),
+            request_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_are_charged_for_their_calls() {
+        let meter = UsageMeter::for_tests(&[], StatusCode::TOO_MANY_REQUESTS);
+        let published = meter
+            .track(
+                "acme",
+                async {
+                    record_llm_call();
+                    record_ebay_call();
+                    record_ebay_call();
+                    Ok::<_, ()>(())
+                },
+                |result| result.is_ok().then_some(Metric::ListingsPublished),
+            )
+            .await;
+        assert!(published.is_ok());
+        // Calls outside a tracked run are not charged to anyone.
+        record_llm_call();
+
+        let report = meter.report(&context(None), None).await.unwrap();
+        assert_eq!(
+            report.usage,
+            Usage {
+                listings_published: 1,
+                dry_runs: 0,
+                llm_calls: 1,
+                ebay_calls: 2,
+            }
+        );
+        assert_eq!(report.plan, "default");
+        assert!(report.quotas.is_empty());
+    }
+
+    #[tokio::test]
+    async fn quotas_follow_the_org_plan() {
+        // The `USAGE_QUOTAS` format.
+        let quotas: HashMap<String, Quotas> =
+            serde_json::from_str(r#"{"default":{"dry_runs":1},"pro":{"dry_runs":3}}"#).unwrap();
+        let meter = UsageMeter::new(
+            UsageStore::Memory(Arc::default()),
+            quotas,
+            StatusCode::PAYMENT_REQUIRED,
+        );
+        let dry_run = Usage {
+            dry_runs: 2,
+            ..Usage::default()
+        };
+        meter.record("acme", &dry_run).await;
+
+        let err = meter
+            .check(&context(None), &[Metric::DryRuns])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            UsageError::QuotaExceeded {
+                metric: Metric::DryRuns,
+                limit: 1,
+                ..
+            }
+        ));
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        assert!(response.headers().contains_key(RETRY_AFTER));
+
+        assert!(
+            meter
+                .check(&context(Some("pro")), &[Metric::DryRuns])
+                .await
+                .is_ok()
+        );
+        // Unknown plans get the default quotas; unlimited metrics always pass.
+        assert!(
+            meter
+                .check(&context(Some("gold")), &[Metric::DryRuns])
+                .await
+                .is_err()
+        );
+        assert!(
+            meter
+                .check(&context(None), &[Metric::ListingsPublished])
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn periods_roll_over_at_the_start_of_the_month() {
+        let at = Utc.with_ymd_and_hms(2026, 12, 31, 23, 59, 0).unwrap();
+        assert_eq!(period_of(at), "2026-12");
+        assert_eq!(
+            period_end(at),
+            Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_runs_cannot_overshoot_a_quota() {
+        let quotas = HashMap::from([(
+            "default".to_string(),
+            Quotas::from([(Metric::ListingsPublished, 2)]),
+        )]);
+        let meter = UsageMeter::new(
+            UsageStore::Memory(Arc::default()),
+            quotas,
+            StatusCode::TOO_MANY_REQUESTS,
+        );
+        let ctx = context(None);
+        let run = |ok: bool| {
+            meter.track_reserved(
+                &ctx,
+                Metric::ListingsPublished,
+                async move {
+                    tokio::task::yield_now().await;
+                    ok
+                },
+                |ok| *ok,
+            )
+        };
+
+        // A failed run gives its unit back.
+        assert!(run(false).await.is_ok());
+        let outcomes = tokio::join!(run(true), run(true), run(true), run(true));
+        let outcomes = [outcomes.0, outcomes.1, outcomes.2, outcomes.3];
+        assert_eq!(outcomes.iter().filter(|outcome| outcome.is_ok()).count(), 2);
+        let report = meter.report(&ctx, None).await.unwrap();
+        assert_eq!(report.usage.listings_published, 2);
+    }
+}